@@ -0,0 +1,191 @@
+use std::convert::TryInto;
+
+use crate::device;
+use crate::save_state;
+use crate::serial;
+
+// Register offsets, selected by the RS input (usually wired to A0)
+const CONTROL_STATUS_REGISTER: u16 = 0;
+const DATA_REGISTER: u16 = 1;
+
+// Status register bits
+pub const STATUS_RECEIVE_DATA_REGISTER_FULL: u8 = 0x01;
+pub const STATUS_TRANSMIT_DATA_REGISTER_EMPTY: u8 = 0x02;
+pub const STATUS_INTERRUPT_REQUEST: u8 = 0x80;
+
+// Control register fields
+const CONTROL_COUNTER_DIVIDE_MASK: u8 = 0x03;
+const CONTROL_MASTER_RESET: u8 = 0x03;
+const CONTROL_TRANSMIT_MASK: u8 = 0x60;
+const CONTROL_TRANSMIT_INTERRUPT_ENABLED: u8 = 0x20;
+const CONTROL_RECEIVE_INTERRUPT_ENABLED: u8 = 0x80;
+
+/// An MC6850 asynchronous communications interface adapter
+///
+/// The ACIA takes two addresses: the control (write) and status (read) register, then the
+/// transmit (write) and receive (read) data register. Bytes are exchanged with a
+/// `serial::SerialPort`. By default every byte is transferred as soon as it can be, set
+/// `set_character_cycles` to pace the transfers like a real baud rate.
+pub struct Acia {
+    port: Box<dyn serial::SerialPort>,
+    control: u8,
+    receive_data: u8,
+    receive_data_register_full: bool,
+    /// Held in master reset until the control register is written with another counter divide
+    in_reset: bool,
+    /// The cycles it takes to shift one character in or out, 0 to transfer bytes immediately
+    character_cycles: u64,
+    /// The cycles left until the transmitter is ready for the next byte
+    transmit_cycles: u64,
+    /// The cycles left until the next byte can be received
+    receive_cycles: u64
+}
+
+impl Acia {
+    pub fn new(port: Box<dyn serial::SerialPort>) -> Acia {
+        Acia {
+            port,
+            control: CONTROL_MASTER_RESET,
+            receive_data: 0,
+            receive_data_register_full: false,
+            in_reset: true,
+            character_cycles: 0,
+            transmit_cycles: 0,
+            receive_cycles: 0
+        }
+    }
+
+    /// Pace the transfers at `cycles` per character, e.g. 10 bits at 9600 baud with a 1MHz clock
+    /// take about 1042 cycles
+    pub fn set_character_cycles(&mut self, cycles: u64) {
+        self.character_cycles = cycles;
+    }
+
+    pub fn get_character_cycles(&self) -> u64 {
+        self.character_cycles
+    }
+
+    pub fn get_control(&self) -> u8 {
+        self.control
+    }
+
+    pub fn get_status(&self) -> u8 {
+        let mut status: u8 = 0;
+
+        if self.receive_data_register_full {
+            status |= STATUS_RECEIVE_DATA_REGISTER_FULL;
+        }
+
+        if !self.in_reset && self.transmit_cycles == 0 {
+            status |= STATUS_TRANSMIT_DATA_REGISTER_EMPTY;
+        }
+
+        if device::Device::irq(self) {
+            status |= STATUS_INTERRUPT_REQUEST;
+        }
+
+        status
+    }
+
+    /// Replace the other end of the serial line
+    pub fn set_port(&mut self, port: Box<dyn serial::SerialPort>) {
+        self.port = port;
+    }
+
+    /// Pull the next byte from the port if the receive register is free and a character time
+    /// passed since the last one
+    fn poll_receiver(&mut self) {
+        if self.in_reset || self.receive_data_register_full || self.receive_cycles > 0 {
+            return;
+        }
+
+        if let Some(byte) = self.port.receive() {
+            self.receive_data = byte;
+            self.receive_data_register_full = true;
+            self.receive_cycles = self.character_cycles;
+        }
+    }
+}
+
+impl device::Device for Acia {
+    fn name(&self) -> &str {
+        "acia"
+    }
+
+    fn size(&self) -> u16 {
+        2
+    }
+
+    fn read(&mut self, offset: u16) -> u8 {
+        if offset == DATA_REGISTER {
+            self.receive_data_register_full = false;
+            return self.receive_data;
+        }
+
+        self.get_status()
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            CONTROL_STATUS_REGISTER => self.get_status(),
+            _ => self.receive_data
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if offset == CONTROL_STATUS_REGISTER {
+            self.control = value;
+
+            if value & CONTROL_COUNTER_DIVIDE_MASK == CONTROL_MASTER_RESET {
+                self.in_reset = true;
+                self.receive_data_register_full = false;
+                self.transmit_cycles = 0;
+                self.receive_cycles = 0;
+            } else {
+                self.in_reset = false;
+            }
+        } else if !self.in_reset {
+            // The byte goes out right away, only the status shows the time it takes
+            self.port.transmit(value);
+            self.transmit_cycles = self.character_cycles;
+        }
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        self.transmit_cycles = self.transmit_cycles.saturating_sub(cycles);
+        self.receive_cycles = self.receive_cycles.saturating_sub(cycles);
+        self.poll_receiver();
+    }
+
+    fn irq(&self) -> bool {
+        let receive_interrupt: bool = self.control & CONTROL_RECEIVE_INTERRUPT_ENABLED != 0 && self.receive_data_register_full;
+        let transmit_interrupt: bool = self.control & CONTROL_TRANSMIT_MASK == CONTROL_TRANSMIT_INTERRUPT_ENABLED
+            && !self.in_reset && self.transmit_cycles == 0;
+
+        receive_interrupt || transmit_interrupt
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = vec![self.control, self.receive_data, self.receive_data_register_full as u8, self.in_reset as u8];
+
+        data.extend_from_slice(&self.transmit_cycles.to_be_bytes());
+        data.extend_from_slice(&self.receive_cycles.to_be_bytes());
+
+        data
+    }
+
+    fn restore(&mut self, data: &[u8]) -> Result<(), save_state::SaveStateError> {
+        if data.len() < 20 {
+            return Err(save_state::SaveStateError::Truncated);
+        }
+
+        self.control = data[0];
+        self.receive_data = data[1];
+        self.receive_data_register_full = data[2] != 0;
+        self.in_reset = data[3] != 0;
+        self.transmit_cycles = u64::from_be_bytes(data[4..12].try_into().unwrap());
+        self.receive_cycles = u64::from_be_bytes(data[12..20].try_into().unwrap());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,441 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::memory_manager;
+use crate::number;
+use crate::processor;
+
+/// Whether the processor read or wrote a byte
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AccessKind {
+    Read,
+    Write
+}
+
+/// A single byte the processor accessed while emulating an instruction
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MemoryAccess {
+    pub kind: AccessKind,
+    pub address: u16,
+    /// The value read or written
+    pub value: u8,
+    /// The value the byte had before the access (equal to `value` for reads)
+    pub previous_value: u8
+}
+
+/// The accesses a watchpoint triggers on
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+    /// A write which changes the value of the byte
+    Change
+}
+
+/// Identifies the breakpoint or watchpoint which stopped a run
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BreakpointHit {
+    pub id: u32,
+    /// The address of the instruction about to run (breakpoints) or which made the access
+    /// (watchpoints)
+    pub program_counter: u16,
+    /// The access which triggered a watchpoint, `None` for execution breakpoints
+    pub access: Option<MemoryAccess>
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum RegisterOperand {
+    AccumulatorA,
+    AccumulatorB,
+    IndexRegister,
+    ProgramCounter,
+    StackPointer,
+    ConditionCodeRegister
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Operator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Add,
+    Subtract,
+    BitwiseAnd
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Expression {
+    Number(u32),
+    Register(RegisterOperand),
+    /// A condition code flag, by its bit index
+    Flag(u8),
+    /// The byte at the address the inner expression evaluates to
+    Memory(Box<Expression>),
+    Not(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>)
+}
+
+/// A condition over the registers, flags and memory, such as `A == $FF && Z` or `[$80] > 3`
+///
+/// Registers are `A`, `B`, `X`, `PC`, `SP` and `CCR`, flags are `H`, `I`, `N`, `Z`, `V` and `C`,
+/// and `[address]` reads a byte of memory. Numbers are decimal, `$` or `0x` prefixed hexadecimal,
+/// or `%` or `0b` prefixed binary. Comparisons, `&&`, `||`, `!`, `+`, `-`, `&` and parentheses
+/// work like in C.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Condition {
+    source: String,
+    expression: Expression
+}
+
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    position: usize
+}
+
+/// Split a condition into numbers, names and operators
+fn tokenize(text: &str) -> Result<Vec<&str>, String> {
+    let mut tokens: Vec<&str> = Vec::new();
+    let bytes: &[u8] = text.as_bytes();
+    let mut start: usize;
+    let mut index: usize = 0;
+
+    while index < bytes.len() {
+        start = index;
+
+        if bytes[index].is_ascii_whitespace() {
+            index += 1;
+            continue;
+        } else if bytes[index].is_ascii_alphanumeric() || b"$%_".contains(&bytes[index]) {
+            index += 1;
+            while index < bytes.len() && (bytes[index].is_ascii_alphanumeric() || bytes[index] == b'_') {
+                index += 1;
+            }
+        } else if ["&&", "||", "==", "!=", "<=", ">="].iter().any(|operator| text[index..].starts_with(operator)) {
+            index += 2;
+        } else if b"<>!+-&()[]".contains(&bytes[index]) {
+            index += 1;
+        } else {
+            return Err(format!("Unexpected character '{}' in condition", text[index..].chars().next().unwrap()));
+        }
+
+        tokens.push(&text[start..index]);
+    }
+
+    Ok(tokens)
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("Expected '{}' but found '{}'", expected, token)),
+            None => Err(format!("Expected '{}'", expected))
+        }
+    }
+
+    /// Parse binary operators of one precedence level, falling back to `operand` for the operands
+    fn parse_binary<F>(&mut self, operators: &[(&str, Operator)], operand: F) -> Result<Expression, String> where
+        F: Fn(&mut Parser<'a>) -> Result<Expression, String> {
+        let mut left: Expression = operand(self)?;
+        let mut right: Expression;
+
+        while let Some((_, operator)) = operators.iter().find(|(token, _)| Some(*token) == self.peek()) {
+            self.position += 1;
+            right = operand(self)?;
+            left = Expression::Binary(*operator, Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, String> {
+        self.parse_binary(&[("||", Operator::Or)], Parser::parse_and)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, String> {
+        self.parse_binary(&[("&&", Operator::And)], Parser::parse_comparison)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression, String> {
+        self.parse_binary(&[("==", Operator::Equal), ("!=", Operator::NotEqual), ("<", Operator::Less),
+                ("<=", Operator::LessEqual), (">", Operator::Greater), (">=", Operator::GreaterEqual)], Parser::parse_sum)
+    }
+
+    fn parse_sum(&mut self) -> Result<Expression, String> {
+        self.parse_binary(&[("+", Operator::Add), ("-", Operator::Subtract), ("&", Operator::BitwiseAnd)], Parser::parse_unary)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, String> {
+        let expression: Expression;
+
+        match self.next() {
+            Some("!") => Ok(Expression::Not(Box::new(self.parse_unary()?))),
+            Some("(") => {
+                expression = self.parse_or()?;
+                self.expect(")")?;
+                Ok(expression)
+            },
+            Some("[") => {
+                expression = self.parse_or()?;
+                self.expect("]")?;
+                Ok(Expression::Memory(Box::new(expression)))
+            },
+            Some(token) => parse_value(token),
+            None => Err(String::from("Unexpected end of condition"))
+        }
+    }
+}
+
+fn parse_value(token: &str) -> Result<Expression, String> {
+    let number: Option<u32>;
+
+    match token.to_ascii_uppercase().as_str() {
+        "A" => return Ok(Expression::Register(RegisterOperand::AccumulatorA)),
+        "B" => return Ok(Expression::Register(RegisterOperand::AccumulatorB)),
+        "X" => return Ok(Expression::Register(RegisterOperand::IndexRegister)),
+        "PC" => return Ok(Expression::Register(RegisterOperand::ProgramCounter)),
+        "SP" => return Ok(Expression::Register(RegisterOperand::StackPointer)),
+        "CCR" | "CC" => return Ok(Expression::Register(RegisterOperand::ConditionCodeRegister)),
+        "C" => return Ok(Expression::Flag(0)),
+        "V" => return Ok(Expression::Flag(1)),
+        "Z" => return Ok(Expression::Flag(2)),
+        "N" => return Ok(Expression::Flag(3)),
+        "I" => return Ok(Expression::Flag(4)),
+        "H" => return Ok(Expression::Flag(5)),
+        _ => {}
+    }
+
+    number = number::parse_number(token).and_then(|value| u32::try_from(value).ok());
+
+    number.map(Expression::Number).ok_or(format!("Unknown value '{}' in condition", token))
+}
+
+impl Expression {
+    fn evaluate(&self, state: &processor::ProcessorState, memory: &memory_manager::MemoryManager) -> u32 {
+        let left: u32;
+        let right: u32;
+
+        match self {
+            Expression::Number(value) => *value,
+            Expression::Register(register) => match register {
+                RegisterOperand::AccumulatorA => state.accumulator_a as u32,
+                RegisterOperand::AccumulatorB => state.accumulator_b as u32,
+                RegisterOperand::IndexRegister => state.index_register as u32,
+                RegisterOperand::ProgramCounter => state.program_counter as u32,
+                RegisterOperand::StackPointer => state.stack_pointer as u32,
+                RegisterOperand::ConditionCodeRegister => state.condition_code_register as u32
+            },
+            Expression::Flag(index) => crate::get_bit!(state.condition_code_register, *index) as u32,
+            Expression::Memory(address) => memory.read(address.evaluate(state, memory) as u16, 1)[0] as u32,
+            Expression::Not(operand) => (operand.evaluate(state, memory) == 0) as u32,
+            Expression::Binary(operator, left_operand, right_operand) => {
+                left = left_operand.evaluate(state, memory);
+
+                // Short circuit like C does
+                match operator {
+                    Operator::And if left == 0 => return 0,
+                    Operator::Or if left != 0 => return 1,
+                    _ => {}
+                }
+
+                right = right_operand.evaluate(state, memory);
+
+                match operator {
+                    Operator::Or | Operator::And => (right != 0) as u32,
+                    Operator::Equal => (left == right) as u32,
+                    Operator::NotEqual => (left != right) as u32,
+                    Operator::Less => (left < right) as u32,
+                    Operator::LessEqual => (left <= right) as u32,
+                    Operator::Greater => (left > right) as u32,
+                    Operator::GreaterEqual => (left >= right) as u32,
+                    Operator::Add => left.wrapping_add(right),
+                    Operator::Subtract => left.wrapping_sub(right),
+                    Operator::BitwiseAnd => left & right
+                }
+            }
+        }
+    }
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Condition, String> {
+        let mut parser = Parser { tokens: tokenize(text)?, position: 0 };
+        let expression: Expression = parser.parse_or()?;
+
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected '{}' in condition", token));
+        }
+
+        Ok(Condition { source: String::from(text.trim()), expression })
+    }
+
+    /// Whether the condition holds, i.e. evaluates to a non zero value
+    pub fn evaluate(&self, state: &processor::ProcessorState, memory: &memory_manager::MemoryManager) -> bool {
+        self.expression.evaluate(state, memory) != 0
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.source)
+    }
+}
+
+/// Stops a run before the instruction at an address is emulated
+#[derive(Clone, Debug)]
+pub struct Breakpoint {
+    pub id: u32,
+    pub address: u16,
+    pub condition: Option<Condition>,
+    pub enabled: bool
+}
+
+/// Stops a run after an instruction accessed a byte in an (inclusive) address range
+#[derive(Clone, Debug)]
+pub struct Watchpoint {
+    pub id: u32,
+    pub start: u16,
+    pub end: u16,
+    pub kind: WatchKind,
+    pub condition: Option<Condition>,
+    pub enabled: bool
+}
+
+impl Watchpoint {
+    fn matches(&self, access: &MemoryAccess) -> bool {
+        if access.address < self.start || access.address > self.end {
+            return false;
+        }
+
+        match self.kind {
+            WatchKind::Read => access.kind == AccessKind::Read,
+            WatchKind::Write => access.kind == AccessKind::Write,
+            WatchKind::ReadWrite => true,
+            WatchKind::Change => access.kind == AccessKind::Write && access.value != access.previous_value
+        }
+    }
+}
+
+fn condition_holds(condition: &Option<Condition>, state: &processor::ProcessorState, memory: &memory_manager::MemoryManager) -> bool {
+    condition.as_ref().is_none_or(|condition| condition.evaluate(state, memory))
+}
+
+/// The breakpoints and watchpoints of a processor. Both share a single sequence of ids.
+#[derive(Default)]
+pub struct BreakpointManager {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    next_id: u32
+}
+
+impl BreakpointManager {
+    pub fn new() -> BreakpointManager {
+        BreakpointManager::default()
+    }
+
+    fn allocate_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Add an execution breakpoint, returning its id
+    pub fn add_breakpoint(&mut self, address: u16, condition: Option<Condition>) -> u32 {
+        let id: u32 = self.allocate_id();
+
+        self.breakpoints.push(Breakpoint { id, address, condition, enabled: true });
+        id
+    }
+
+    /// Add a watchpoint on the bytes from `start` to `end` (inclusive), returning its id
+    pub fn add_watchpoint(&mut self, start: u16, end: u16, kind: WatchKind, condition: Option<Condition>) -> u32 {
+        let id: u32 = self.allocate_id();
+
+        self.watchpoints.push(Watchpoint { id, start, end, kind, condition, enabled: true });
+        id
+    }
+
+    /// Remove a breakpoint or watchpoint by id. Returns false if there is none with that id.
+    pub fn remove(&mut self, id: u32) -> bool {
+        let count: usize = self.breakpoints.len() + self.watchpoints.len();
+
+        self.breakpoints.retain(|breakpoint| breakpoint.id != id);
+        self.watchpoints.retain(|watchpoint| watchpoint.id != id);
+
+        count != self.breakpoints.len() + self.watchpoints.len()
+    }
+
+    /// Enable or disable a breakpoint or watchpoint by id. Returns false if there is none with
+    /// that id.
+    pub fn set_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        if let Some(breakpoint) = self.breakpoints.iter_mut().find(|breakpoint| breakpoint.id == id) {
+            breakpoint.enabled = enabled;
+        } else if let Some(watchpoint) = self.watchpoints.iter_mut().find(|watchpoint| watchpoint.id == id) {
+            watchpoint.enabled = enabled;
+        } else {
+            return false;
+        }
+
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+    }
+
+    pub fn get_breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn get_watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn has_breakpoint_at(&self, address: u16) -> bool {
+        self.breakpoints.iter().any(|breakpoint| breakpoint.address == address)
+    }
+
+    /// Whether memory accesses have to be recorded at all
+    pub fn has_watchpoints(&self) -> bool {
+        self.watchpoints.iter().any(|watchpoint| watchpoint.enabled)
+    }
+
+    /// Find an enabled breakpoint at the program counter whose condition holds
+    pub fn check_execution(&self, state: &processor::ProcessorState, memory: &memory_manager::MemoryManager) -> Option<BreakpointHit> {
+        self.breakpoints.iter()
+            .find(|breakpoint| breakpoint.enabled && breakpoint.address == state.program_counter && condition_holds(&breakpoint.condition, state, memory))
+            .map(|breakpoint| BreakpointHit { id: breakpoint.id, program_counter: state.program_counter, access: None })
+    }
+
+    /// Find the first access of an instruction which triggers an enabled watchpoint. Conditions
+    /// are evaluated against the state after the instruction.
+    pub fn check_accesses(&self, accesses: &[MemoryAccess], instruction_address: u16, state: &processor::ProcessorState,
+            memory: &memory_manager::MemoryManager) -> Option<BreakpointHit> {
+        for access in accesses {
+            for watchpoint in self.watchpoints.iter().filter(|watchpoint| watchpoint.enabled && watchpoint.matches(access)) {
+                if condition_holds(&watchpoint.condition, state, memory) {
+                    return Some(BreakpointHit { id: watchpoint.id, program_counter: instruction_address, access: Some(*access) });
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,197 @@
+use std::fmt;
+
+use crate::symbols;
+
+// Frames beyond this depth are dropped from the bottom, so runaway recursion cannot grow the
+// shadow stack forever
+const MAX_DEPTH: usize = 1024;
+
+// How many of the most recent mismatches are kept
+const MAX_MISMATCHES: usize = 32;
+
+/// Whether a stack pointer is above another, i.e. holds less of the stack. The comparison wraps,
+/// so it also holds for a stack which grows down through $0000.
+pub fn is_above(stack_pointer: u16, other: u16) -> bool {
+    (stack_pointer.wrapping_sub(other) as i16) > 0
+}
+
+/// How a frame was entered
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FrameKind {
+    /// JSR or BSR, left with RTS
+    Subroutine,
+    /// SWI, left with RTI
+    SoftwareInterrupt,
+    /// IRQ, left with RTI
+    Irq,
+    /// NMI, left with RTI
+    Nmi
+}
+
+/// A call or interrupt the program has not returned from yet
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Frame {
+    pub kind: FrameKind,
+    /// The address of the calling instruction, or of the instruction which was interrupted
+    pub call_address: u16,
+    /// The address of the routine or handler entered
+    pub target: u16,
+    /// The address stacked for the return
+    pub return_address: u16,
+    /// The stack pointer right after the return address (and registers) were stacked, which is
+    /// also the stack pointer the matching return is expected to run with
+    pub stack_pointer: u16
+}
+
+/// A return which did not match the innermost frame, e.g. because the routine moved the stack
+/// pointer or rewrote its return address before RTS
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StackMismatch {
+    /// The address of the RTS or RTI
+    pub address: u16,
+    /// The address it returned to
+    pub return_address: u16,
+    /// The return address of the innermost frame, if there was one
+    pub expected: Option<u16>,
+    /// How many frames were dropped to resynchronize with the real stack
+    pub discarded: usize
+}
+
+/// A shadow of the 6800 stack, following calls, interrupts and returns
+///
+/// The real stack only holds bytes, so the processor keeps this alongside it to show where the
+/// program is called from.
+#[derive(Clone, Default)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+    mismatches: Vec<StackMismatch>
+}
+
+impl FrameKind {
+    pub fn is_interrupt(&self) -> bool {
+        *self != FrameKind::Subroutine
+    }
+}
+
+impl fmt::Display for FrameKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameKind::Subroutine => write!(formatter, "call"),
+            FrameKind::SoftwareInterrupt => write!(formatter, "SWI"),
+            FrameKind::Irq => write!(formatter, "IRQ"),
+            FrameKind::Nmi => write!(formatter, "NMI")
+        }
+    }
+}
+
+impl fmt::Display for StackMismatch {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "Return at ${:04X} went to ${:04X}", self.address, self.return_address)?;
+
+        if let Some(expected) = self.expected {
+            write!(formatter, ", expected ${:04X}", expected)?;
+        }
+
+        if self.discarded > 0 {
+            write!(formatter, " ({} frame{} dropped)", self.discarded, if self.discarded == 1 { "" } else { "s" })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl CallStack {
+    pub fn new() -> CallStack {
+        CallStack::default()
+    }
+
+    /// The frames, outermost first
+    pub fn get_frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn set_frames(&mut self, frames: Vec<Frame>) {
+        self.frames = frames;
+    }
+
+    /// The most recent returns which did not match the shadow stack, oldest first
+    pub fn get_mismatches(&self) -> &[StackMismatch] {
+        &self.mismatches
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.mismatches.clear();
+    }
+
+    /// Record a call or an interrupt
+    pub fn enter(&mut self, frame: Frame) {
+        // Frames stored at or below the new one were abandoned, e.g. by reloading SP
+        while self.frames.last().is_some_and(|last| !is_above(last.stack_pointer, frame.stack_pointer)) {
+            self.frames.pop();
+        }
+
+        if self.frames.len() == MAX_DEPTH {
+            self.frames.remove(0);
+        }
+
+        self.frames.push(frame);
+    }
+
+    /// Record an RTS (`interrupt` false) or an RTI run at `address` with the stack pointer it
+    /// had before popping. Returns the mismatch if the return did not leave the innermost frame.
+    pub fn leave(&mut self, address: u16, interrupt: bool, stack_pointer: u16, return_address: u16) -> Option<StackMismatch> {
+        let expected: Option<u16> = self.frames.last().map(|frame| frame.return_address);
+        let mut discarded: usize = 0;
+        let mismatch: StackMismatch;
+
+        let matches = |frame: &Frame| frame.kind.is_interrupt() == interrupt && frame.stack_pointer == stack_pointer
+                && frame.return_address == return_address;
+
+        if self.frames.last().is_some_and(matches) {
+            self.frames.pop();
+            return None;
+        }
+
+        if let Some(index) = self.frames.iter().rposition(matches) {
+            // The program left several frames at once, e.g. by popping a return address
+            discarded = self.frames.len() - index - 1;
+            self.frames.truncate(index);
+        } else {
+            // Frames whose return address was popped or rewritten are gone, anything stored
+            // above the stack pointer is still live
+            while self.frames.last().is_some_and(|last| !is_above(last.stack_pointer, stack_pointer)) {
+                self.frames.pop();
+                discarded += 1;
+            }
+        }
+
+        mismatch = StackMismatch { address, return_address, expected, discarded };
+
+        if self.mismatches.len() == MAX_MISMATCHES {
+            self.mismatches.remove(0);
+        }
+
+        self.mismatches.push(mismatch);
+
+        Some(mismatch)
+    }
+
+    /// Format the call stack innermost first, starting with the current program counter
+    pub fn format_backtrace(&self, program_counter: u16, symbols: &symbols::SymbolTable) -> String {
+        let mut lines: Vec<String> = vec![format!("#0  {}", symbols.format_address(program_counter))];
+
+        for (level, frame) in self.frames.iter().rev().enumerate() {
+            lines.push(match frame.kind {
+                FrameKind::Subroutine => format!("#{}  {}", level + 1, symbols.format_address(frame.call_address)),
+                kind => format!("#{}  {}  <{}>", level + 1, symbols.format_address(frame.call_address), kind)
+            });
+        }
+
+        lines.join("\n")
+    }
+}
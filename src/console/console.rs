@@ -0,0 +1,98 @@
+use crate::device;
+use crate::save_state;
+use crate::serial;
+
+// Register offsets
+const DATA_REGISTER: u16 = 0;
+const STATUS_REGISTER: u16 = 1;
+
+// Status register bits
+pub const STATUS_INPUT_READY: u8 = 0x01;
+
+/// The simplest console a program can use, with no setup
+///
+/// Writing the data register (at the address of the device) sends a byte to a
+/// `serial::SerialPort`, e.g. stdout with `serial::StdioPort`. Reading it takes the next byte
+/// received, or 0 when none is waiting, without blocking. Bit 0 of the status register (right
+/// after it) is set while a byte is waiting.
+pub struct Console {
+    port: Box<dyn serial::SerialPort>,
+    input: Option<u8>,
+    data: u8
+}
+
+impl Console {
+    pub fn new(port: Box<dyn serial::SerialPort>) -> Console {
+        Console { port, input: None, data: 0 }
+    }
+
+    pub fn get_status(&self) -> u8 {
+        if self.input.is_some() {
+            STATUS_INPUT_READY
+        } else {
+            0
+        }
+    }
+
+    fn poll_port(&mut self) {
+        if self.input.is_none() {
+            self.input = self.port.receive();
+        }
+    }
+}
+
+impl device::Device for Console {
+    fn name(&self) -> &str {
+        "console"
+    }
+
+    fn size(&self) -> u16 {
+        2
+    }
+
+    fn read(&mut self, offset: u16) -> u8 {
+        self.poll_port();
+
+        match offset {
+            DATA_REGISTER => {
+                self.data = self.input.take().unwrap_or(0);
+                self.poll_port();
+
+                self.data
+            },
+            _ => self.peek(offset)
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            DATA_REGISTER => self.data,
+            STATUS_REGISTER => self.get_status(),
+            _ => 0
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if offset == DATA_REGISTER {
+            self.port.transmit(value);
+        }
+    }
+
+    fn tick(&mut self, _cycles: u64) {
+        self.poll_port();
+    }
+
+    fn save(&self) -> Vec<u8> {
+        vec![self.data]
+    }
+
+    fn restore(&mut self, data: &[u8]) -> Result<(), save_state::SaveStateError> {
+        if data.is_empty() {
+            return Err(save_state::SaveStateError::Truncated);
+        }
+
+        self.data = data[0];
+
+        Ok(())
+    }
+}
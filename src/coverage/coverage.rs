@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path;
+use std::time;
+
+use crate::disassembler;
+use crate::line_table;
+use crate::memory_manager;
+use crate::observer;
+use crate::processor;
+
+/// The report formats coverage can be written in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CoverageFormat {
+    /// An lcov tracefile, as read by `genhtml` and most editors
+    Lcov,
+    /// A Cobertura XML report, as read by most CI servers
+    Cobertura
+}
+
+/// How often a conditional branch went each way
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct BranchCounts {
+    pub taken: u64,
+    pub not_taken: u64
+}
+
+/// Collects which instructions ran, and which way every conditional branch went, keyed by the
+/// address of the instruction
+///
+/// Attach it to a processor with `Processor::add_observer`. Reports map the addresses back to
+/// source lines through the line table of an assembler listing.
+#[derive(Clone, Default)]
+pub struct Coverage {
+    instructions: BTreeMap<u16, u64>,
+    branches: BTreeMap<u16, BranchCounts>
+}
+
+/// The coverage of a single source line
+struct LineCoverage {
+    line: usize,
+    hits: u64,
+    /// Set for conditional branches, even when they never ran
+    branches: Option<BranchCounts>
+}
+
+impl LineCoverage {
+    /// How many of the two directions of a conditional branch were taken
+    fn get_covered_directions(&self) -> usize {
+        self.branches.map_or(0, |counts| (counts.taken > 0) as usize + (counts.not_taken > 0) as usize)
+    }
+}
+
+fn is_conditional_branch(instruction_info: &disassembler::InstructionInfo) -> bool {
+    instruction_info.opcode_info.group == disassembler::OpcodeGroup::Branch &&
+        !matches!(instruction_info.opcode_info.opcode, disassembler::Opcode::BranchUnconditional | disassembler::Opcode::BranchToSubroutine)
+}
+
+fn rate(covered: usize, valid: usize) -> f64 {
+    if valid == 0 { 1.0 } else { covered as f64 / valid as f64 }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+impl observer::Observer for Coverage {
+    fn after_instruction(&mut self, instruction_info: &disassembler::InstructionInfo, before: &processor::ProcessorState,
+            after: &processor::ProcessorState, _cycles: u64) {
+        let next_address: u16;
+        let counts: &mut BranchCounts;
+
+        *self.instructions.entry(before.program_counter).or_insert(0) += 1;
+
+        if is_conditional_branch(instruction_info) {
+            next_address = before.program_counter.wrapping_add(instruction_info.opcode_info.instruction_length as u16);
+            counts = self.branches.entry(before.program_counter).or_default();
+
+            if after.program_counter == next_address {
+                counts.not_taken += 1;
+            } else {
+                counts.taken += 1;
+            }
+        }
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    /// How many times the instruction at an address ran
+    pub fn get_hits(&self, address: u16) -> u64 {
+        self.instructions.get(&address).copied().unwrap_or(0)
+    }
+
+    /// Which way the conditional branch at an address went, or None if it never ran
+    pub fn get_branch(&self, address: u16) -> Option<BranchCounts> {
+        self.branches.get(&address).copied()
+    }
+
+    /// The addresses of every instruction which ran, in order
+    pub fn get_addresses(&self) -> Vec<u16> {
+        self.instructions.keys().copied().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.instructions.clear();
+        self.branches.clear();
+    }
+
+    /// Add the counts of another run
+    pub fn merge(&mut self, other: &Coverage) {
+        for (address, hits) in &other.instructions {
+            *self.instructions.entry(*address).or_insert(0) += hits;
+        }
+
+        for (address, counts) in &other.branches {
+            let own_counts: &mut BranchCounts = self.branches.entry(*address).or_default();
+
+            own_counts.taken += counts.taken;
+            own_counts.not_taken += counts.not_taken;
+        }
+    }
+
+    /// The coverage of every line of the listing which produced code. The memory is decoded to
+    /// find the branches which never ran.
+    fn collect_lines(&self, line_table: &line_table::LineTable, memory: &memory_manager::MemoryManager) -> Vec<LineCoverage> {
+        let mut lines: Vec<LineCoverage> = Vec::with_capacity(line_table.len());
+        let mut bytes: [u8; 3];
+        let mut branches: Option<BranchCounts>;
+
+        for (line, address) in line_table.get_lines() {
+            bytes = [0; 3];
+
+            for (offset, byte) in bytes.iter_mut().enumerate() {
+                *byte = memory.read(address.wrapping_add(offset as u16), 1)[0];
+            }
+
+            branches = match disassembler::disassemble_instruction(&bytes) {
+                Ok(instruction_info) if is_conditional_branch(&instruction_info) => Some(self.get_branch(address).unwrap_or_default()),
+                _ => self.get_branch(address)
+            };
+
+            lines.push(LineCoverage { line, hits: self.get_hits(address), branches });
+        }
+
+        lines
+    }
+
+    /// Format the coverage of a single source file, given the line table of its listing
+    pub fn format_report(&self, format: CoverageFormat, line_table: &line_table::LineTable, memory: &memory_manager::MemoryManager,
+            source_path: &str) -> String {
+        match format {
+            CoverageFormat::Lcov => self.to_lcov(line_table, memory, source_path),
+            CoverageFormat::Cobertura => self.to_cobertura(line_table, memory, source_path)
+        }
+    }
+
+    /// Format the coverage as an lcov tracefile for a single source file
+    pub fn to_lcov(&self, line_table: &line_table::LineTable, memory: &memory_manager::MemoryManager, source_path: &str) -> String {
+        let lines: Vec<LineCoverage> = self.collect_lines(line_table, memory);
+        let mut output: String = String::new();
+        let mut branches_found: usize = 0;
+        let mut branches_hit: usize = 0;
+
+        writeln!(output, "TN:").unwrap();
+        writeln!(output, "SF:{}", source_path).unwrap();
+
+        // Branch directions are listed as taken (0) and not taken (1). Branches which never ran
+        // are reported as such with `-`.
+        for line in &lines {
+            if let Some(counts) = line.branches {
+                if line.hits == 0 {
+                    writeln!(output, "BRDA:{},0,0,-", line.line).unwrap();
+                    writeln!(output, "BRDA:{},0,1,-", line.line).unwrap();
+                } else {
+                    writeln!(output, "BRDA:{},0,0,{}", line.line, counts.taken).unwrap();
+                    writeln!(output, "BRDA:{},0,1,{}", line.line, counts.not_taken).unwrap();
+                }
+
+                branches_found += 2;
+                branches_hit += line.get_covered_directions();
+            }
+        }
+
+        writeln!(output, "BRF:{}", branches_found).unwrap();
+        writeln!(output, "BRH:{}", branches_hit).unwrap();
+
+        for line in &lines {
+            writeln!(output, "DA:{},{}", line.line, line.hits).unwrap();
+        }
+
+        writeln!(output, "LF:{}", lines.len()).unwrap();
+        writeln!(output, "LH:{}", lines.iter().filter(|line| line.hits > 0).count()).unwrap();
+        writeln!(output, "end_of_record").unwrap();
+
+        output
+    }
+
+    /// Format the coverage as a Cobertura XML report for a single source file
+    pub fn to_cobertura(&self, line_table: &line_table::LineTable, memory: &memory_manager::MemoryManager, source_path: &str) -> String {
+        let lines: Vec<LineCoverage> = self.collect_lines(line_table, memory);
+        let lines_covered: usize = lines.iter().filter(|line| line.hits > 0).count();
+        let branches_valid: usize = lines.iter().filter(|line| line.branches.is_some()).count() * 2;
+        let branches_covered: usize = lines.iter().map(|line| line.get_covered_directions()).sum();
+        let line_rate: f64 = rate(lines_covered, lines.len());
+        let branch_rate: f64 = rate(branches_covered, branches_valid);
+        let timestamp: u128 = time::SystemTime::now().duration_since(time::UNIX_EPOCH).map_or(0, |duration| duration.as_millis());
+        let name: String = escape_xml(&path::Path::new(source_path).file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned()));
+        let mut output: String = String::new();
+
+        writeln!(output, "<?xml version=\"1.0\" ?>").unwrap();
+        writeln!(output, "<!DOCTYPE coverage SYSTEM \"http://cobertura.sourceforge.net/xml/coverage-04.dtd\">").unwrap();
+        writeln!(output, "<coverage line-rate=\"{:.4}\" branch-rate=\"{:.4}\" lines-covered=\"{}\" lines-valid=\"{}\" branches-covered=\"{}\" branches-valid=\"{}\" complexity=\"0\" version=\"{}\" timestamp=\"{}\">",
+                line_rate, branch_rate, lines_covered, lines.len(), branches_covered, branches_valid, env!("CARGO_PKG_VERSION"), timestamp).unwrap();
+        writeln!(output, "  <sources><source>.</source></sources>").unwrap();
+        writeln!(output, "  <packages>").unwrap();
+        writeln!(output, "    <package name=\"{}\" line-rate=\"{:.4}\" branch-rate=\"{:.4}\" complexity=\"0\">", name, line_rate, branch_rate).unwrap();
+        writeln!(output, "      <classes>").unwrap();
+        writeln!(output, "        <class name=\"{}\" filename=\"{}\" line-rate=\"{:.4}\" branch-rate=\"{:.4}\" complexity=\"0\">",
+                name, escape_xml(source_path), line_rate, branch_rate).unwrap();
+        writeln!(output, "          <methods/>").unwrap();
+        writeln!(output, "          <lines>").unwrap();
+
+        for line in &lines {
+            if line.branches.is_some() {
+                writeln!(output, "            <line number=\"{}\" hits=\"{}\" branch=\"true\" condition-coverage=\"{}% ({}/2)\"/>",
+                        line.line, line.hits, line.get_covered_directions() * 50, line.get_covered_directions()).unwrap();
+            } else {
+                writeln!(output, "            <line number=\"{}\" hits=\"{}\" branch=\"false\"/>", line.line, line.hits).unwrap();
+            }
+        }
+
+        writeln!(output, "          </lines>").unwrap();
+        writeln!(output, "        </class>").unwrap();
+        writeln!(output, "      </classes>").unwrap();
+        writeln!(output, "    </package>").unwrap();
+        writeln!(output, "  </packages>").unwrap();
+        writeln!(output, "</coverage>").unwrap();
+
+        output
+    }
+}
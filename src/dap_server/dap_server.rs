@@ -0,0 +1,676 @@
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::Read;
+use std::io::Write;
+use std::path;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::breakpoints;
+use crate::json::JsonValue;
+use crate::line_table;
+use crate::machine;
+use crate::number::parse_number;
+use crate::processor;
+use crate::symbols;
+
+// How many instructions run between checks for new requests from the editor
+const RUN_SLICE: u64 = 10000;
+
+// The emulator has a single thread of execution
+const THREAD_ID: u64 = 1;
+
+// References of the variable scopes shown by the editor
+const REGISTERS_REFERENCE: u64 = 1;
+const FLAGS_REFERENCE: u64 = 2;
+
+// Flag names in the order of their bit in the condition code register, from bit 5 down
+const FLAG_NAMES: [&str; 6] = ["H", "I", "N", "Z", "V", "C"];
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// What the processor does while the editor is not waiting for it
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RunMode {
+    Continue,
+    /// Run until a subroutine or interrupt handler returns
+    StepOver(processor::StopCondition)
+}
+
+/// Serves a machine to an editor speaking the Debug Adapter Protocol
+///
+/// Programs are launched with a `launch` request taking `program` (a raw binary), and optionally
+/// `listing` (an `as0` listing used for source lines and symbols), `source` (the assembly file,
+/// by default the listing with an `.asm` extension), `loadAddress`, `startAddress` and
+/// `stopOnEntry`.
+pub struct DapServer {
+    machine: machine::Machine,
+    symbols: symbols::SymbolTable,
+    line_table: line_table::LineTable,
+    source_path: Option<String>,
+    /// The breakpoints the editor asked for as (line, condition), resolved again after a launch
+    requested_breakpoints: Vec<(usize, Option<String>)>,
+    breakpoint_ids: Vec<u32>,
+    output: Box<dyn Write + Send>,
+    sequence: u64,
+    run_mode: Option<RunMode>,
+    /// The run was just resumed, so a breakpoint at the program counter must not fire
+    resuming: bool,
+    stop_on_entry: bool,
+    launched: bool,
+    configured: bool,
+    quit: bool
+}
+
+/// Get an address given either as a number or as a string
+fn get_address(value: Option<&JsonValue>) -> Option<u16> {
+    let number: u64 = match value? {
+        JsonValue::String(text) => parse_number(text)?,
+        value => value.as_u64()?
+    };
+
+    if number <= 0xFFFF { Some(number as u16) } else { None }
+}
+
+fn encode_base64(data: &[u8]) -> String {
+    let mut output: String = String::with_capacity(data.len().div_ceil(3) * 4);
+    let mut group: u32;
+
+    for chunk in data.chunks(3) {
+        group = chunk.iter().enumerate().fold(0, |group, (index, byte)| group | ((*byte as u32) << (16 - 8 * index)));
+
+        for index in 0..4 {
+            if index <= chunk.len() {
+                output.push(BASE64_ALPHABET[((group >> (18 - 6 * index)) & 0x3F) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+
+    output
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut output: Vec<u8> = Vec::with_capacity(text.len() / 4 * 3);
+    let mut group: u32 = 0;
+    let mut bits: u32 = 0;
+
+    for character in text.bytes().filter(|character| *character != b'=') {
+        group = (group << 6) | BASE64_ALPHABET.iter().position(|symbol| *symbol == character)? as u32;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            output.push((group >> bits) as u8);
+        }
+    }
+
+    Some(output)
+}
+
+/// Read a single message, framed by a `Content-Length` header. Returns `None` at the end of
+/// the input.
+fn read_message(reader: &mut dyn BufRead) -> io::Result<Option<JsonValue>> {
+    let mut line: String = String::new();
+    let mut length: Option<usize> = None;
+    let mut body: Vec<u8>;
+
+    loop {
+        line.clear();
+
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        if line.trim().is_empty() {
+            if length.is_some() {
+                break;
+            }
+
+            continue;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+
+    body = vec![0; length.unwrap()];
+    reader.read_exact(&mut body)?;
+
+    match JsonValue::parse(&String::from_utf8_lossy(&body)) {
+        Ok(message) => Ok(Some(message)),
+        Err(err) => Err(io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+impl DapServer {
+    pub fn new(output: Box<dyn Write + Send>) -> DapServer {
+        DapServer {
+            machine: machine::Machine::new(),
+            symbols: symbols::SymbolTable::new(),
+            line_table: line_table::LineTable::new(),
+            source_path: None,
+            requested_breakpoints: Vec::new(),
+            breakpoint_ids: Vec::new(),
+            output,
+            sequence: 0,
+            run_mode: None,
+            resuming: false,
+            stop_on_entry: false,
+            launched: false,
+            configured: false,
+            quit: false
+        }
+    }
+
+    pub fn get_machine(&self) -> &machine::Machine {
+        &self.machine
+    }
+
+    pub fn get_machine_mut(&mut self) -> &mut machine::Machine {
+        &mut self.machine
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.run_mode.is_some()
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Serve requests read from `input` until the editor disconnects. Requests are read on
+    /// another thread, so the editor can pause a running program.
+    pub fn serve(&mut self, input: Box<dyn Read + Send>) -> io::Result<()> {
+        let (sender, receiver) = mpsc::channel::<JsonValue>();
+
+        thread::spawn(move || {
+            let mut reader = io::BufReader::new(input);
+
+            while let Ok(Some(message)) = read_message(&mut reader) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        while !self.quit {
+            if self.is_running() {
+                match receiver.try_recv() {
+                    Ok(message) => self.handle_message(&message)?,
+                    Err(mpsc::TryRecvError::Empty) => self.run_slice()?,
+                    Err(mpsc::TryRecvError::Disconnected) => break
+                }
+            } else {
+                match receiver.recv() {
+                    Ok(message) => self.handle_message(&message)?,
+                    Err(_) => break
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send(&mut self, mut members: Vec<(&str, JsonValue)>) -> io::Result<()> {
+        let message: String;
+
+        self.sequence += 1;
+        members.insert(0, ("seq", JsonValue::from(self.sequence)));
+        message = JsonValue::object(members).to_string();
+
+        write!(self.output, "Content-Length: {}\r\n\r\n{}", message.len(), message)?;
+        self.output.flush()
+    }
+
+    fn send_event(&mut self, event: &str, body: Option<JsonValue>) -> io::Result<()> {
+        let mut members: Vec<(&str, JsonValue)> = vec![("type", JsonValue::from("event")), ("event", JsonValue::from(event))];
+
+        if let Some(body) = body {
+            members.push(("body", body));
+        }
+
+        self.send(members)
+    }
+
+    fn send_stopped(&mut self, reason: &str, description: Option<String>, hit_breakpoint: Option<u32>) -> io::Result<()> {
+        let mut body: Vec<(&str, JsonValue)> = vec![
+            ("reason", JsonValue::from(reason)),
+            ("threadId", JsonValue::from(THREAD_ID)),
+            ("allThreadsStopped", JsonValue::from(true))
+        ];
+
+        if let Some(description) = description {
+            body.push(("description", JsonValue::from(description.clone())));
+            body.push(("text", JsonValue::from(description)));
+        }
+
+        if let Some(id) = hit_breakpoint {
+            body.push(("hitBreakpointIds", JsonValue::from(vec![JsonValue::from(id as u64)])));
+        }
+
+        self.run_mode = None;
+        self.send_event("stopped", Some(JsonValue::object(body)))
+    }
+
+    /// Handle a single request from the editor, sending its response and any events it causes
+    pub fn handle_message(&mut self, message: &JsonValue) -> io::Result<()> {
+        let command: String = message.get("command").and_then(|command| command.as_str()).unwrap_or("").to_string();
+        let request_sequence: u64 = message.get("seq").and_then(|sequence| sequence.as_u64()).unwrap_or(0);
+        let arguments: JsonValue = message.get("arguments").cloned().unwrap_or(JsonValue::Object(Vec::new()));
+        let result: Result<JsonValue, String>;
+        let success: bool;
+        let mut response: Vec<(&str, JsonValue)>;
+
+        if message.get("type").and_then(|message_type| message_type.as_str()) != Some("request") {
+            return Ok(());
+        }
+
+        result = match command.as_str() {
+            "initialize" => Ok(DapServer::capabilities()),
+            "launch" => self.launch(&arguments),
+            "setBreakpoints" => self.set_breakpoints(&arguments),
+            "configurationDone" => {
+                self.configured = true;
+                Ok(JsonValue::Null)
+            },
+            "threads" => Ok(JsonValue::object(vec![("threads", JsonValue::from(vec![
+                JsonValue::object(vec![("id", JsonValue::from(THREAD_ID)), ("name", JsonValue::from("MC6800"))])
+            ]))])),
+            "stackTrace" => Ok(self.stack_trace()),
+            "scopes" => Ok(DapServer::scopes()),
+            "variables" => self.variables(&arguments),
+            "setVariable" => self.set_variable(&arguments),
+            "continue" => Ok(JsonValue::object(vec![("allThreadsContinued", JsonValue::from(true))])),
+            "next" | "stepIn" | "stepOut" | "pause" => Ok(JsonValue::Null),
+            "readMemory" => self.read_memory(&arguments),
+            "writeMemory" => self.write_memory(&arguments),
+            "disconnect" | "terminate" => {
+                self.quit = true;
+                Ok(JsonValue::Null)
+            },
+            _ => Err(format!("Unsupported request '{}'", command))
+        };
+
+        success = result.is_ok();
+        response = vec![
+            ("type", JsonValue::from("response")),
+            ("request_seq", JsonValue::from(request_sequence)),
+            ("success", JsonValue::from(success)),
+            ("command", JsonValue::from(command.as_str()))
+        ];
+
+        match result {
+            Ok(JsonValue::Null) => {},
+            Ok(body) => response.push(("body", body)),
+            Err(error) => response.push(("message", JsonValue::from(error)))
+        }
+
+        self.send(response)?;
+
+        if !success {
+            return Ok(());
+        }
+
+        // Events caused by the request follow its response
+        match command.as_str() {
+            "initialize" => self.send_event("initialized", None)?,
+            "launch" | "configurationDone" if self.launched && self.configured => self.start()?,
+            "continue" => self.resume(RunMode::Continue),
+            "next" => self.step_over()?,
+            "stepIn" => self.step()?,
+            "stepOut" => self.step_out()?,
+            "pause" if self.is_running() => self.send_stopped("pause", None, None)?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn capabilities() -> JsonValue {
+        JsonValue::object(vec![
+            ("supportsConfigurationDoneRequest", JsonValue::from(true)),
+            ("supportsConditionalBreakpoints", JsonValue::from(true)),
+            ("supportsSetVariable", JsonValue::from(true)),
+            ("supportsReadMemoryRequest", JsonValue::from(true)),
+            ("supportsWriteMemoryRequest", JsonValue::from(true)),
+            ("supportsTerminateRequest", JsonValue::from(true))
+        ])
+    }
+
+    fn launch(&mut self, arguments: &JsonValue) -> Result<JsonValue, String> {
+        let program_path: &str = arguments.get("program").and_then(|path| path.as_str()).ok_or("Missing 'program'")?;
+        let load_address: u16 = get_address(arguments.get("loadAddress")).unwrap_or(0);
+        let program: Vec<u8> = fs::read(program_path).map_err(|err| format!("Cannot read {}: {}", program_path, err))?;
+
+        if let Some(listing_path) = arguments.get("listing").and_then(|path| path.as_str()) {
+            self.line_table = line_table::LineTable::load_listing(listing_path).map_err(|err| format!("Cannot read {}: {}", listing_path, err))?;
+            self.symbols = symbols::SymbolTable::load_file(listing_path).map_err(|err| format!("Cannot read {}: {}", listing_path, err))?;
+            self.source_path = Some(path::Path::new(listing_path).with_extension("asm").to_string_lossy().into_owned());
+        }
+
+        if let Some(source_path) = arguments.get("source").and_then(|path| path.as_str()) {
+            self.source_path = Some(String::from(source_path));
+        }
+
+        self.machine.load_program(load_address, &program);
+
+        if let Some(start_address) = get_address(arguments.get("startAddress")) {
+            self.machine.get_processor_mut().set_program_counter(start_address);
+        }
+
+        self.stop_on_entry = arguments.get("stopOnEntry").and_then(|stop| stop.as_bool()).unwrap_or(false);
+        self.launched = true;
+        self.apply_breakpoints();
+
+        Ok(JsonValue::Null)
+    }
+
+    /// Start the program once it is launched and the editor sent its configuration
+    fn start(&mut self) -> io::Result<()> {
+        if self.stop_on_entry {
+            self.send_stopped("entry", None, None)
+        } else {
+            self.run_mode = Some(RunMode::Continue);
+            Ok(())
+        }
+    }
+
+    /// Replace the breakpoints of the processor by the requested ones, returning how each of
+    /// them was resolved
+    fn apply_breakpoints(&mut self) -> Vec<JsonValue> {
+        let mut results: Vec<JsonValue> = Vec::new();
+        let mut condition: Option<breakpoints::Condition>;
+        let breakpoint_manager = self.machine.get_processor_mut().get_breakpoints_mut();
+        let mut id: u32;
+
+        for id in self.breakpoint_ids.drain(..) {
+            breakpoint_manager.remove(id);
+        }
+
+        for (line, condition_text) in &self.requested_breakpoints {
+            condition = None;
+
+            if let Some(text) = condition_text {
+                match breakpoints::Condition::parse(text) {
+                    Ok(parsed_condition) => condition = Some(parsed_condition),
+                    Err(err) => {
+                        results.push(JsonValue::object(vec![
+                            ("verified", JsonValue::from(false)),
+                            ("line", JsonValue::from(*line as u64)),
+                            ("message", JsonValue::from(err))
+                        ]));
+                        continue;
+                    }
+                }
+            }
+
+            match self.line_table.find_code_line(*line) {
+                Some((code_line, address)) => {
+                    id = breakpoint_manager.add_breakpoint(address, condition);
+                    self.breakpoint_ids.push(id);
+                    results.push(JsonValue::object(vec![
+                        ("id", JsonValue::from(id as u64)),
+                        ("verified", JsonValue::from(true)),
+                        ("line", JsonValue::from(code_line as u64))
+                    ]));
+                },
+                None => {
+                    results.push(JsonValue::object(vec![
+                        ("verified", JsonValue::from(false)),
+                        ("line", JsonValue::from(*line as u64)),
+                        ("message", JsonValue::from("No code at or after this line"))
+                    ]));
+                }
+            }
+        }
+
+        results
+    }
+
+    fn set_breakpoints(&mut self, arguments: &JsonValue) -> Result<JsonValue, String> {
+        let requested: &[JsonValue] = arguments.get("breakpoints").and_then(|breakpoints| breakpoints.as_array()).unwrap_or(&[]);
+
+        self.requested_breakpoints = requested.iter()
+            .filter_map(|breakpoint| {
+                let line: u64 = breakpoint.get("line")?.as_u64()?;
+                let condition: Option<String> = breakpoint.get("condition").and_then(|condition| condition.as_str()).map(String::from);
+
+                Some((line as usize, condition.filter(|condition| !condition.trim().is_empty())))
+            })
+            .collect();
+
+        Ok(JsonValue::object(vec![("breakpoints", JsonValue::from(self.apply_breakpoints()))]))
+    }
+
+    fn stack_frame(&self, id: u64, address: u16) -> JsonValue {
+        let line: Option<usize> = self.line_table.get_line(address);
+        let mut frame: Vec<(&str, JsonValue)> = vec![
+            ("id", JsonValue::from(id)),
+            ("name", JsonValue::from(self.symbols.format_address(address))),
+            ("line", JsonValue::from(line.unwrap_or(0) as u64)),
+            ("column", JsonValue::from(if line.is_some() { 1 } else { 0 })),
+            ("instructionPointerReference", JsonValue::from(format!("0x{:04X}", address)))
+        ];
+
+        if let (Some(_), Some(source_path)) = (line, &self.source_path) {
+            frame.push(("source", JsonValue::object(vec![
+                ("name", JsonValue::from(path::Path::new(source_path).file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned()))),
+                ("path", JsonValue::from(source_path.as_str()))
+            ])));
+        }
+
+        JsonValue::object(frame)
+    }
+
+    /// The current position followed by the calling instructions of the shadow call stack
+    fn stack_trace(&self) -> JsonValue {
+        let mut frames: Vec<JsonValue> = vec![self.stack_frame(0, self.machine.get_state().program_counter)];
+
+        for (index, frame) in self.machine.get_processor().get_call_stack().get_frames().iter().rev().enumerate() {
+            frames.push(self.stack_frame(index as u64 + 1, frame.call_address));
+        }
+
+        JsonValue::object(vec![
+            ("totalFrames", JsonValue::from(frames.len() as u64)),
+            ("stackFrames", JsonValue::from(frames))
+        ])
+    }
+
+    fn scopes() -> JsonValue {
+        let scope = |name: &str, reference: u64| JsonValue::object(vec![
+            ("name", JsonValue::from(name)),
+            ("variablesReference", JsonValue::from(reference)),
+            ("expensive", JsonValue::from(false))
+        ]);
+
+        JsonValue::object(vec![("scopes", JsonValue::from(vec![scope("Registers", REGISTERS_REFERENCE), scope("Flags", FLAGS_REFERENCE)]))])
+    }
+
+    fn variables(&self, arguments: &JsonValue) -> Result<JsonValue, String> {
+        let state: processor::ProcessorState = self.machine.get_state();
+        let variable = |name: &str, value: String, memory_reference: Option<u16>| {
+            let mut members: Vec<(&str, JsonValue)> = vec![
+                ("name", JsonValue::from(name)),
+                ("value", JsonValue::from(value)),
+                ("variablesReference", JsonValue::from(0))
+            ];
+
+            if let Some(address) = memory_reference {
+                members.push(("memoryReference", JsonValue::from(format!("0x{:04X}", address))));
+            }
+
+            JsonValue::object(members)
+        };
+        let variables: Vec<JsonValue>;
+
+        variables = match arguments.get("variablesReference").and_then(|reference| reference.as_u64()) {
+            Some(REGISTERS_REFERENCE) => vec![
+                variable("A", format!("${:02X}", state.accumulator_a), None),
+                variable("B", format!("${:02X}", state.accumulator_b), None),
+                variable("X", format!("${:04X}", state.index_register), Some(state.index_register)),
+                variable("PC", format!("${:04X}", state.program_counter), Some(state.program_counter)),
+                variable("SP", format!("${:04X}", state.stack_pointer), Some(state.stack_pointer)),
+                variable("CCR", format!("${:02X}", state.condition_code_register), None)
+            ],
+            Some(FLAGS_REFERENCE) => FLAG_NAMES.iter().enumerate()
+                .map(|(index, name)| variable(name, (crate::get_bit!(state.condition_code_register, 5 - index) as u8).to_string(), None))
+                .collect(),
+            _ => return Err(String::from("Unknown variables reference"))
+        };
+
+        Ok(JsonValue::object(vec![("variables", JsonValue::from(variables))]))
+    }
+
+    fn set_variable(&mut self, arguments: &JsonValue) -> Result<JsonValue, String> {
+        let name: &str = arguments.get("name").and_then(|name| name.as_str()).ok_or("Missing 'name'")?;
+        let text: &str = arguments.get("value").and_then(|value| value.as_str()).ok_or("Missing 'value'")?;
+        let mut state: processor::ProcessorState = self.machine.get_state();
+        let value: u64;
+        let formatted: String;
+
+        value = match text.trim() {
+            "true" => 1,
+            "false" => 0,
+            text => parse_number(text).ok_or(format!("Invalid value '{}'", text))?
+        };
+
+        if let Some(index) = FLAG_NAMES.iter().position(|flag| *flag == name) {
+            if value > 1 {
+                return Err(String::from("Flags can only be 0 or 1"));
+            }
+
+            state.condition_code_register = (state.condition_code_register & !(1 << (5 - index))) | ((value as u8) << (5 - index));
+            formatted = value.to_string();
+        } else {
+            match (name, value) {
+                ("A", 0..=0xFF) => state.accumulator_a = value as u8,
+                ("B", 0..=0xFF) => state.accumulator_b = value as u8,
+                ("CCR", 0..=0xFF) => state.condition_code_register = value as u8,
+                ("X", 0..=0xFFFF) => state.index_register = value as u16,
+                ("PC", 0..=0xFFFF) => state.program_counter = value as u16,
+                ("SP", 0..=0xFFFF) => state.stack_pointer = value as u16,
+                ("A", _) | ("B", _) | ("CCR", _) | ("X", _) | ("PC", _) | ("SP", _) => return Err(format!("Value {} is too large", text)),
+                _ => return Err(format!("Unknown register '{}'", name))
+            }
+
+            formatted = if matches!(name, "X" | "PC" | "SP") { format!("${:04X}", value) } else { format!("${:02X}", value) };
+        }
+
+        self.machine.get_processor_mut().set_state(state);
+
+        Ok(JsonValue::object(vec![("value", JsonValue::from(formatted))]))
+    }
+
+    /// Resolve the memory reference and offset of a memory request
+    fn get_memory_address(arguments: &JsonValue) -> Result<u16, String> {
+        let reference: &str = arguments.get("memoryReference").and_then(|reference| reference.as_str()).ok_or("Missing 'memoryReference'")?;
+        let offset: f64 = arguments.get("offset").and_then(|offset| offset.as_f64()).unwrap_or(0.0);
+        let address: u64 = parse_number(reference).ok_or(format!("Invalid memory reference '{}'", reference))?;
+
+        Ok((address as i64 + offset as i64).rem_euclid(0x10000) as u16)
+    }
+
+    fn read_memory(&self, arguments: &JsonValue) -> Result<JsonValue, String> {
+        let address: u16 = DapServer::get_memory_address(arguments)?;
+        let count: u64 = arguments.get("count").and_then(|count| count.as_u64()).ok_or("Missing 'count'")?.min(0x10000);
+        let memory = self.machine.get_memory_manager();
+        let data: Vec<u8> = (0..count).map(|offset| memory.read(address.wrapping_add(offset as u16), 1)[0]).collect();
+
+        Ok(JsonValue::object(vec![
+            ("address", JsonValue::from(format!("0x{:04X}", address))),
+            ("data", JsonValue::from(encode_base64(&data)))
+        ]))
+    }
+
+    fn write_memory(&mut self, arguments: &JsonValue) -> Result<JsonValue, String> {
+        let address: u16 = DapServer::get_memory_address(arguments)?;
+        let data: Vec<u8> = arguments.get("data").and_then(|data| data.as_str()).and_then(decode_base64).ok_or("Invalid 'data'")?;
+
+        for (offset, byte) in data.iter().enumerate() {
+            self.machine.write_memory(address.wrapping_add(offset as u16), &[*byte]);
+        }
+
+        Ok(JsonValue::object(vec![("bytesWritten", JsonValue::from(data.len() as u64))]))
+    }
+
+    fn resume(&mut self, run_mode: RunMode) {
+        self.run_mode = Some(run_mode);
+        self.resuming = true;
+    }
+
+    fn step(&mut self) -> io::Result<()> {
+        let program_counter: u16 = self.machine.get_state().program_counter;
+
+        match self.machine.step() {
+            Ok(_) => self.send_stopped("step", None, None),
+            Err(_) => self.send_stopped("exception", Some(format!("Illegal opcode at {}", self.symbols.format_address(program_counter))), None)
+        }
+    }
+
+    /// Step over subroutine calls, running the whole subroutine (unless a breakpoint fires)
+    fn step_over(&mut self) -> io::Result<()> {
+        match self.machine.get_processor().get_step_over_condition() {
+            Some(condition) => {
+                self.resume(RunMode::StepOver(condition));
+                Ok(())
+            },
+            None => self.step()
+        }
+    }
+
+    /// Run until the current subroutine or interrupt handler returns, or step outside of any
+    fn step_out(&mut self) -> io::Result<()> {
+        match self.machine.get_processor().get_step_out_condition() {
+            Some(condition) => {
+                self.resume(RunMode::StepOver(condition));
+                Ok(())
+            },
+            None => self.step()
+        }
+    }
+
+    /// Emulate a bounded number of instructions of a running program, sending a stopped event
+    /// if it stops
+    pub fn run_slice(&mut self) -> io::Result<()> {
+        let mut conditions: Vec<processor::StopCondition> = vec![processor::StopCondition::InstructionBudget(RUN_SLICE), processor::StopCondition::InfiniteLoop];
+        let result: processor::RunResult;
+
+        // `run` never fires a breakpoint on its first instruction, which is only wanted when the
+        // program was just resumed
+        if !self.resuming {
+            if let Some(hit) = self.machine.get_processor().check_breakpoints() {
+                return self.send_stopped("breakpoint", None, Some(hit.id));
+            }
+        }
+
+        self.resuming = false;
+
+        match self.run_mode {
+            Some(RunMode::StepOver(condition)) => conditions.push(condition),
+            Some(RunMode::Continue) => {},
+            None => return Ok(())
+        }
+
+        result = self.machine.run(&conditions);
+
+        match result.stop_reason {
+            processor::StopReason::InstructionBudgetExhausted => Ok(()),
+            processor::StopReason::Returned(_) => self.send_stopped("step", None, None),
+            processor::StopReason::Breakpoint(hit) if hit.access.is_none() => self.send_stopped("breakpoint", None, Some(hit.id)),
+            processor::StopReason::Breakpoint(hit) => self.send_stopped("data breakpoint", None, Some(hit.id)),
+            processor::StopReason::InfiniteLoop(address) => {
+                self.send_stopped("pause", Some(format!("Infinite loop at {}", self.symbols.format_address(address))), None)
+            },
+            processor::StopReason::IllegalOpcode(address) => {
+                self.send_stopped("exception", Some(format!("Illegal opcode at {}", self.symbols.format_address(address))), None)
+            },
+            reason => self.send_stopped("exception", Some(format!("{:?}", reason)), None)
+        }
+    }
+}
@@ -0,0 +1,518 @@
+use std::fmt::Write;
+
+use crate::breakpoints;
+use crate::disassembler;
+use crate::machine;
+use crate::memory_manager;
+use crate::number;
+use crate::processor;
+use crate::symbols;
+
+// How many instructions `disasm` shows by default, and how many of them come before the PC
+const DISASSEMBLY_LENGTH: usize = 8;
+const DISASSEMBLY_CONTEXT: usize = 3;
+
+// The longest 6800 instruction is 3 bytes
+const MAX_INSTRUCTION_LENGTH: u16 = 3;
+
+const HELP: &str = "Commands:
+    s, step [count]             Emulate one (or count) instructions
+    n, next                     Step, running subroutine calls as a single step
+    f, finish                   Run until the current subroutine or interrupt handler returns
+    c, continue                 Run until a breakpoint is hit or the program stops
+    b, break <location> [if <condition>]
+                                Set a breakpoint at an address or symbol
+    w, watch <location> [length] [r|w|rw|change] [if <condition>]
+                                Stop when memory is accessed (on writes by default)
+    bd, delete <location|#id>   Remove the breakpoints at a location, or one by id
+    bl, breakpoints             List the breakpoints and watchpoints
+    r, regs [register value]    Show the registers, or set one (a, b, x, pc, sp, ccr)
+    m, mem <address> [length]   Dump memory in hexadecimal
+    e, edit <address> <bytes>   Write bytes to memory
+    d, disasm [address] [count] Disassemble around the PC or from an address
+    bt, backtrace               Show the calls and interrupts leading to the PC
+    q, quit                     Exit the debugger
+Numbers are decimal, hexadecimal prefixed with $ or 0x, or binary prefixed with % or 0b. Conditions are expressions over
+registers, flags and memory, e.g. `A == $FF && Z` or `[$80] != 0`.";
+
+/// A monitor style debugger driving a machine with textual commands
+pub struct Debugger {
+    machine: machine::Machine,
+    symbols: symbols::SymbolTable,
+    quit: bool
+}
+
+impl Debugger {
+    pub fn new(machine: machine::Machine, symbols: symbols::SymbolTable) -> Debugger {
+        Debugger {
+            machine,
+            symbols,
+            quit: false
+        }
+    }
+
+    pub fn get_machine(&self) -> &machine::Machine {
+        &self.machine
+    }
+
+    pub fn get_machine_mut(&mut self) -> &mut machine::Machine {
+        &mut self.machine
+    }
+
+    pub fn get_symbols(&self) -> &symbols::SymbolTable {
+        &self.symbols
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Resolve a symbol name or an address
+    pub fn parse_location(&self, text: &str) -> Result<u16, String> {
+        if let Some(address) = self.symbols.get_address(text) {
+            return Ok(address);
+        }
+
+        match number::parse_number(text) {
+            Some(address) if address <= 0xFFFF => Ok(address as u16),
+            _ => Err(format!("Unknown location '{}'", text))
+        }
+    }
+
+    fn parse_value(text: Option<&&str>, maximum: u32) -> Result<u32, String> {
+        match text.and_then(|text| number::parse_number(text)) {
+            Some(value) if value <= maximum as u64 => Ok(value as u32),
+            Some(value) => Err(format!("Value ${:X} is too large", value)),
+            None => Err(String::from("Expected a number"))
+        }
+    }
+
+    /// Execute a single command line and return its output
+    pub fn execute(&mut self, line: &str) -> String {
+        let arguments: Vec<&str> = line.split_whitespace().collect();
+        let result: Result<String, String>;
+
+        result = match arguments.first() {
+            None => Ok(String::new()),
+            Some(&"h") | Some(&"help") | Some(&"?") => Ok(String::from(HELP)),
+            Some(&"s") | Some(&"step") => self.step_command(&arguments[1..]),
+            Some(&"n") | Some(&"next") => Ok(self.next_command()),
+            Some(&"f") | Some(&"finish") => self.finish_command(),
+            Some(&"c") | Some(&"continue") => Ok(self.continue_command()),
+            Some(&"b") | Some(&"break") => self.break_command(&arguments[1..]),
+            Some(&"w") | Some(&"watch") => self.watch_command(&arguments[1..]),
+            Some(&"bd") | Some(&"delete") => self.delete_command(&arguments[1..]),
+            Some(&"bl") | Some(&"breakpoints") => Ok(self.list_breakpoints()),
+            Some(&"r") | Some(&"regs") => self.registers_command(&arguments[1..]),
+            Some(&"m") | Some(&"mem") => self.memory_command(&arguments[1..]),
+            Some(&"e") | Some(&"edit") => self.edit_command(&arguments[1..]),
+            Some(&"d") | Some(&"disasm") => self.disassemble_command(&arguments[1..]),
+            Some(&"bt") | Some(&"backtrace") => Ok(self.backtrace()),
+            Some(&"q") | Some(&"quit") => {
+                self.quit = true;
+                Ok(String::new())
+            },
+            Some(command) => Err(format!("Unknown command '{}', type 'help' for a list of commands", command))
+        };
+
+        match result {
+            Ok(output) => output,
+            Err(error) => error
+        }
+    }
+
+    /// Read the (longest possible) instruction at an address, wrapping around the end of memory
+    fn read_instruction_bytes(&self, address: u16) -> [u8; MAX_INSTRUCTION_LENGTH as usize] {
+        let memory = self.machine.get_memory_manager();
+        let mut bytes: [u8; MAX_INSTRUCTION_LENGTH as usize] = [0; MAX_INSTRUCTION_LENGTH as usize];
+
+        for (offset, byte) in bytes.iter_mut().enumerate() {
+            *byte = memory.read(address.wrapping_add(offset as u16), 1)[0];
+        }
+
+        bytes
+    }
+
+    /// Format the instruction at an address as a single listing line, returning its length too
+    pub fn format_instruction_line(&self, address: u16) -> (String, u16) {
+        let bytes: [u8; MAX_INSTRUCTION_LENGTH as usize] = self.read_instruction_bytes(address);
+        let marker: &str = if address == self.machine.get_state().program_counter { ">" } else { " " };
+        let breakpoint: &str = if self.machine.get_processor().get_breakpoints().has_breakpoint_at(address) { "*" } else { " " };
+        let mut label: String = String::new();
+
+        if let Some(name) = self.symbols.get_name(address) {
+            label = format!("{}:", name);
+        }
+
+        match disassembler::disassemble_instruction(&bytes) {
+            Ok(instruction_info) => {
+                let length: u16 = instruction_info.opcode_info.instruction_length as u16;
+                let hex: Vec<String> = bytes[..length as usize].iter().map(|byte| format!("{:02X}", byte)).collect();
+
+                (format!("{}{} {:04X}  {:<8}  {:<12} {}", marker, breakpoint, address, hex.join(" "),
+                        disassembler::format_instruction(&instruction_info, address), label).trim_end().to_string(), length)
+            },
+            Err(_) => (format!("{}{} {:04X}  {:02X}        ???          {}", marker, breakpoint, address, bytes[0], label).trim_end().to_string(), 1)
+        }
+    }
+
+    /// Find an address a few instructions before `address` from which decoding lands on it
+    pub fn find_disassembly_start(&self, address: u16) -> u16 {
+        let memory = self.machine.get_memory_manager();
+        let mut best: (usize, u16) = (0, address);
+        let mut current: u16;
+        let mut count: usize;
+        let mut byte: u8;
+
+        for distance in 1..=(DISASSEMBLY_CONTEXT as u16 * MAX_INSTRUCTION_LENGTH) {
+            current = address.wrapping_sub(distance);
+            count = 0;
+
+            while current != address && address.wrapping_sub(current) <= distance {
+                byte = memory.read(current, 1)[0];
+
+                match disassembler::disassemble_instruction(&[byte, 0, 0]) {
+                    Ok(instruction_info) => current = current.wrapping_add(instruction_info.opcode_info.instruction_length as u16),
+                    Err(_) => break
+                }
+
+                count += 1;
+            }
+
+            if current == address && count <= DISASSEMBLY_CONTEXT && count > best.0 {
+                best = (count, address.wrapping_sub(distance));
+            }
+        }
+
+        best.1
+    }
+
+    fn disassemble(&self, start: u16, count: usize) -> String {
+        let mut output: String = String::new();
+        let mut address: u16 = start;
+        let mut line: String;
+        let mut length: u16;
+
+        for _ in 0..count {
+            (line, length) = self.format_instruction_line(address);
+            writeln!(output, "{}", line).unwrap();
+            address = address.wrapping_add(length);
+        }
+
+        output
+    }
+
+    /// Describe where the processor is now: the registers and the next instruction
+    fn describe_position(&self) -> String {
+        let state = self.machine.get_state();
+
+        format!("PC={} A={:02X} B={:02X} X={:04X} SP={:04X} CCR={:02X}\n{}",
+                self.symbols.format_address(state.program_counter), state.accumulator_a, state.accumulator_b,
+                state.index_register, state.stack_pointer, state.condition_code_register,
+                self.format_instruction_line(state.program_counter).0)
+    }
+
+    fn step_command(&mut self, arguments: &[&str]) -> Result<String, String> {
+        let count: u32 = match arguments.first() {
+            Some(_) => Debugger::parse_value(arguments.first(), u32::MAX)?,
+            None => 1
+        };
+
+        for _ in 0..count {
+            if let Err(err) = self.machine.step() {
+                return Ok(format!("Cannot emulate instruction: {:?}\n{}", err, self.describe_position()));
+            }
+        }
+
+        Ok(self.describe_position())
+    }
+
+    fn next_command(&mut self) -> String {
+        let result: processor::RunResult = self.machine.step_over(&[processor::StopCondition::InfiniteLoop]);
+
+        self.describe_run(result)
+    }
+
+    fn finish_command(&mut self) -> Result<String, String> {
+        match self.machine.step_out(&[processor::StopCondition::InfiniteLoop]) {
+            Some(result) => Ok(self.describe_run(result)),
+            None => Err(String::from("Not inside a subroutine or interrupt handler"))
+        }
+    }
+
+    /// Run until a breakpoint is reached or the program stops
+    fn continue_command(&mut self) -> String {
+        let result: processor::RunResult = self.machine.run(&[processor::StopCondition::InfiniteLoop]);
+
+        self.describe_run(result)
+    }
+
+    /// Describe why a run stopped, with symbol names where they are known
+    pub fn describe_stop_reason(&self, stop_reason: &processor::StopReason) -> String {
+        match stop_reason {
+            processor::StopReason::Breakpoint(hit) => self.describe_hit(hit),
+            processor::StopReason::ProgramCounterReached(address) => format!("Reached {}", self.symbols.format_address(*address)),
+            processor::StopReason::Returned(address) => format!("Returned to {}", self.symbols.format_address(*address)),
+            processor::StopReason::InfiniteLoop(address) => format!("Infinite loop at {}", self.symbols.format_address(*address)),
+            processor::StopReason::IllegalOpcode(address) => format!("Illegal opcode at {}", self.symbols.format_address(*address)),
+            processor::StopReason::Exited(exit_code) => format!("Exited with status {}", exit_code),
+            reason => format!("Stopped: {:?}", reason)
+        }
+    }
+
+    fn describe_hit(&self, hit: &breakpoints::BreakpointHit) -> String {
+        match hit.access {
+            None => format!("Breakpoint {} at {}", hit.id, self.symbols.format_address(hit.program_counter)),
+            Some(access) if access.kind == breakpoints::AccessKind::Read => {
+                format!("Watchpoint {}: {} read ${:02X} from {}", hit.id, self.symbols.format_address(hit.program_counter),
+                        access.value, self.symbols.format_address(access.address))
+            },
+            Some(access) => {
+                format!("Watchpoint {}: {} wrote ${:02X} to {} (was ${:02X})", hit.id, self.symbols.format_address(hit.program_counter),
+                        access.value, self.symbols.format_address(access.address), access.previous_value)
+            }
+        }
+    }
+
+    /// Describe where a run stopped, explaining why unless it stopped where it was asked to
+    fn describe_run(&self, result: processor::RunResult) -> String {
+        let description: String = match result.stop_reason {
+            processor::StopReason::ProgramCounterReached(_) | processor::StopReason::Returned(_) |
+                processor::StopReason::InstructionBudgetExhausted | processor::StopReason::Stepped => String::new(),
+            reason => self.describe_stop_reason(&reason)
+        };
+
+        if description.is_empty() {
+            self.describe_position()
+        } else {
+            format!("{}\n{}", description, self.describe_position())
+        }
+    }
+
+    /// Split the arguments of a command at `if`, parsing everything after it as a condition
+    fn split_condition<'b>(arguments: &'b [&'b str]) -> Result<(&'b [&'b str], Option<breakpoints::Condition>), String> {
+        match arguments.iter().position(|argument| *argument == "if") {
+            Some(index) => Ok((&arguments[..index], Some(breakpoints::Condition::parse(&arguments[index + 1..].join(" "))?))),
+            None => Ok((arguments, None))
+        }
+    }
+
+    fn describe_condition(condition: &Option<breakpoints::Condition>) -> String {
+        match condition {
+            Some(condition) => format!(" if {}", condition),
+            None => String::new()
+        }
+    }
+
+    fn break_command(&mut self, arguments: &[&str]) -> Result<String, String> {
+        let (arguments, condition) = Debugger::split_condition(arguments)?;
+        let address: u16 = self.parse_location(arguments.first().ok_or("Expected a location")?)?;
+        let description: String = Debugger::describe_condition(&condition);
+        let id: u32;
+
+        id = self.machine.get_processor_mut().get_breakpoints_mut().add_breakpoint(address, condition);
+
+        Ok(format!("Breakpoint {} set at {}{}", id, self.symbols.format_address(address), description))
+    }
+
+    fn watch_command(&mut self, arguments: &[&str]) -> Result<String, String> {
+        let (arguments, condition) = Debugger::split_condition(arguments)?;
+        let start: u16 = self.parse_location(arguments.first().ok_or("Expected a location")?)?;
+        let mut length: u32 = 1;
+        let mut kind: breakpoints::WatchKind = breakpoints::WatchKind::Write;
+        let description: String = Debugger::describe_condition(&condition);
+        let end: u16;
+        let id: u32;
+
+        for argument in &arguments[1..] {
+            match *argument {
+                "r" | "read" => kind = breakpoints::WatchKind::Read,
+                "w" | "write" => kind = breakpoints::WatchKind::Write,
+                "rw" => kind = breakpoints::WatchKind::ReadWrite,
+                "c" | "change" => kind = breakpoints::WatchKind::Change,
+                _ => length = Debugger::parse_value(Some(argument), 0x10000)?
+            }
+        }
+
+        if length == 0 || start as u32 + length > 0x10000 {
+            return Err(String::from("Watched range goes past the end of memory"));
+        }
+
+        end = (start as u32 + length - 1) as u16;
+        id = self.machine.get_processor_mut().get_breakpoints_mut().add_watchpoint(start, end, kind, condition);
+
+        Ok(format!("Watchpoint {} set on {}{}", id, self.describe_range(start, end, kind), description))
+    }
+
+    fn describe_range(&self, start: u16, end: u16, kind: breakpoints::WatchKind) -> String {
+        let kind: &str = match kind {
+            breakpoints::WatchKind::Read => "reads",
+            breakpoints::WatchKind::Write => "writes",
+            breakpoints::WatchKind::ReadWrite => "accesses",
+            breakpoints::WatchKind::Change => "changes"
+        };
+
+        if start == end {
+            format!("{} of {}", kind, self.symbols.format_address(start))
+        } else {
+            format!("{} of {}-${:04X}", kind, self.symbols.format_address(start), end)
+        }
+    }
+
+    fn delete_command(&mut self, arguments: &[&str]) -> Result<String, String> {
+        let argument: &str = arguments.first().ok_or("Expected a location or an id")?;
+        let ids: Vec<u32>;
+        let address: u16;
+
+        if let Some(id) = argument.strip_prefix('#') {
+            return match id.parse() {
+                Ok(id) if self.machine.get_processor_mut().get_breakpoints_mut().remove(id) => Ok(format!("Removed #{}", id)),
+                _ => Err(format!("No breakpoint or watchpoint {}", argument))
+            };
+        }
+
+        address = self.parse_location(argument)?;
+        ids = self.machine.get_processor().get_breakpoints().get_breakpoints().iter()
+            .filter(|breakpoint| breakpoint.address == address)
+            .map(|breakpoint| breakpoint.id)
+            .collect();
+
+        if ids.is_empty() {
+            return Err(format!("No breakpoint at {}", self.symbols.format_address(address)));
+        }
+
+        for id in ids {
+            self.machine.get_processor_mut().get_breakpoints_mut().remove(id);
+        }
+
+        Ok(format!("Breakpoint removed from {}", self.symbols.format_address(address)))
+    }
+
+    fn list_breakpoints(&self) -> String {
+        let breakpoint_manager = self.machine.get_processor().get_breakpoints();
+        let mut lines: Vec<(u32, String)> = Vec::new();
+
+        for breakpoint in breakpoint_manager.get_breakpoints() {
+            lines.push((breakpoint.id, format!("#{}  break at {}{}", breakpoint.id, self.symbols.format_address(breakpoint.address),
+                    Debugger::describe_condition(&breakpoint.condition))));
+        }
+
+        for watchpoint in breakpoint_manager.get_watchpoints() {
+            lines.push((watchpoint.id, format!("#{}  watch {}{}", watchpoint.id, self.describe_range(watchpoint.start, watchpoint.end, watchpoint.kind),
+                    Debugger::describe_condition(&watchpoint.condition))));
+        }
+
+        if lines.is_empty() {
+            return String::from("No breakpoints");
+        }
+
+        lines.sort();
+        lines.into_iter().map(|(_, line)| line).collect::<Vec<String>>().join("\n")
+    }
+
+    fn registers_command(&mut self, arguments: &[&str]) -> Result<String, String> {
+        let mut state: processor::ProcessorState = self.machine.get_state();
+
+        if arguments.is_empty() {
+            return Ok(format!("{}", state).trim_end().to_string());
+        }
+
+        match arguments[0].to_ascii_lowercase().as_str() {
+            "a" => state.accumulator_a = Debugger::parse_value(arguments.get(1), 0xFF)? as u8,
+            "b" => state.accumulator_b = Debugger::parse_value(arguments.get(1), 0xFF)? as u8,
+            "x" => state.index_register = Debugger::parse_value(arguments.get(1), 0xFFFF)? as u16,
+            "pc" => state.program_counter = self.parse_location(arguments.get(1).ok_or("Expected a location")?)?,
+            "sp" => state.stack_pointer = Debugger::parse_value(arguments.get(1), 0xFFFF)? as u16,
+            "ccr" | "cc" => state.condition_code_register = Debugger::parse_value(arguments.get(1), 0xFF)? as u8,
+            register => return Err(format!("Unknown register '{}'", register))
+        }
+
+        self.machine.get_processor_mut().set_state(state);
+
+        Ok(self.describe_position())
+    }
+
+    fn memory_command(&self, arguments: &[&str]) -> Result<String, String> {
+        let start: u16 = self.parse_location(arguments.first().ok_or("Expected an address")?)?;
+        let length: u32 = match arguments.get(1) {
+            Some(_) => Debugger::parse_value(arguments.get(1), 0x10000)?,
+            None => 0x40
+        };
+
+        Ok(hex_dump(self.machine.get_memory_manager(), start, length))
+    }
+
+    fn edit_command(&mut self, arguments: &[&str]) -> Result<String, String> {
+        let address: u16 = self.parse_location(arguments.first().ok_or("Expected an address")?)?;
+        let mut bytes: Vec<u8> = Vec::new();
+
+        for index in 1..arguments.len() {
+            bytes.push(Debugger::parse_value(arguments.get(index), 0xFF)? as u8);
+        }
+
+        if bytes.is_empty() {
+            return Err(String::from("Expected bytes to write"));
+        }
+
+        if address as usize + bytes.len() > 0x10000 {
+            return Err(String::from("Write goes past the end of memory"));
+        }
+
+        self.machine.write_memory(address, &bytes);
+
+        Ok(hex_dump(self.machine.get_memory_manager(), address, bytes.len() as u32))
+    }
+
+    fn backtrace(&self) -> String {
+        let call_stack = self.machine.get_processor().get_call_stack();
+        let backtrace: String = call_stack.format_backtrace(self.machine.get_state().program_counter, &self.symbols);
+
+        match call_stack.get_mismatches().last() {
+            Some(mismatch) => format!("{}\nLast stack mismatch: {}", backtrace, mismatch),
+            None => backtrace
+        }
+    }
+
+    fn disassemble_command(&self, arguments: &[&str]) -> Result<String, String> {
+        let start: u16 = match arguments.first() {
+            Some(location) => self.parse_location(location)?,
+            None => self.find_disassembly_start(self.machine.get_state().program_counter)
+        };
+        let count: usize = match arguments.get(1) {
+            Some(_) => Debugger::parse_value(arguments.get(1), 0x1000)? as usize,
+            None => DISASSEMBLY_LENGTH
+        };
+
+        Ok(self.disassemble(start, count).trim_end().to_string())
+    }
+}
+
+/// Format memory as lines of 16 hexadecimal bytes followed by their ASCII representation
+pub fn hex_dump(memory: &memory_manager::MemoryManager, start: u16, length: u32) -> String {
+    let mut output: String = String::new();
+    let mut offset: u32 = 0;
+    let mut address: u16;
+    let mut byte: u8;
+    let mut ascii: String;
+
+    while offset < length {
+        address = start.wrapping_add(offset as u16);
+        ascii = String::new();
+        write!(output, "{:04X} ", address).unwrap();
+
+        for column in 0..16u32 {
+            if offset + column < length {
+                byte = memory.read(address.wrapping_add(column as u16), 1)[0];
+                write!(output, " {:02X}", byte).unwrap();
+                ascii.push(if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' });
+            } else {
+                output.push_str("   ");
+            }
+        }
+
+        writeln!(output, "  {}", ascii).unwrap();
+        offset += 16;
+    }
+
+    output.trim_end().to_string()
+}
@@ -0,0 +1,52 @@
+use std::any::Any;
+
+use crate::save_state;
+
+/// A peripheral whose registers are mapped into the address space
+///
+/// Devices are attached to a `MemoryManager` with `attach_device`, which routes the bytes the
+/// program reads and writes at their addresses to them. The processor clocks every device
+/// after each instruction and services an IRQ while any device asserts its output.
+pub trait Device: Any + Send {
+    /// A short name identifying the kind of device, e.g. in save states
+    fn name(&self) -> &str;
+
+    /// The number of registers, mapped at consecutive addresses
+    fn size(&self) -> u16;
+
+    /// Read a register as the processor does. Unlike `peek`, this may change the state of the
+    /// device (e.g. reading received data clears the receive flag).
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    /// Read a register without side effects, for debuggers and disassembly
+    fn peek(&self, offset: u16) -> u8;
+
+    fn write(&mut self, offset: u16, value: u8);
+
+    /// Advance the device by the cycles the processor just spent
+    fn tick(&mut self, _cycles: u64) {}
+
+    /// The level of the interrupt output
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /// The status the program exited with through the device, which stops the run. Taking it
+    /// clears it, so the run after can continue.
+    fn take_exit_code(&mut self) -> Option<u8> {
+        None
+    }
+
+    /// Serialize the state of the device for a save state
+    fn save(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restore the state serialized by `save`. Data at least as long as what `save` returns must
+    /// be accepted, since `Machine::load_state` checks the length before restoring anything.
+    fn restore(&mut self, _data: &[u8]) -> Result<(), save_state::SaveStateError> {
+        Ok(())
+    }
+}
@@ -76,7 +76,7 @@ pub enum Opcode {
 }
 
 /// Opcode groups that group together opcodes with different codes but similar meaning
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OpcodeGroup {
     Subtract,
     Add,
@@ -108,7 +108,7 @@ pub enum OperandType {
 }
 
 /// Addressing Mode (indexed, direct, immediate, relative, inherent, extended)
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AddressingMode {
     Inherent,
     Accumulator,
@@ -120,7 +120,7 @@ pub enum AddressingMode {
 }
 
 /// Contains basic information about an opcode
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct OpcodeInfo {
     pub opcode: Opcode,
     pub group: OpcodeGroup,
@@ -131,7 +131,7 @@ pub struct OpcodeInfo {
 }
 
 /// Containts the disassembled information about an instruction
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct InstructionInfo {
     pub opcode_info: OpcodeInfo,
     pub operands: Option<Vec<OperandType>>
@@ -194,8 +194,8 @@ fn match_byte_to_opcode_info(byte: u8) -> Result<OpcodeInfo, DisassemblyError> {
         0x37 => OpcodeInfo { opcode: Opcode::Push, group: OpcodeGroup::StackOperations, instruction_length: 1, cycles: 4, addressing_mode: AddressingMode::Accumulator, increment_program_counter: true },
         0x39 => OpcodeInfo { opcode: Opcode::Return, group: OpcodeGroup::Misc, instruction_length: 1, cycles: 5, addressing_mode: AddressingMode::Inherent, increment_program_counter: false },
         0x3B => OpcodeInfo { opcode: Opcode::ReturnFromInterrupt, group: OpcodeGroup::Misc, instruction_length: 1, cycles: 10, addressing_mode: AddressingMode::Inherent, increment_program_counter: false },
-        0x3E => OpcodeInfo { opcode: Opcode::WaitForInterrupt, group: OpcodeGroup::Misc, instruction_length: 1, cycles: 9, addressing_mode: AddressingMode::Inherent, increment_program_counter: true },
-        0x3F => OpcodeInfo { opcode: Opcode::SoftwareInterrupt, group: OpcodeGroup::Misc, instruction_length: 1, cycles: 12, addressing_mode: AddressingMode::Inherent, increment_program_counter: true },
+        0x3E => OpcodeInfo { opcode: Opcode::WaitForInterrupt, group: OpcodeGroup::Misc, instruction_length: 1, cycles: 9, addressing_mode: AddressingMode::Inherent, increment_program_counter: false },
+        0x3F => OpcodeInfo { opcode: Opcode::SoftwareInterrupt, group: OpcodeGroup::Misc, instruction_length: 1, cycles: 12, addressing_mode: AddressingMode::Inherent, increment_program_counter: false },
         0x40 => OpcodeInfo { opcode: Opcode::Negate, group: OpcodeGroup::BitwiseArithmetic, instruction_length: 1, cycles: 2, addressing_mode: AddressingMode::Accumulator, increment_program_counter: true },
         0x43 => OpcodeInfo { opcode: Opcode::Complement, group: OpcodeGroup::BitwiseArithmetic, instruction_length: 1, cycles: 2, addressing_mode: AddressingMode::Accumulator, increment_program_counter: true },
         0x44 => OpcodeInfo { opcode: Opcode::LogicalShiftRight, group: OpcodeGroup::BitwiseArithmetic, instruction_length: 1, cycles: 2, addressing_mode: AddressingMode::Accumulator, increment_program_counter: true },
@@ -366,7 +366,7 @@ fn disassemble_operands(data: &[u8]) -> Option<Vec<OperandType>> {
         operand16 = OperandType::Immediate16(((data[1] as u16) << 8) | (data[2] as u16));
     }
 
-    let operands = match data[0]{
+    match data[0]{
         0x01 => None,
         0x06 => Some(vec![OperandType::AccumulatorA, OperandType::ConditionCodeRegister]),
         0x07 => Some(vec![OperandType::ConditionCodeRegister, OperandType::AccumulatorA]),
@@ -565,9 +565,7 @@ fn disassemble_operands(data: &[u8]) -> Option<Vec<OperandType>> {
         0xFE => Some(vec![OperandType::IndexRegister, operand16]),
         0xFF => Some(vec![OperandType::IndexRegister, operand16]),
         _ => None
-    };
-
-    operands
+    }
 }
 
 /// Disassemble the next instruction in a byte stream
@@ -577,10 +575,7 @@ pub fn disassemble_instruction(data: &[u8]) -> Result<InstructionInfo, Disassemb
         _ => match_byte_to_opcode_info(data[0])
     };
     
-    let opcode_info = match opcode_info {
-        Ok(info) => info,
-        Err(err) => return Err(err)
-    };
+    let opcode_info = opcode_info?;
 
     // Return an error if data is too short for operands
     if data.len() < opcode_info.instruction_length as usize {
@@ -591,3 +586,121 @@ pub fn disassemble_instruction(data: &[u8]) -> Result<InstructionInfo, Disassemb
 
     Ok(InstructionInfo { opcode_info, operands })
 }
+
+impl Opcode {
+    /// The assembler mnemonic, without the accumulator suffix of instructions such as LDAA
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Opcode::AddBToA => "ABA",
+            Opcode::AddWithCarry => "ADC",
+            Opcode::Add => "ADD",
+            Opcode::And => "AND",
+            Opcode::ArithmeticShiftLeft => "ASL",
+            Opcode::ArithmeticShiftRight => "ASR",
+            Opcode::BranchIfCarryClear => "BCC",
+            Opcode::BranchIfCarrySet => "BCS",
+            Opcode::BranchIfEqual => "BEQ",
+            Opcode::BranchIfGreaterThanEqual => "BGE",
+            Opcode::BranchIfGreaterThan => "BGT",
+            Opcode::BranchIfHigherThan => "BHI",
+            Opcode::BitTest => "BIT",
+            Opcode::BranchIfLessThanEqaul => "BLE",
+            Opcode::BranchIfLowerThanEqual => "BLS",
+            Opcode::BranchIfLessThan => "BLT",
+            Opcode::BranchIfMinus => "BMI",
+            Opcode::BranchIfNotEqual => "BNE",
+            Opcode::BranchIfPlus => "BPL",
+            Opcode::BranchUnconditional => "BRA",
+            Opcode::BranchToSubroutine => "BSR",
+            Opcode::BranchIfOverflowClear => "BVC",
+            Opcode::BranchIfOverflowSet => "BVS",
+            Opcode::CompareAAndB => "CBA",
+            Opcode::ClearCarryFlag => "CLC",
+            Opcode::ClearInterruptMask => "CLI",
+            Opcode::Clear => "CLR",
+            Opcode::ClearOverflowFlag => "CLV",
+            Opcode::Compare => "CMP",
+            Opcode::Complement => "COM",
+            Opcode::CompareIndexRegister => "CPX",
+            Opcode::DecimalAdjustA => "DAA",
+            Opcode::Decrement => "DEC",
+            Opcode::DecrementStackPointer => "DES",
+            Opcode::DecrementIndexRegister => "DEX",
+            Opcode::Xor => "EOR",
+            Opcode::Increment => "INC",
+            Opcode::IncrementStackPointer => "INS",
+            Opcode::IncrementIndexRegister => "INX",
+            Opcode::Jump => "JMP",
+            Opcode::JumpToSubroutine => "JSR",
+            Opcode::LoadAccumulator => "LDA",
+            Opcode::LoadStackPointer => "LDS",
+            Opcode::LoadIndexRegister => "LDX",
+            Opcode::LogicalShiftRight => "LSR",
+            Opcode::Negate => "NEG",
+            Opcode::Nop => "NOP",
+            Opcode::Or => "ORA",
+            Opcode::Push => "PSH",
+            Opcode::Pop => "PUL",
+            Opcode::RotateLeft => "ROL",
+            Opcode::RotateRight => "ROR",
+            Opcode::ReturnFromInterrupt => "RTI",
+            Opcode::Return => "RTS",
+            Opcode::SubtractBFromA => "SBA",
+            Opcode::SubtractWithCarry => "SBC",
+            Opcode::SetCarryFlag => "SEC",
+            Opcode::SetInterruptMask => "SEI",
+            Opcode::SetOverflowFlag => "SEV",
+            Opcode::StoreAccumulator => "STA",
+            Opcode::StoreStackPointer => "STS",
+            Opcode::StoreIndexRegister => "STX",
+            Opcode::Subtract => "SUB",
+            Opcode::SoftwareInterrupt => "SWI",
+            Opcode::TransferAToB => "TAB",
+            Opcode::TransferAToConditionCodes => "TAP",
+            Opcode::TransferBToA => "TBA",
+            Opcode::TransferConditionCodesToA => "TPA",
+            Opcode::Test => "TST",
+            Opcode::TransferStackPointerToIndexRegister => "TSX",
+            Opcode::TransferIndexRegisterToStackPointer => "TXS",
+            Opcode::WaitForInterrupt => "WAI"
+        }
+    }
+}
+
+/// Format a disassembled instruction in assembler syntax (e.g. `LDAA $10,X`). The address of the
+/// instruction is needed to resolve the target of relative branches.
+pub fn format_instruction(instruction_info: &InstructionInfo, address: u16) -> String {
+    let mut text: String = String::from(instruction_info.opcode_info.opcode.mnemonic());
+    let operands: &[OperandType] = instruction_info.operands.as_deref().unwrap_or(&[]);
+    let value: Option<&OperandType>;
+
+    // Instructions which work on either accumulator carry it in their name
+    if instruction_info.opcode_info.addressing_mode != AddressingMode::Inherent {
+        match operands.first() {
+            Some(OperandType::AccumulatorA) => text.push('A'),
+            Some(OperandType::AccumulatorB) => text.push('B'),
+            _ => {}
+        }
+    }
+
+    value = operands.iter().rev().find(|operand| matches!(operand, OperandType::Immediate8(_) | OperandType::Immediate16(_)));
+
+    let operand_text = match (instruction_info.opcode_info.addressing_mode, value) {
+        (AddressingMode::Immediate, Some(OperandType::Immediate8(value))) => format!("#${:02X}", value),
+        (AddressingMode::Immediate, Some(OperandType::Immediate16(value))) => format!("#${:04X}", value),
+        (AddressingMode::Direct, Some(OperandType::Immediate8(address))) => format!("${:02X}", address),
+        (AddressingMode::Extended, Some(OperandType::Immediate16(address))) => format!("${:04X}", address),
+        (AddressingMode::Indexed, Some(OperandType::Immediate8(offset))) => format!("${:02X},X", offset),
+        (AddressingMode::Relative, Some(OperandType::Immediate8(offset))) => {
+            format!("${:04X}", address.wrapping_add(2).wrapping_add(*offset as i8 as u16))
+        },
+        _ => String::new()
+    };
+
+    if !operand_text.is_empty() {
+        text.push(' ');
+        text.push_str(&operand_text);
+    }
+
+    text
+}
@@ -0,0 +1,233 @@
+use crate::device;
+use crate::keypad;
+use crate::machine;
+use crate::profile;
+use crate::save_state;
+use crate::seven_segment;
+
+// The memory map: RAM from $0000 ($0000-$01FF when fully populated), the keypad, the displays
+// and the monitor ROM, which covers the vectors
+pub const KEYPAD_ADDRESS: u16 = 0xC000;
+pub const DISPLAY_ADDRESS: u16 = 0xC110;
+pub const ROM_ADDRESS: u16 = 0xFC00;
+pub const ROM_SIZE: usize = 0x400;
+
+// The processor clock, roughly
+pub const CLOCK_RATE: u64 = 1_000_000;
+
+pub const DIGIT_COUNT: usize = 6;
+
+// Each digit has an addressable latch of 8 outputs, at 16 addresses from the rightmost digit on
+const DIGIT_STRIDE: u16 = 0x10;
+
+// The keypad rows, selected by pulling address line 0, 1 or 2 low. Bit n of a row reads low
+// while the nth key of it is held.
+const KEYPAD_ROWS: [&[u8]; 3] = [&[0xD, 0xA, 0x7, 0x4, 0x1, 0x0], &[0xE, 0xB, 0x8, 0x5, 0x2], &[0xF, 0xC, 0x9, 0x6, 0x3]];
+
+/// The six LED digits of the trainer
+///
+/// Each digit is a 74LS259 addressable latch: address lines 0 to 2 select one of its outputs, and
+/// data bit 0 lights or darkens the segment on it. The outputs follow the layout of the segment
+/// bytes in the monitor: 7 is the decimal point, 6 to 0 are segments a to g. The registers take
+/// $C110-$C16F, from the rightmost digit up to the leftmost.
+#[derive(Default)]
+pub struct Display {
+    /// The latch outputs of every digit, from the leftmost
+    latches: [u8; DIGIT_COUNT]
+}
+
+impl Display {
+    pub fn new() -> Display {
+        Display::default()
+    }
+
+    /// The segments lit on every digit, from the leftmost, as `seven_segment` bitmasks
+    pub fn get_segments(&self) -> [u8; DIGIT_COUNT] {
+        let mut segments: [u8; DIGIT_COUNT] = [0; DIGIT_COUNT];
+
+        for (digit, latch) in self.latches.iter().enumerate() {
+            // Output 6 drives segment a and output 0 segment g, output 7 the decimal point
+            segments[digit] = (latch & 0x80) | (latch & 0x7F).reverse_bits() >> 1;
+        }
+
+        segments
+    }
+
+    /// Draw the digits as ASCII art
+    pub fn render(&self) -> String {
+        seven_segment::render(&self.get_segments())
+    }
+
+    /// The digit and the latch output an offset selects
+    fn decode(offset: u16) -> (usize, u8) {
+        (DIGIT_COUNT - 1 - (offset / DIGIT_STRIDE) as usize, (offset & 7) as u8)
+    }
+}
+
+impl device::Device for Display {
+    fn name(&self) -> &str {
+        "display"
+    }
+
+    fn size(&self) -> u16 {
+        DIGIT_STRIDE * DIGIT_COUNT as u16
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        let (digit, output) = Display::decode(offset);
+
+        (self.latches[digit] >> output) & 1
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        let (digit, output) = Display::decode(offset);
+
+        if value & 1 != 0 {
+            self.latches[digit] |= 1 << output;
+        } else {
+            self.latches[digit] &= !(1 << output);
+        }
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.latches.to_vec()
+    }
+
+    fn restore(&mut self, data: &[u8]) -> Result<(), save_state::SaveStateError> {
+        if data.len() < DIGIT_COUNT {
+            return Err(save_state::SaveStateError::Truncated);
+        }
+
+        self.latches.copy_from_slice(&data[..DIGIT_COUNT]);
+
+        Ok(())
+    }
+}
+
+/// The hexadecimal keypad of the trainer, keys 0 to 15
+///
+/// The RESET key is not part of the keypad, it resets the processor (see `press_reset`).
+#[derive(Default)]
+pub struct Keypad {
+    keys: keypad::Keys
+}
+
+impl Keypad {
+    pub fn new() -> Keypad {
+        Keypad::default()
+    }
+
+    pub fn get_keys(&self) -> &keypad::Keys {
+        &self.keys
+    }
+
+    /// Hold, release or type keys
+    pub fn get_keys_mut(&mut self) -> &mut keypad::Keys {
+        &mut self.keys
+    }
+
+    /// The rows selected by an offset, read together as a wired AND
+    fn read_rows(&self, offset: u16) -> u8 {
+        let mut value: u8 = 0xFF;
+
+        for (row, keys) in KEYPAD_ROWS.iter().enumerate() {
+            if offset & (1 << row) != 0 {
+                continue;
+            }
+
+            for (bit, key) in keys.iter().enumerate() {
+                if self.keys.is_held(*key) {
+                    value &= !(1 << bit);
+                }
+            }
+        }
+
+        value
+    }
+}
+
+impl device::Device for Keypad {
+    fn name(&self) -> &str {
+        "keypad"
+    }
+
+    fn size(&self) -> u16 {
+        8
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        self.read_rows(offset)
+    }
+
+    fn write(&mut self, _offset: u16, _value: u8) {}
+
+    fn tick(&mut self, cycles: u64) {
+        self.keys.tick(cycles);
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.keys.get_held().to_be_bytes().to_vec()
+    }
+
+    fn restore(&mut self, data: &[u8]) -> Result<(), save_state::SaveStateError> {
+        if data.len() < 4 {
+            return Err(save_state::SaveStateError::Truncated);
+        }
+
+        self.keys.set_held(u32::from_be_bytes([data[0], data[1], data[2], data[3]]));
+
+        Ok(())
+    }
+}
+
+/// Build a Heathkit ET-3400 with its 1K monitor ROM, the displays and the keypad. The processor is
+/// reset, so running the machine boots the monitor.
+pub fn build(rom: &[u8]) -> Result<machine::Machine, profile::ProfileError> {
+    let mut machine = machine::Machine::new();
+
+    if rom.len() != ROM_SIZE {
+        return Err(profile::ProfileError::RomSize(rom.len()));
+    }
+
+    profile::install_monitor_rom(&mut machine, ROM_ADDRESS, rom, ROM_SIZE)?;
+
+    machine.get_memory_manager_mut().attach_device(KEYPAD_ADDRESS, Box::new(Keypad::new()));
+    machine.get_memory_manager_mut().attach_device(DISPLAY_ADDRESS, Box::new(Display::new()));
+    machine.get_processor_mut().reset();
+    machine.set_clock_rate(Some(CLOCK_RATE));
+
+    Ok(machine)
+}
+
+/// Press the RESET key, which restarts the monitor
+pub fn press_reset(machine: &mut machine::Machine) {
+    machine.get_processor_mut().reset();
+}
+
+/// Draw the displays of a machine built by `build`
+pub fn render_display(machine: &machine::Machine) -> String {
+    machine.get_memory_manager().find_device::<Display>().map(|display| display.render()).unwrap_or_default()
+}
+
+/// Type the key for a character typed on the host: 0-9 and a-f are the hexadecimal keys, r is
+/// RESET. Returns false for other characters.
+pub fn press_host_key(machine: &mut machine::Machine, character: u8) -> bool {
+    let key: u8 = match character {
+        b'0'..=b'9' => character - b'0',
+        b'a'..=b'f' => character - b'a' + 10,
+        b'A'..=b'F' => character - b'A' + 10,
+        b'r' | b'R' => {
+            press_reset(machine);
+            return true;
+        },
+        _ => return false
+    };
+
+    match machine.get_memory_manager_mut().find_device_mut::<Keypad>() {
+        Some(keypad) => {
+            keypad.get_keys_mut().type_keys(&[key]);
+            true
+        },
+        None => false
+    }
+}
@@ -0,0 +1,493 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Write as FmtWrite;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::net;
+
+use crate::breakpoints;
+use crate::machine;
+use crate::number;
+use crate::processor;
+
+// How many instructions run between checks for an interrupt from the debugger
+const RUN_SLICE: u64 = 10000;
+
+// The largest packet we accept, reported to the debugger in qSupported
+const PACKET_SIZE: usize = 0x1000;
+
+// Signals reported in stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+// The debugger interrupts a running target by sending this byte outside of a packet
+const INTERRUPT: u8 = 0x03;
+
+// Describes the registers to the debugger. `g` returns them in this order (in big endian) and
+// `p`/`P` number them the same way.
+const TARGET_DESCRIPTION: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.momulator.m6800">
+    <reg name="a" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="b" bitsize="8" type="uint8"/>
+    <reg name="x" bitsize="16" type="uint16"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="ccr" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+// The sizes of the registers in the order of the target description
+const REGISTER_SIZES: [usize; 6] = [1, 1, 2, 2, 2, 1];
+
+/// What to do after a packet was handled
+enum Action {
+    Reply(String),
+    Resume,
+    Step,
+    Close(Option<String>)
+}
+
+/// Serves a machine to a debugger speaking the GDB remote serial protocol
+pub struct GdbStub {
+    machine: machine::Machine,
+    /// Breakpoint ids by the (type, address, kind) the debugger used to insert them
+    inserted: HashMap<(u8, u16, u16), u32>
+}
+
+/// Parse a hexadecimal field of a packet
+fn parse_hex(text: &[u8]) -> Option<u32> {
+    u32::try_from(number::parse_digits(std::str::from_utf8(text).ok()?, 16)?).ok()
+}
+
+fn decode_hex(text: &[u8]) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    text.chunks(2).map(|pair| parse_hex(pair).map(|value| value as u8)).collect()
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    let mut output: String = String::with_capacity(data.len() * 2);
+
+    for byte in data {
+        write!(output, "{:02x}", byte).unwrap();
+    }
+
+    output
+}
+
+/// Undo the escaping of binary data (`}` followed by the byte xor 0x20)
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut output: Vec<u8> = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+
+    while let Some(byte) = bytes.next() {
+        match (*byte, bytes.as_slice().first()) {
+            (b'}', Some(escaped)) => {
+                output.push(escaped ^ 0x20);
+                bytes.next();
+            },
+            (byte, _) => output.push(byte)
+        }
+    }
+
+    output
+}
+
+/// Split `address,length` (as used by `m`, `M`, `X`, `Z` and `z`) into its numbers
+fn parse_address_length(text: &[u8]) -> Option<(u16, u32)> {
+    let separator: usize = text.iter().position(|byte| *byte == b',')?;
+    let address: u32 = parse_hex(&text[..separator])?;
+
+    if address > 0xFFFF {
+        return None;
+    }
+
+    Some((address as u16, parse_hex(&text[separator + 1..])?))
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+impl GdbStub {
+    pub fn new(machine: machine::Machine) -> GdbStub {
+        GdbStub { machine, inserted: HashMap::new() }
+    }
+
+    pub fn get_machine(&self) -> &machine::Machine {
+        &self.machine
+    }
+
+    pub fn get_machine_mut(&mut self) -> &mut machine::Machine {
+        &mut self.machine
+    }
+
+    pub fn into_machine(self) -> machine::Machine {
+        self.machine
+    }
+
+    /// Accept a single debugger connection and serve it until it detaches or disconnects
+    pub fn listen(&mut self, address: &str) -> io::Result<()> {
+        let listener = net::TcpListener::bind(address)?;
+        let (stream, _) = listener.accept()?;
+
+        self.serve(stream)
+    }
+
+    /// Serve a connected debugger until it detaches, kills the target, the program exits or the
+    /// debugger disconnects
+    pub fn serve(&mut self, mut stream: net::TcpStream) -> io::Result<()> {
+        let mut reply: String;
+
+        stream.set_nodelay(true)?;
+
+        while let Some(packet) = GdbStub::read_packet(&mut stream)? {
+            reply = match self.handle_packet(&packet) {
+                Action::Reply(reply) => reply,
+                Action::Step => self.step(),
+                Action::Resume => match self.resume(&mut stream)? {
+                    Some(reply) => reply,
+                    None => return Ok(())
+                },
+                Action::Close(reply) => {
+                    if let Some(reply) = reply {
+                        GdbStub::write_packet(&mut stream, &reply)?;
+                    }
+
+                    return Ok(());
+                }
+            };
+
+            GdbStub::write_packet(&mut stream, &reply)?;
+
+            // There is nothing left to debug once the program exited
+            if reply.starts_with('W') {
+                return Ok(());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read the next packet, acknowledging it. Returns `None` once the connection is closed.
+    fn read_packet(stream: &mut net::TcpStream) -> io::Result<Option<Vec<u8>>> {
+        let mut byte: [u8; 1] = [0];
+        let mut packet: Vec<u8> = Vec::new();
+        let mut received_checksum: [u8; 2] = [0; 2];
+
+        loop {
+            // Skip acknowledgements and interrupts arriving while the target is already stopped
+            loop {
+                if stream.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+
+                if byte[0] == b'$' {
+                    break;
+                }
+            }
+
+            packet.clear();
+
+            loop {
+                if stream.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+
+                if byte[0] == b'#' {
+                    break;
+                }
+
+                packet.push(byte[0]);
+            }
+
+            stream.read_exact(&mut received_checksum)?;
+
+            if parse_hex(&received_checksum) == Some(checksum(&packet) as u32) {
+                stream.write_all(b"+")?;
+                return Ok(Some(packet));
+            }
+
+            stream.write_all(b"-")?;
+        }
+    }
+
+    fn write_packet(stream: &mut net::TcpStream, data: &str) -> io::Result<()> {
+        write!(stream, "${}#{:02x}", data, checksum(data.as_bytes()))?;
+        stream.flush()
+    }
+
+    fn read_registers(&self) -> Vec<u8> {
+        let state: processor::ProcessorState = self.machine.get_state();
+        let mut registers: Vec<u8> = vec![state.accumulator_a, state.accumulator_b];
+
+        registers.extend_from_slice(&state.index_register.to_be_bytes());
+        registers.extend_from_slice(&state.program_counter.to_be_bytes());
+        registers.extend_from_slice(&state.stack_pointer.to_be_bytes());
+        registers.push(state.condition_code_register);
+
+        registers
+    }
+
+    fn write_register(&mut self, number: usize, value: &[u8]) -> bool {
+        let mut state: processor::ProcessorState = self.machine.get_state();
+        let word: u16;
+
+        if number >= REGISTER_SIZES.len() || value.len() != REGISTER_SIZES[number] {
+            return false;
+        }
+
+        word = if value.len() == 2 { u16::from_be_bytes([value[0], value[1]]) } else { 0 };
+
+        match number {
+            0 => state.accumulator_a = value[0],
+            1 => state.accumulator_b = value[0],
+            2 => state.index_register = word,
+            3 => state.program_counter = word,
+            4 => state.stack_pointer = word,
+            _ => state.condition_code_register = value[0]
+        }
+
+        self.machine.get_processor_mut().set_state(state);
+
+        true
+    }
+
+    fn read_memory(&self, address: u16, length: u32) -> String {
+        let memory = self.machine.get_memory_manager();
+        let bytes: Vec<u8> = (0..length).map(|offset| memory.read(address.wrapping_add(offset as u16), 1)[0]).collect();
+
+        encode_hex(&bytes)
+    }
+
+    fn write_memory(&mut self, address: u16, data: &[u8]) {
+        for (offset, byte) in data.iter().enumerate() {
+            self.machine.write_memory(address.wrapping_add(offset as u16), &[*byte]);
+        }
+    }
+
+    /// Insert (`Z`) or remove (`z`) a breakpoint or watchpoint. Types 0 and 1 are breakpoints,
+    /// 2 to 4 are write, read and access watchpoints.
+    fn update_breakpoint(&mut self, insert: bool, arguments: &[u8]) -> Option<String> {
+        let mut fields = arguments.split(|byte| *byte == b',');
+        let breakpoint_type: u8 = parse_hex(fields.next()?)? as u8;
+        let address: u32 = parse_hex(fields.next()?)?;
+        let kind: u32 = parse_hex(fields.next()?)?;
+        let key: (u8, u16, u16);
+        let watch_kind: breakpoints::WatchKind;
+        let breakpoint_manager = self.machine.get_processor_mut().get_breakpoints_mut();
+        let id: u32;
+
+        if address > 0xFFFF || kind > 0x10000 || breakpoint_type > 4 {
+            return None;
+        }
+
+        key = (breakpoint_type, address as u16, kind as u16);
+
+        if !insert {
+            if let Some(id) = self.inserted.remove(&key) {
+                breakpoint_manager.remove(id);
+            }
+
+            return Some(String::from("OK"));
+        }
+
+        if self.inserted.contains_key(&key) {
+            return Some(String::from("OK"));
+        }
+
+        watch_kind = match breakpoint_type {
+            2 => breakpoints::WatchKind::Write,
+            3 => breakpoints::WatchKind::Read,
+            _ => breakpoints::WatchKind::ReadWrite
+        };
+
+        id = match breakpoint_type {
+            0 | 1 => breakpoint_manager.add_breakpoint(address as u16, None),
+            _ => breakpoint_manager.add_watchpoint(address as u16, (address + kind.max(1) - 1).min(0xFFFF) as u16, watch_kind, None)
+        };
+
+        self.inserted.insert(key, id);
+
+        Some(String::from("OK"))
+    }
+
+    /// Answer `qXfer:features:read:target.xml:offset,length`
+    fn read_target_description(&self, arguments: &[u8]) -> Option<String> {
+        let (offset, length) = parse_address_length(arguments.strip_prefix(b"target.xml:")?)?;
+        let start: usize = (offset as usize).min(TARGET_DESCRIPTION.len());
+        let end: usize = (start + length as usize).min(TARGET_DESCRIPTION.len());
+
+        Some(format!("{}{}", if end == TARGET_DESCRIPTION.len() { "l" } else { "m" }, &TARGET_DESCRIPTION[start..end]))
+    }
+
+    fn handle_query(&self, query: &[u8]) -> String {
+        if query.starts_with(b"Supported") {
+            format!("PacketSize={:x};qXfer:features:read+", PACKET_SIZE)
+        } else if let Some(arguments) = query.strip_prefix(b"Xfer:features:read:") {
+            self.read_target_description(arguments).unwrap_or_else(|| String::from("E01"))
+        } else if query == b"Attached" {
+            String::from("1")
+        } else if query == b"C" {
+            String::from("QC1")
+        } else if query == b"fThreadInfo" {
+            String::from("m1")
+        } else if query == b"sThreadInfo" {
+            String::from("l")
+        } else {
+            String::new()
+        }
+    }
+
+    fn handle_packet(&mut self, packet: &[u8]) -> Action {
+        let arguments: &[u8] = packet.get(1..).unwrap_or(&[]);
+        let error = || Action::Reply(String::from("E01"));
+        let ok = || Action::Reply(String::from("OK"));
+
+        match packet.first() {
+            Some(b'?') => Action::Reply(format!("S{:02x}", SIGTRAP)),
+            Some(b'g') => Action::Reply(encode_hex(&self.read_registers())),
+            Some(b'G') => match decode_hex(arguments) {
+                Some(values) if values.len() == REGISTER_SIZES.iter().sum() => {
+                    let mut offset: usize = 0;
+
+                    for (number, size) in REGISTER_SIZES.iter().enumerate() {
+                        self.write_register(number, &values[offset..offset + size]);
+                        offset += size;
+                    }
+
+                    ok()
+                },
+                _ => error()
+            },
+            Some(b'p') => match parse_hex(arguments) {
+                Some(number) if (number as usize) < REGISTER_SIZES.len() => {
+                    let offset: usize = REGISTER_SIZES[..number as usize].iter().sum();
+
+                    Action::Reply(encode_hex(&self.read_registers()[offset..offset + REGISTER_SIZES[number as usize]]))
+                },
+                _ => error()
+            },
+            Some(b'P') => {
+                let separator: Option<usize> = arguments.iter().position(|byte| *byte == b'=');
+                let number: Option<u32> = separator.and_then(|separator| parse_hex(&arguments[..separator]));
+                let value: Option<Vec<u8>> = separator.and_then(|separator| decode_hex(&arguments[separator + 1..]));
+
+                match (number, value) {
+                    (Some(number), Some(value)) if self.write_register(number as usize, &value) => ok(),
+                    _ => error()
+                }
+            },
+            Some(b'm') => match parse_address_length(arguments) {
+                Some((address, length)) if length as usize <= PACKET_SIZE / 2 - 8 => Action::Reply(self.read_memory(address, length)),
+                _ => error()
+            },
+            Some(b'M') | Some(b'X') => {
+                let separator: Option<usize> = arguments.iter().position(|byte| *byte == b':');
+                let range: Option<(u16, u32)> = separator.and_then(|separator| parse_address_length(&arguments[..separator]));
+                let data: Option<Vec<u8>> = match (packet[0], separator) {
+                    (b'M', Some(separator)) => decode_hex(&arguments[separator + 1..]),
+                    (_, Some(separator)) => Some(unescape(&arguments[separator + 1..])),
+                    _ => None
+                };
+
+                match (range, data) {
+                    (Some((address, length)), Some(data)) if data.len() == length as usize => {
+                        self.write_memory(address, &data);
+                        ok()
+                    },
+                    _ => error()
+                }
+            },
+            Some(b's') | Some(b'c') => {
+                // Resuming at another address
+                if let Some(address) = parse_hex(arguments).filter(|address| *address <= 0xFFFF) {
+                    self.machine.get_processor_mut().set_program_counter(address as u16);
+                }
+
+                if packet[0] == b's' { Action::Step } else { Action::Resume }
+            },
+            Some(b'Z') | Some(b'z') => match self.update_breakpoint(packet[0] == b'Z', arguments) {
+                Some(reply) => Action::Reply(reply),
+                None => Action::Reply(String::new())
+            },
+            Some(b'q') => Action::Reply(self.handle_query(arguments)),
+            Some(b'H') => ok(),
+            Some(b'D') => Action::Close(Some(String::from("OK"))),
+            Some(b'k') => Action::Close(None),
+            // Anything else is unsupported, which an empty reply tells the debugger
+            _ => Action::Reply(String::new())
+        }
+    }
+
+    /// The stop reply for a run stopped by a breakpoint or watchpoint
+    fn describe_hit(&self, hit: &breakpoints::BreakpointHit) -> String {
+        let access: breakpoints::MemoryAccess = match hit.access {
+            Some(access) => access,
+            None => return format!("S{:02x}", SIGTRAP)
+        };
+        let watchpoint = self.machine.get_processor().get_breakpoints().get_watchpoints().iter().find(|watchpoint| watchpoint.id == hit.id);
+
+        match watchpoint.map(|watchpoint| watchpoint.kind) {
+            Some(breakpoints::WatchKind::Read) => format!("T{:02x}rwatch:{:04x};", SIGTRAP, access.address),
+            Some(breakpoints::WatchKind::ReadWrite) => format!("T{:02x}awatch:{:04x};", SIGTRAP, access.address),
+            _ => format!("T{:02x}watch:{:04x};", SIGTRAP, access.address)
+        }
+    }
+
+    fn step(&mut self) -> String {
+        match self.machine.step() {
+            Ok(_) => match self.machine.get_memory_manager_mut().take_exit_code() {
+                Some(exit_code) => format!("W{:02x}", exit_code),
+                None => format!("S{:02x}", SIGTRAP)
+            },
+            Err(_) => format!("S{:02x}", SIGILL)
+        }
+    }
+
+    /// Run until a breakpoint fires, the program stops or the debugger interrupts. Returns `None`
+    /// if the debugger disconnected meanwhile.
+    fn resume(&mut self, stream: &mut net::TcpStream) -> io::Result<Option<String>> {
+        let mut result: processor::RunResult;
+        let mut byte: [u8; 1] = [0];
+        let mut received: io::Result<usize>;
+        let mut first_slice: bool = true;
+
+        loop {
+            // Every slice starts with an instruction `run` does not check breakpoints for
+            if let Some(hit) = self.machine.get_processor().check_breakpoints().filter(|_| !first_slice) {
+                return Ok(Some(self.describe_hit(&hit)));
+            }
+
+            first_slice = false;
+            result = self.machine.run(&[processor::StopCondition::InstructionBudget(RUN_SLICE)]);
+
+            match result.stop_reason {
+                processor::StopReason::InstructionBudgetExhausted => {},
+                processor::StopReason::Breakpoint(hit) => return Ok(Some(self.describe_hit(&hit))),
+                processor::StopReason::IllegalOpcode(_) => return Ok(Some(format!("S{:02x}", SIGILL))),
+                processor::StopReason::Exited(exit_code) => return Ok(Some(format!("W{:02x}", exit_code))),
+                _ => return Ok(Some(format!("S{:02x}", SIGTRAP)))
+            }
+
+            stream.set_nonblocking(true)?;
+            received = stream.read(&mut byte);
+            stream.set_nonblocking(false)?;
+
+            match received {
+                Ok(0) => return Ok(None),
+                Ok(_) if byte[0] == INTERRUPT => return Ok(Some(format!("S{:02x}", SIGINT))),
+                Ok(_) => {},
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {},
+                Err(err) => return Err(err)
+            }
+        }
+    }
+}
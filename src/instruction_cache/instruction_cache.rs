@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::disassembler;
+
+// The longest 6800 instruction is 3 bytes, so a write can touch instructions starting up to 2
+// bytes before the written address
+const MAX_INSTRUCTION_LENGTH: u16 = 3;
+
+/// Caches decoded instructions by the address they start at
+///
+/// Entries are invalidated whenever a write touches one of their bytes, so programs which modify
+/// their own code keep behaving correctly. They are shared with the processor, so a hit copies
+/// nothing.
+pub struct InstructionCache {
+    entries: HashMap<u16, Arc<disassembler::InstructionInfo>>,
+    hits: u64,
+    misses: u64
+}
+
+impl Default for InstructionCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InstructionCache {
+    pub fn new() -> InstructionCache {
+        InstructionCache {
+            entries: HashMap::new(),
+            hits: 0,
+            misses: 0
+        }
+    }
+
+    /// Get the cached instruction at an address, counting the lookup as a hit or a miss
+    pub fn get(&mut self, address: u16) -> Option<Arc<disassembler::InstructionInfo>> {
+        match self.entries.get(&address) {
+            Some(instruction_info) => {
+                self.hits += 1;
+                Some(Arc::clone(instruction_info))
+            },
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, address: u16, instruction_info: Arc<disassembler::InstructionInfo>) {
+        self.entries.insert(address, instruction_info);
+    }
+
+    pub fn contains(&self, address: u16) -> bool {
+        self.entries.contains_key(&address)
+    }
+
+    /// Drop every cached instruction which has a byte in the written range
+    pub fn invalidate(&mut self, address: u16, size: u16) {
+        let mut start: u16;
+
+        if self.entries.is_empty() {
+            return;
+        }
+
+        // Instructions starting before the written range only matter if they reach into it
+        for distance in 1..MAX_INSTRUCTION_LENGTH {
+            start = address.wrapping_sub(distance);
+
+            if let Some(instruction_info) = self.entries.get(&start) {
+                if instruction_info.opcode_info.instruction_length as u16 > distance {
+                    self.entries.remove(&start);
+                }
+            }
+        }
+
+        for offset in 0..size {
+            self.entries.remove(&address.wrapping_add(offset));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
@@ -0,0 +1,358 @@
+use std::fmt;
+
+/// A JSON document, as exchanged with editors and other tools
+#[derive(Clone, PartialEq, Debug)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// Members in the order they appeared (or were added)
+    Object(Vec<(String, JsonValue)>)
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    position: usize
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.position < self.text.len() && self.text[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.position).copied()
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at offset {}", message, self.position)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), String> {
+        self.skip_whitespace();
+
+        if self.peek() != Some(expected) {
+            return Err(self.error(&format!("Expected '{}'", expected as char)));
+        }
+
+        self.position += 1;
+        Ok(())
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: JsonValue) -> Result<JsonValue, String> {
+        if !self.text[self.position..].starts_with(literal.as_bytes()) {
+            return Err(self.error("Unexpected token"));
+        }
+
+        self.position += literal.len();
+        Ok(value)
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some(b'n') => self.parse_literal("null", JsonValue::Null),
+            Some(b't') => self.parse_literal("true", JsonValue::Bool(true)),
+            Some(b'f') => self.parse_literal("false", JsonValue::Bool(false)),
+            Some(b'"') => Ok(JsonValue::String(self.parse_string()?)),
+            Some(b'[') => self.parse_array(),
+            Some(b'{') => self.parse_object(),
+            Some(byte) if byte == b'-' || byte.is_ascii_digit() => self.parse_number(),
+            Some(_) => Err(self.error("Unexpected character")),
+            None => Err(self.error("Unexpected end of input"))
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, String> {
+        let start: usize = self.position;
+
+        while let Some(byte) = self.peek() {
+            if !(byte.is_ascii_digit() || b"+-.eE".contains(&byte)) {
+                break;
+            }
+
+            self.position += 1;
+        }
+
+        match std::str::from_utf8(&self.text[start..self.position]).ok().and_then(|number| number.parse().ok()) {
+            Some(number) => Ok(JsonValue::Number(number)),
+            None => Err(self.error("Invalid number"))
+        }
+    }
+
+    fn parse_hex_escape(&mut self) -> Result<u32, String> {
+        let digits = self.text.get(self.position..self.position + 4).ok_or_else(|| self.error("Truncated escape"))?;
+        let value = std::str::from_utf8(digits).ok().and_then(|digits| u32::from_str_radix(digits, 16).ok());
+
+        self.position += 4;
+        value.ok_or_else(|| self.error("Invalid escape"))
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut code_point: u32;
+        let mut low_surrogate: u32;
+
+        self.expect(b'"')?;
+
+        loop {
+            match self.peek() {
+                None => return Err(self.error("Unterminated string")),
+                Some(b'"') => {
+                    self.position += 1;
+                    break;
+                },
+                Some(b'\\') => {
+                    self.position += 1;
+
+                    match self.peek() {
+                        Some(b'"') => bytes.push(b'"'),
+                        Some(b'\\') => bytes.push(b'\\'),
+                        Some(b'/') => bytes.push(b'/'),
+                        Some(b'b') => bytes.push(0x08),
+                        Some(b'f') => bytes.push(0x0C),
+                        Some(b'n') => bytes.push(b'\n'),
+                        Some(b'r') => bytes.push(b'\r'),
+                        Some(b't') => bytes.push(b'\t'),
+                        Some(b'u') => {
+                            self.position += 1;
+                            code_point = self.parse_hex_escape()?;
+
+                            // Characters outside the basic plane are escaped as surrogate pairs
+                            if (0xD800..0xDC00).contains(&code_point) && self.text[self.position..].starts_with(b"\\u") {
+                                self.position += 2;
+                                low_surrogate = self.parse_hex_escape()?;
+                                code_point = 0x10000 + ((code_point - 0xD800) << 10) + (low_surrogate.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+
+                            bytes.extend_from_slice(char::from_u32(code_point).unwrap_or('\u{FFFD}').to_string().as_bytes());
+                            continue;
+                        },
+                        _ => return Err(self.error("Invalid escape"))
+                    }
+
+                    self.position += 1;
+                },
+                Some(byte) => {
+                    bytes.push(byte);
+                    self.position += 1;
+                }
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| self.error("Invalid UTF-8 in string"))
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, String> {
+        let mut elements: Vec<JsonValue> = Vec::new();
+
+        self.expect(b'[')?;
+        self.skip_whitespace();
+
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(JsonValue::Array(elements));
+        }
+
+        loop {
+            elements.push(self.parse_value()?);
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(JsonValue::Array(elements));
+                },
+                _ => return Err(self.error("Expected ',' or ']'"))
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, String> {
+        let mut members: Vec<(String, JsonValue)> = Vec::new();
+        let mut key: String;
+
+        self.expect(b'{')?;
+        self.skip_whitespace();
+
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(JsonValue::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            key = self.parse_string()?;
+            self.expect(b':')?;
+            members.push((key, self.parse_value()?));
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(JsonValue::Object(members));
+                },
+                _ => return Err(self.error("Expected ',' or '}'"))
+            }
+        }
+    }
+}
+
+fn write_string(formatter: &mut fmt::Formatter, text: &str) -> fmt::Result {
+    write!(formatter, "\"")?;
+
+    for character in text.chars() {
+        match character {
+            '"' => write!(formatter, "\\\"")?,
+            '\\' => write!(formatter, "\\\\")?,
+            '\n' => write!(formatter, "\\n")?,
+            '\r' => write!(formatter, "\\r")?,
+            '\t' => write!(formatter, "\\t")?,
+            character if (character as u32) < 0x20 => write!(formatter, "\\u{:04x}", character as u32)?,
+            character => write!(formatter, "{}", character)?
+        }
+    }
+
+    write!(formatter, "\"")
+}
+
+impl JsonValue {
+    pub fn parse(text: &str) -> Result<JsonValue, String> {
+        let mut parser = Parser { text: text.as_bytes(), position: 0 };
+        let value: JsonValue = parser.parse_value()?;
+
+        parser.skip_whitespace();
+
+        if parser.position != text.len() {
+            return Err(parser.error("Trailing characters"));
+        }
+
+        Ok(value)
+    }
+
+    /// Build an object from its members
+    pub fn object(members: Vec<(&str, JsonValue)>) -> JsonValue {
+        JsonValue::Object(members.into_iter().map(|(key, value)| (String::from(key), value)).collect())
+    }
+
+    /// Get a member of an object
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(text) => Some(text),
+            _ => None
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(number) => Some(*number),
+            _ => None
+        }
+    }
+
+    /// Get a number which is a non negative integer
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            JsonValue::Number(number) if *number >= 0.0 && number.fract() == 0.0 && *number <= u64::MAX as f64 => Some(*number as u64),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(value) => Some(*value),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(elements) => Some(elements),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for JsonValue {
+    /// Serialize the value as compact JSON
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonValue::Null => write!(formatter, "null"),
+            JsonValue::Bool(value) => write!(formatter, "{}", value),
+            JsonValue::Number(number) if number.is_finite() => write!(formatter, "{}", number),
+            JsonValue::Number(_) => write!(formatter, "null"),
+            JsonValue::String(text) => write_string(formatter, text),
+            JsonValue::Array(elements) => {
+                write!(formatter, "[")?;
+
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        write!(formatter, ",")?;
+                    }
+
+                    write!(formatter, "{}", element)?;
+                }
+
+                write!(formatter, "]")
+            },
+            JsonValue::Object(members) => {
+                write!(formatter, "{{")?;
+
+                for (index, (key, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        write!(formatter, ",")?;
+                    }
+
+                    write_string(formatter, key)?;
+                    write!(formatter, ":{}", value)?;
+                }
+
+                write!(formatter, "}}")
+            }
+        }
+    }
+}
+
+impl From<bool> for JsonValue {
+    fn from(value: bool) -> JsonValue {
+        JsonValue::Bool(value)
+    }
+}
+
+impl From<u64> for JsonValue {
+    fn from(value: u64) -> JsonValue {
+        JsonValue::Number(value as f64)
+    }
+}
+
+impl From<&str> for JsonValue {
+    fn from(value: &str) -> JsonValue {
+        JsonValue::String(String::from(value))
+    }
+}
+
+impl From<String> for JsonValue {
+    fn from(value: String) -> JsonValue {
+        JsonValue::String(value)
+    }
+}
+
+impl From<Vec<JsonValue>> for JsonValue {
+    fn from(value: Vec<JsonValue>) -> JsonValue {
+        JsonValue::Array(value)
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+
+// How long a typed key is held, and then released before the next one. At about 20ms monitors
+// debounce it as a single press.
+pub const DEFAULT_HOLD_CYCLES: u64 = 20000;
+
+/// The keys held on a keypad, for the keypad devices of the trainer boards to scan
+///
+/// Keys are numbered from 0 to 31 by each board. They are held with `press` and `release`, or
+/// typed one after another with `type_keys`, which holds each for a number of emulated cycles as
+/// the keypad device is ticked.
+pub struct Keys {
+    /// Bit n is set while key n is held
+    held: u32,
+    /// Keys waiting to be typed
+    script: VecDeque<u8>,
+    /// The key being typed, if it is still held
+    typed_key: Option<u8>,
+    /// The cycles left until the key being typed is released, or until the next one is pressed
+    script_cycles: u64,
+    hold_cycles: u64
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Keys {
+    pub fn new() -> Keys {
+        Keys { held: 0, script: VecDeque::new(), typed_key: None, script_cycles: 0, hold_cycles: DEFAULT_HOLD_CYCLES }
+    }
+
+    pub fn press(&mut self, key: u8) {
+        self.held |= 1 << (key & 0x1F);
+    }
+
+    pub fn release(&mut self, key: u8) {
+        self.held &= !(1 << (key & 0x1F));
+    }
+
+    pub fn is_held(&self, key: u8) -> bool {
+        self.held & (1 << (key & 0x1F)) != 0
+    }
+
+    /// The keys held, bit n for key n
+    pub fn get_held(&self) -> u32 {
+        self.held
+    }
+
+    pub fn set_held(&mut self, held: u32) {
+        self.held = held;
+    }
+
+    /// Queue keys to be pressed and released in turn as the emulation runs
+    pub fn type_keys(&mut self, keys: &[u8]) {
+        self.script.extend(keys);
+    }
+
+    /// Whether keys queued by `type_keys` are still being typed
+    pub fn is_typing(&self) -> bool {
+        !self.script.is_empty() || self.typed_key.is_some()
+    }
+
+    /// The cycles typed keys are held, and released after
+    pub fn set_hold_cycles(&mut self, cycles: u64) {
+        self.hold_cycles = cycles;
+    }
+
+    /// Advance the typing by a number of cycles
+    pub fn tick(&mut self, cycles: u64) {
+        if self.script_cycles > cycles {
+            self.script_cycles -= cycles;
+            return;
+        }
+
+        self.script_cycles = 0;
+
+        if let Some(key) = self.typed_key.take() {
+            self.release(key);
+            self.script_cycles = self.hold_cycles;
+        } else if let Some(key) = self.script.pop_front() {
+            self.press(key);
+            self.typed_key = Some(key);
+            self.script_cycles = self.hold_cycles;
+        }
+    }
+}
@@ -1,8 +1,105 @@
+#[path = "acia/acia.rs"]
+pub mod acia;
+
+#[path = "breakpoints/breakpoints.rs"]
+pub mod breakpoints;
+
+#[path = "call_stack/call_stack.rs"]
+pub mod call_stack;
+
+#[path = "console/console.rs"]
+pub mod console;
+#[path = "coverage/coverage.rs"]
+pub mod coverage;
+
+#[path = "dap_server/dap_server.rs"]
+pub mod dap_server;
+
+#[path = "debugger/debugger.rs"]
+pub mod debugger;
+
+#[path = "device/device.rs"]
+pub mod device;
+
 #[path = "disassembler/disassembler.rs"]
 pub mod disassembler;
 
+#[path = "et3400/et3400.rs"]
+pub mod et3400;
+
+#[path = "gdb_stub/gdb_stub.rs"]
+pub mod gdb_stub;
+
+#[path = "instruction_cache/instruction_cache.rs"]
+pub mod instruction_cache;
+
+#[path = "json/json.rs"]
+pub mod json;
+
+#[path = "keypad/keypad.rs"]
+pub mod keypad;
+
+#[path = "line_table/line_table.rs"]
+pub mod line_table;
+
+#[path = "machine/machine.rs"]
+pub mod machine;
+
+#[path = "machine_config/machine_config.rs"]
+pub mod machine_config;
+
+#[path = "mek6800d2/mek6800d2.rs"]
+pub mod mek6800d2;
+
 #[path = "memory_manager/memory_manager.rs"]
 pub mod memory_manager;
 
+#[path = "number/number.rs"]
+pub mod number;
+
+#[path = "observer/observer.rs"]
+pub mod observer;
+
+#[path = "pia/pia.rs"]
+pub mod pia;
+
 #[path = "processor/processor.rs"]
 pub mod processor;
+
+#[path = "profile/profile.rs"]
+pub mod profile;
+
+#[path = "profiler/profiler.rs"]
+pub mod profiler;
+
+#[path = "ptm/ptm.rs"]
+pub mod ptm;
+
+#[path = "rewind_buffer/rewind_buffer.rs"]
+pub mod rewind_buffer;
+
+#[path = "save_state/save_state.rs"]
+pub mod save_state;
+
+#[path = "semihosting/semihosting.rs"]
+pub mod semihosting;
+#[path = "serial/serial.rs"]
+pub mod serial;
+
+#[path = "seven_segment/seven_segment.rs"]
+pub mod seven_segment;
+
+#[path = "swtpc/swtpc.rs"]
+pub mod swtpc;
+
+#[path = "symbols/symbols.rs"]
+pub mod symbols;
+
+#[path = "toml/toml.rs"]
+pub mod toml;
+
+#[path = "trace/trace.rs"]
+pub mod trace;
+
+#[path = "tui/tui.rs"]
+pub mod tui;
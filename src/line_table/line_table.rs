@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+/// Maps the lines of an assembly source file to the addresses of the instructions they produced
+#[derive(Default)]
+pub struct LineTable {
+    lines: BTreeMap<usize, u16>,
+    addresses: BTreeMap<u16, usize>
+}
+
+fn is_hex_byte(text: &str) -> bool {
+    text.len() == 2 && text.chars().all(|character| character.is_ascii_hexdigit())
+}
+
+impl LineTable {
+    pub fn new() -> LineTable {
+        LineTable::default()
+    }
+
+    /// Parse an `as0` listing, where every line producing code looks like
+    /// `0011 0003 c9 f0   adcb #$f0`: the decimal source line, the hexadecimal address and the
+    /// bytes. Lines without code (comments, labels, `org`, ...) are skipped.
+    pub fn parse_listing(text: &str) -> LineTable {
+        let mut table = LineTable::new();
+        let mut tokens: std::str::SplitWhitespace;
+
+        for listing_line in text.lines() {
+            tokens = listing_line.split_whitespace();
+
+            if let (Some(line), Some(address), Some(first_byte)) = (tokens.next(), tokens.next(), tokens.next()) {
+                if line.len() != 4 || address.len() != 4 || !is_hex_byte(first_byte) {
+                    continue;
+                }
+
+                if let (Ok(line), Ok(address)) = (line.parse(), u16::from_str_radix(address, 16)) {
+                    table.insert(line, address);
+                }
+            }
+        }
+
+        table
+    }
+
+    pub fn load_listing(path: &str) -> io::Result<LineTable> {
+        Ok(LineTable::parse_listing(&fs::read_to_string(path)?))
+    }
+
+    pub fn insert(&mut self, line: usize, address: u16) {
+        self.lines.entry(line).or_insert(address);
+        self.addresses.insert(address, line);
+    }
+
+    /// Get the line of the instruction starting at an address
+    pub fn get_line(&self, address: u16) -> Option<usize> {
+        self.addresses.get(&address).copied()
+    }
+
+    /// Get the address of the first instruction of a line
+    pub fn get_address(&self, line: usize) -> Option<u16> {
+        self.lines.get(&line).copied()
+    }
+
+    /// Find the first line at or after `line` which produced code, with its address. Used to move
+    /// breakpoints set on comments or labels to the next instruction.
+    pub fn find_code_line(&self, line: usize) -> Option<(usize, u16)> {
+        self.lines.range(line..).next().map(|(line, address)| (*line, *address))
+    }
+
+    /// Every line which produced code with the address of its first instruction, in line order
+    pub fn get_lines(&self) -> Vec<(usize, u16)> {
+        self.lines.iter().map(|(line, address)| (*line, *address)).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+}
@@ -0,0 +1,154 @@
+use crate::device;
+use crate::disassembler;
+use crate::memory_manager;
+use crate::processor;
+use crate::save_state;
+
+/// A complete system which owns its processor and memory
+///
+/// Unlike a `Processor` borrowing a `MemoryManager`, a machine can be stored in other structs,
+/// sent to another thread and have its memory inspected between steps.
+pub struct Machine {
+    processor: processor::Processor<'static>,
+    /// The clock rate of the emulated board in Hz, which front ends run it at in real time
+    clock_rate: Option<u64>
+}
+
+/// The name a device is saved under, e.g. `acia@8004`
+fn device_state_name(address: u16, device: &dyn device::Device) -> String {
+    format!("{}@{:04X}", device.name(), address)
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Machine {
+    pub fn new() -> Machine {
+        Machine::with_memory_manager(Box::default())
+    }
+
+    pub fn with_memory_manager(memory_manager: Box<memory_manager::MemoryManager>) -> Machine {
+        let mut processor = processor::Processor::new();
+
+        processor.set_owned_memory_manager(memory_manager);
+
+        Machine { processor, clock_rate: None }
+    }
+
+    /// Copy a program into memory and point the program counter at its first byte
+    pub fn load_program(&mut self, address: u16, program: &[u8]) {
+        self.write_memory(address, program);
+        self.processor.set_program_counter(address);
+    }
+
+    pub fn get_processor(&self) -> &processor::Processor<'static> {
+        &self.processor
+    }
+
+    pub fn get_processor_mut(&mut self) -> &mut processor::Processor<'static> {
+        &mut self.processor
+    }
+
+    pub fn get_memory_manager(&self) -> &memory_manager::MemoryManager {
+        // The memory manager is set on construction and never removed
+        self.processor.get_memory_manager().unwrap()
+    }
+
+    pub fn get_memory_manager_mut(&mut self) -> &mut memory_manager::MemoryManager {
+        self.processor.get_memory_manager_mut().unwrap()
+    }
+
+    /// The clock rate to run the machine at in real time, None to run it as fast as possible
+    pub fn get_clock_rate(&self) -> Option<u64> {
+        self.clock_rate
+    }
+
+    pub fn set_clock_rate(&mut self, clock_rate: Option<u64>) {
+        self.clock_rate = clock_rate;
+    }
+
+    pub fn read_memory(&self, address: u16, size: u16) -> &[u8] {
+        self.get_memory_manager().read(address, size)
+    }
+
+    pub fn write_memory(&mut self, address: u16, data: &[u8]) {
+        self.processor.write_memory(address, data).unwrap();
+    }
+
+    pub fn get_state(&self) -> processor::ProcessorState {
+        self.processor.get_state()
+    }
+
+    pub fn step(&mut self) -> Result<disassembler::InstructionInfo, processor::EmulationError> {
+        self.processor.emulate_instruction()
+    }
+
+    pub fn run(&mut self, conditions: &[processor::StopCondition]) -> processor::RunResult {
+        self.processor.run(conditions)
+    }
+
+    pub fn step_over(&mut self, conditions: &[processor::StopCondition]) -> processor::RunResult {
+        self.processor.step_over(conditions)
+    }
+
+    pub fn step_out(&mut self, conditions: &[processor::StopCondition]) -> Option<processor::RunResult> {
+        self.processor.step_out(conditions)
+    }
+
+    /// Take a snapshot of the processor, memory and interrupt state
+    pub fn save_state(&self) -> save_state::SaveState {
+        save_state::SaveState {
+            processor_state: self.processor.get_state(),
+            cycles: self.processor.get_cycles(),
+            interrupts: self.processor.get_interrupt_state(),
+            memory: self.get_memory_manager().dump().to_vec(),
+            devices: self.get_memory_manager().get_devices().iter()
+                .map(|(address, device)| save_state::DeviceState { name: device_state_name(*address, *device), data: device.save() })
+                .collect()
+        }
+    }
+
+    /// Restore a snapshot taken by `save_state`. Every device in it must be attached to the
+    /// machine, at the same address; otherwise nothing is restored. The undo history and the call
+    /// stack belong to the run which was replaced, so they are dropped.
+    pub fn load_state(&mut self, state: &save_state::SaveState) -> Result<(), save_state::SaveStateError> {
+        let mut devices: Vec<(u16, &mut dyn device::Device)> = self.get_memory_manager_mut().get_devices_mut();
+        let mut matched: Vec<usize> = Vec::with_capacity(state.devices.len());
+        let mut found: Option<usize>;
+
+        // Match every device and check its data before changing anything, so a state which does
+        // not fit this machine leaves it as it was
+        for device_state in &state.devices {
+            found = devices.iter().position(|(address, device)| device_state_name(*address, &**device) == device_state.name);
+
+            match found {
+                Some(index) if device_state.data.len() < devices[index].1.save().len() => {
+                    return Err(save_state::SaveStateError::Truncated);
+                },
+                Some(index) => matched.push(index),
+                None => return Err(save_state::SaveStateError::UnknownDevice(device_state.name.clone()))
+            }
+        }
+
+        for (index, device_state) in matched.iter().zip(&state.devices) {
+            devices[*index].1.restore(&device_state.data)?;
+        }
+
+        // Load the memory behind the back of the devices, which were restored above
+        self.get_memory_manager_mut().load(0, &state.memory);
+        self.get_memory_manager_mut().refresh_devices();
+        self.processor.set_state(state.processor_state);
+        self.processor.set_cycles(state.cycles);
+        self.processor.set_interrupt_state(state.interrupts);
+        self.processor.get_call_stack_mut().clear();
+
+        if let Some(buffer) = self.processor.get_rewind_buffer_mut() {
+            buffer.clear();
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,378 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::acia;
+use crate::console;
+use crate::device;
+use crate::machine;
+use crate::memory_manager;
+use crate::pia;
+use crate::ptm;
+use crate::serial;
+use crate::toml;
+
+// The byte read from addresses nothing answers at, as the data bus floats high
+const OPEN_BUS: u8 = 0xFF;
+
+/// Why a machine description could not be read or built
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Syntax(String),
+    /// A value is missing, has the wrong type or is out of range
+    Invalid(String)
+}
+
+/// Where a serial device is connected
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PortConfig {
+    Terminal,
+    /// Nothing is connected, the device never receives anything
+    None,
+    Files { input: Option<PathBuf>, output: Option<PathBuf> }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DeviceKind {
+    Acia(PortConfig),
+    Console(PortConfig),
+    Pia,
+    Ptm
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DeviceConfig {
+    pub kind: DeviceKind,
+    pub address: u16,
+    pub interrupt_line: memory_manager::InterruptLine
+}
+
+/// RAM or ROM, with its contents loaded from a file
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MemoryConfig {
+    pub region: memory_manager::Region,
+    pub file: Option<PathBuf>
+}
+
+/// A machine described in a TOML file
+///
+/// ```toml
+/// name = "SWTPC 6800"
+/// clock_rate = 898000
+///
+/// [[ram]]
+/// address = 0x0000
+/// size = 0x8000
+///
+/// [[rom]]
+/// address = 0xE000
+/// file = "swtbug.bin"
+///
+/// [[mirror]]
+/// address = 0xFC00
+/// size = 0x400
+/// target = 0xE000
+///
+/// [[device]]
+/// type = "acia"
+/// address = 0x8004
+/// interrupt = "irq"
+/// port = "terminal"
+/// ```
+///
+/// RAM may be loaded from a `file` too, and ROM may be given a `size` larger than its file. Devices
+/// are `acia`, `console`, `pia` and `ptm`, with their interrupt output wired to `irq` (the default),
+/// `nmi` or `none`. An ACIA or a console is connected to the `terminal` (the default), to `none`, or
+/// reads an `input` file and writes an `output` file. Files are relative to the description.
+/// Addresses nothing is mapped at read $FF and ignore writes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MachineConfig {
+    pub name: String,
+    /// The processor clock in Hz, None to run as fast as possible
+    pub clock_rate: Option<u64>,
+    pub ram: Vec<MemoryConfig>,
+    pub rom: Vec<MemoryConfig>,
+    pub mirrors: Vec<memory_manager::Mirror>,
+    pub devices: Vec<DeviceConfig>
+}
+
+/// Reject keys a table does not know, which are most likely typing mistakes
+fn check_keys(table: &toml::TomlValue, keys: &[&str], context: &str) -> Result<(), ConfigError> {
+    for (key, _) in table.as_table().unwrap_or_default() {
+        if !keys.contains(&key.as_str()) {
+            return Err(ConfigError::Invalid(format!("unknown key '{}' in {}", key, context)));
+        }
+    }
+
+    Ok(())
+}
+
+fn get_integer(table: &toml::TomlValue, key: &str, maximum: u64, context: &str) -> Result<Option<u64>, ConfigError> {
+    match table.get(key) {
+        Some(value) => match value.as_u64() {
+            Some(number) if number <= maximum => Ok(Some(number)),
+            _ => Err(ConfigError::Invalid(format!("'{}' in {} must be a number from 0 to {}", key, context, maximum)))
+        },
+        None => Ok(None)
+    }
+}
+
+fn get_string<'a>(table: &'a toml::TomlValue, key: &str, context: &str) -> Result<Option<&'a str>, ConfigError> {
+    match table.get(key) {
+        Some(value) => match value.as_str() {
+            Some(text) => Ok(Some(text)),
+            None => Err(ConfigError::Invalid(format!("'{}' in {} must be a string", key, context)))
+        },
+        None => Ok(None)
+    }
+}
+
+fn require<T>(value: Option<T>, key: &str, context: &str) -> Result<T, ConfigError> {
+    value.ok_or_else(|| ConfigError::Invalid(format!("{} needs '{}'", context, key)))
+}
+
+fn get_address(table: &toml::TomlValue, key: &str, context: &str) -> Result<u16, ConfigError> {
+    Ok(require(get_integer(table, key, 0xFFFF, context)?, key, context)? as u16)
+}
+
+/// The tables of an array of tables, e.g. every `[[ram]]`
+fn get_tables<'a>(document: &'a toml::TomlValue, key: &str) -> Result<&'a [toml::TomlValue], ConfigError> {
+    match document.get(key) {
+        Some(value) => match value.as_array() {
+            Some(tables) if tables.iter().all(|table| table.as_table().is_some()) => Ok(tables),
+            _ => Err(ConfigError::Invalid(format!("'{}' must be written as [[{}]] tables", key, key)))
+        },
+        None => Ok(&[])
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, ConfigError> {
+    fs::read(path).map_err(|error| ConfigError::Io(path.to_path_buf(), error))
+}
+
+impl MachineConfig {
+    /// Read a machine description, with the files it names relative to `directory`
+    pub fn parse(text: &str, directory: &Path) -> Result<MachineConfig, ConfigError> {
+        let document: toml::TomlValue = toml::TomlValue::parse(text).map_err(ConfigError::Syntax)?;
+        let mut config = MachineConfig {
+            name: String::from("machine"),
+            clock_rate: None,
+            ram: Vec::new(),
+            rom: Vec::new(),
+            mirrors: Vec::new(),
+            devices: Vec::new()
+        };
+        let mut context: String;
+        let mut address: u16;
+        let mut size: Option<u64>;
+        let mut file: Option<PathBuf>;
+
+        check_keys(&document, &["name", "clock_rate", "ram", "rom", "mirror", "device"], "the machine")?;
+
+        if let Some(name) = get_string(&document, "name", "the machine")? {
+            config.name = name.to_string();
+        }
+
+        config.clock_rate = get_integer(&document, "clock_rate", u32::MAX as u64, "the machine")?.filter(|clock_rate| *clock_rate > 0);
+
+        for (kind, regions) in [("ram", &mut config.ram), ("rom", &mut config.rom)] {
+            for (index, table) in get_tables(&document, kind)?.iter().enumerate() {
+                context = format!("{} {}", kind, index + 1);
+                check_keys(table, &["address", "size", "file"], &context)?;
+
+                address = get_address(table, "address", &context)?;
+                size = get_integer(table, "size", memory_manager::BUFFER_SIZE as u64 - address as u64, &context)?;
+                file = get_string(table, "file", &context)?.map(|path| directory.join(path));
+
+                // The size of a ROM defaults to the size of its image
+                if kind == "rom" && size.is_none() {
+                    size = Some(read_file(require(file.as_deref(), "file", &context)?)?.len() as u64);
+                }
+
+                regions.push(MemoryConfig {
+                    region: memory_manager::Region { address, size: require(size, "size", &context)? as u32 },
+                    file
+                });
+            }
+        }
+
+        for (index, table) in get_tables(&document, "mirror")?.iter().enumerate() {
+            context = format!("mirror {}", index + 1);
+            check_keys(table, &["address", "size", "target"], &context)?;
+
+            address = get_address(table, "address", &context)?;
+            config.mirrors.push(memory_manager::Mirror {
+                region: memory_manager::Region {
+                    address,
+                    size: require(get_integer(table, "size", memory_manager::BUFFER_SIZE as u64 - address as u64, &context)?, "size", &context)? as u32
+                },
+                target: get_address(table, "target", &context)?
+            });
+        }
+
+        for (index, table) in get_tables(&document, "device")?.iter().enumerate() {
+            context = format!("device {}", index + 1);
+            config.devices.push(MachineConfig::parse_device(table, directory, &context)?);
+        }
+
+        Ok(config)
+    }
+
+    fn parse_device(table: &toml::TomlValue, directory: &Path, context: &str) -> Result<DeviceConfig, ConfigError> {
+        let input: Option<PathBuf> = get_string(table, "input", context)?.map(|path| directory.join(path));
+        let output: Option<PathBuf> = get_string(table, "output", context)?.map(|path| directory.join(path));
+        let kind: DeviceKind;
+        let port: PortConfig;
+
+        kind = match require(get_string(table, "type", context)?, "type", context)? {
+            name @ ("acia" | "console") => {
+                check_keys(table, &["type", "address", "interrupt", "port", "input", "output"], context)?;
+
+                port = match get_string(table, "port", context)? {
+                    _ if input.is_some() || output.is_some() => PortConfig::Files { input, output },
+                    Some("terminal") | None => PortConfig::Terminal,
+                    Some("none") => PortConfig::None,
+                    Some(port) => return Err(ConfigError::Invalid(format!("unknown port '{}' in {}", port, context)))
+                };
+
+                if name == "acia" {
+                    DeviceKind::Acia(port)
+                } else {
+                    DeviceKind::Console(port)
+                }
+            },
+            "pia" => DeviceKind::Pia,
+            "ptm" => DeviceKind::Ptm,
+            name => return Err(ConfigError::Invalid(format!("unknown device type '{}' in {}", name, context)))
+        };
+
+        if !matches!(kind, DeviceKind::Acia(_) | DeviceKind::Console(_)) {
+            check_keys(table, &["type", "address", "interrupt"], context)?;
+        }
+
+        Ok(DeviceConfig {
+            kind,
+            address: get_address(table, "address", context)?,
+            interrupt_line: match get_string(table, "interrupt", context)? {
+                Some("irq") | None => memory_manager::InterruptLine::Irq,
+                Some("nmi") => memory_manager::InterruptLine::Nmi,
+                Some("none") => memory_manager::InterruptLine::None,
+                Some(line) => return Err(ConfigError::Invalid(format!("unknown interrupt '{}' in {}, expected irq, nmi or none", line, context)))
+            }
+        })
+    }
+
+    /// Read a machine description from a file
+    pub fn load_file(path: &str) -> Result<MachineConfig, ConfigError> {
+        let text: String = fs::read_to_string(path).map_err(|error| ConfigError::Io(PathBuf::from(path), error))?;
+
+        MachineConfig::parse(&text, Path::new(path).parent().unwrap_or(Path::new("")))
+    }
+
+    /// Open the port of a serial device. Monitors on an ACIA expect carriage returns for the line
+    /// feeds typed on the terminal, programs using a console get them as typed.
+    pub fn build_port(port: &PortConfig, translate_newlines: bool) -> Result<Box<dyn serial::SerialPort>, ConfigError> {
+        Ok(match port {
+            PortConfig::Terminal => Box::new(serial::StdioPort { translate_newlines }),
+            PortConfig::None => Box::new(serial::BufferPort::new()),
+            PortConfig::Files { input, output } => Box::new(serial::FilePort::new(
+                    input.as_deref().and_then(Path::to_str), output.as_deref().and_then(Path::to_str))
+                .map_err(|error| ConfigError::Io(input.clone().or(output.clone()).unwrap_or_default(), error))?)
+        })
+    }
+
+    fn build_device(kind: &DeviceKind) -> Result<Box<dyn device::Device>, ConfigError> {
+        Ok(match kind {
+            DeviceKind::Acia(port) => Box::new(acia::Acia::new(MachineConfig::build_port(port, true)?)),
+            DeviceKind::Console(port) => Box::new(console::Console::new(MachineConfig::build_port(port, false)?)),
+            DeviceKind::Pia => Box::new(pia::Pia::new()),
+            DeviceKind::Ptm => Box::new(ptm::Ptm::new())
+        })
+    }
+
+    /// Build the machine, loading the memory files. The processor is reset, so it starts from the
+    /// reset vector.
+    pub fn build(&self) -> Result<machine::Machine, ConfigError> {
+        let mut machine = machine::Machine::new();
+        let memory_manager: &mut memory_manager::MemoryManager = machine.get_memory_manager_mut();
+        let mut mapped: Vec<bool> = vec![false; memory_manager::BUFFER_SIZE];
+        let mut data: Vec<u8>;
+        let mut device: Box<dyn device::Device>;
+        let mut id: u32;
+        let mut start: usize;
+        let mut end: usize;
+
+        // Mark the addresses of a region as answered, returning whether any of them already were
+        let mut map = |region: memory_manager::Region| -> bool {
+            let mut overlaps: bool = false;
+
+            for offset in 0..region.size {
+                overlaps |= mapped[region.address.wrapping_add(offset as u16) as usize];
+                mapped[region.address.wrapping_add(offset as u16) as usize] = true;
+            }
+
+            overlaps
+        };
+
+        for (memory, read_only) in self.ram.iter().map(|memory| (memory, false)).chain(self.rom.iter().map(|memory| (memory, true))) {
+            data = match &memory.file {
+                Some(path) => read_file(path)?,
+                None => Vec::new()
+            };
+
+            if data.len() > memory.region.size as usize {
+                return Err(ConfigError::Invalid(format!("{} does not fit the {} bytes at ${:04X}",
+                        memory.file.as_ref().unwrap().display(), memory.region.size, memory.region.address)));
+            }
+
+            // ROM past the end of its image reads as erased EPROM
+            data.resize(memory.region.size as usize, if read_only { 0xFF } else { 0 });
+
+            if read_only {
+                memory_manager.load_rom(memory.region.address, &data);
+            } else {
+                memory_manager.load(memory.region.address, &data);
+            }
+
+            map(memory.region);
+        }
+
+        for (index, device_config) in self.devices.iter().enumerate() {
+            device = MachineConfig::build_device(&device_config.kind)?;
+
+            if map(memory_manager::Region { address: device_config.address, size: device.size() as u32 }) {
+                return Err(ConfigError::Invalid(format!("device {} at ${:04X} overlaps RAM, ROM or another device",
+                        index + 1, device_config.address)));
+            }
+
+            id = memory_manager.attach_device(device_config.address, device);
+            memory_manager.set_interrupt_line(id, device_config.interrupt_line);
+        }
+
+        for mirror in &self.mirrors {
+            memory_manager.add_mirror(mirror.region.address, mirror.region.size, mirror.target);
+            map(mirror.region);
+        }
+
+        // Nothing answers at the addresses left, which behave like ROM full of the open bus value
+        start = 0;
+
+        while start < mapped.len() {
+            if mapped[start] {
+                start += 1;
+                continue;
+            }
+
+            end = mapped[start..].iter().position(|mapped| *mapped).map_or(mapped.len(), |length| start + length);
+
+            memory_manager.load_rom(start as u16, &vec![OPEN_BUS; end - start]);
+            start = end;
+        }
+
+        machine.get_processor_mut().reset();
+        machine.set_clock_rate(self.clock_rate);
+
+        Ok(machine)
+    }
+}
@@ -0,0 +1,653 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use momulator::acia::Acia;
+use momulator::console::Console;
+use momulator::coverage;
+use momulator::dap_server::DapServer;
+use momulator::et3400;
+use momulator::debugger::Debugger;
+use momulator::gdb_stub::GdbStub;
+use momulator::line_table::LineTable;
+use momulator::machine::Machine;
+use momulator::machine_config::{MachineConfig, PortConfig};
+use momulator::mek6800d2;
+use momulator::number::parse_number;
+use momulator::processor::*;
+use momulator::profile::MachineProfile;
+use momulator::profiler::Profiler;
+use momulator::save_state::SaveState;
+use momulator::semihosting::Semihosting;
+use momulator::serial;
+use momulator::serial::{CharacterMode, SerialPort, StdioPort};
+use momulator::swtpc;
+use momulator::symbols::SymbolTable;
+use momulator::trace;
+use momulator::tui::Tui;
+
+const USAGE: &str = "Usage:
+    momulator run [options] [program.bin]
+    momulator debug [options] [program.bin]
+    momulator tui [options] [program.bin]
+    momulator gdb [options] [program.bin]
+    momulator dap
+    momulator trace-diff <left trace> <right trace>
+
+Options:
+    --address <addr>           Address to load the program at (default 0)
+    --start <addr>             Address to start running from (default: the load address)
+    --stop-at <addr>           Stop when the program counter reaches an address
+    --max-instructions <n>     Stop after running this many instructions
+    --load-state <file>        Restore a save state before running
+    --save-state <file>        Write a save state when the run stops
+    --trace <file>             Log every instruction to a trace file
+    --trace-format <format>    Either text (default) or binary
+    --symbols <file>           Load symbols (e.g. an as0 listing) for the debugger
+    --coverage <file>          Write a code coverage report of the source in --listing
+    --coverage-format <format> Either lcov (default) or cobertura
+    --listing <file>           The as0 listing of the program, for coverage reports. The source
+                               is expected next to it with an .asm extension.
+    --profile <file>           Write the routines and instructions taking the most cycles
+    --folded <file>            Write the cycles of every call chain as folded stacks, for
+                               flame graph tools
+    --acia <addr>              Attach an MC6850 ACIA at an address, connected to the terminal
+    --console <addr>           Attach a console at an address: writing it prints a byte, reading
+                               it takes the next byte typed or 0, and bit 0 of the byte after it
+                               is set while one is waiting
+    --semihosting <addr>       Attach a semihosting device at an address, letting the program
+                               use the console and files, and exit with a status code which
+                               the run command exits with
+    --machine <name>           Build a ready-made machine instead of a bare processor and RAM,
+                               described in a file (e.g. a .toml file) or one of:
+                               swtpc (an SWTPC 6800, needs a monitor ROM such as SWTBUG),
+                               et3400 (a Heathkit ET-3400 trainer, needs its 1K monitor ROM) or
+                               mek6800d2 (a Motorola MEK6800D2 kit, needs a 1K ROM such as JBUG)
+    --rom <file>               The monitor ROM image of the machine
+    --serial <port>            Connect the ACIA of the swtpc or mek6800d2 machine to the terminal
+                               or to none (the default for the mek6800d2, whose keypad takes
+                               the terminal)
+    --serial-input <file>      Connect the ACIA of the machine to a file it receives from
+    --serial-output <file>     Connect the ACIA of the machine to a file it transmits to
+    --port <port>              Port the gdb command listens on, on localhost (default 1234)
+
+Addresses and numbers may be decimal, hexadecimal prefixed with $ or 0x, or binary prefixed
+with % or 0b.";
+
+// How many routines and instructions the profile report lists
+const PROFILE_LENGTH: usize = 20;
+
+// How often a run at the clock rate of the machine catches up with real time, redraws the front
+// panel and reads its keys, in milliseconds
+const PACING_INTERVAL: u64 = 20;
+
+// How many cycles a machine without a clock rate runs between checks for Ctrl-C
+const UNPACED_SLICE_CYCLES: u64 = 100000;
+
+/// Options of the `run`, `debug`, `tui` and `gdb` commands
+struct RunOptions {
+    program_path: Option<String>,
+    load_address: u16,
+    start_address: Option<u16>,
+    stop_conditions: Vec<StopCondition>,
+    load_state_path: Option<String>,
+    save_state_path: Option<String>,
+    trace_path: Option<String>,
+    trace_format: trace::TraceFormat,
+    symbols_path: Option<String>,
+    coverage_path: Option<String>,
+    coverage_format: coverage::CoverageFormat,
+    listing_path: Option<String>,
+    profile_path: Option<String>,
+    folded_path: Option<String>,
+    acia_address: Option<u16>,
+    console_address: Option<u16>,
+    semihosting_address: Option<u16>,
+    machine_profile: Option<MachineProfile>,
+    machine_config_path: Option<String>,
+    rom_path: Option<String>,
+    /// Where the ACIA of the ready-made machine is connected, if not where it usually is
+    serial_port: Option<PortConfig>,
+    gdb_port: u16
+}
+
+impl RunOptions {
+    /// Whether a ready-made machine is built, which boots from its reset vector
+    fn is_ready_made(&self) -> bool {
+        self.machine_profile.is_some() || self.machine_config_path.is_some()
+    }
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("momulator: {}", message);
+    process::exit(2);
+}
+
+fn parse_address(text: &str) -> u16 {
+    match parse_number(text) {
+        Some(address) if address <= 0xFFFF => address as u16,
+        _ => exit_with_error(&format!("invalid address '{}'", text))
+    }
+}
+
+fn parse_run_options(arguments: &[String]) -> RunOptions {
+    let mut options = RunOptions {
+        program_path: None,
+        load_address: 0,
+        start_address: None,
+        stop_conditions: vec![StopCondition::InfiniteLoop],
+        load_state_path: None,
+        save_state_path: None,
+        trace_path: None,
+        trace_format: trace::TraceFormat::Text,
+        symbols_path: None,
+        coverage_path: None,
+        coverage_format: coverage::CoverageFormat::Lcov,
+        listing_path: None,
+        profile_path: None,
+        folded_path: None,
+        acia_address: None,
+        console_address: None,
+        semihosting_address: None,
+        machine_profile: None,
+        machine_config_path: None,
+        rom_path: None,
+        serial_port: None,
+        gdb_port: 1234
+    };
+    let mut arguments = arguments.iter();
+    let mut value: &String;
+    let mut serial_input: Option<PathBuf> = None;
+    let mut serial_output: Option<PathBuf> = None;
+
+    while let Some(argument) = arguments.next() {
+        if !argument.starts_with("--") {
+            options.program_path = Some(argument.clone());
+            continue;
+        }
+
+        value = match arguments.next() {
+            Some(value) => value,
+            None => exit_with_error(&format!("missing value for {}", argument))
+        };
+
+        match argument.as_str() {
+            "--address" => options.load_address = parse_address(value),
+            "--start" => options.start_address = Some(parse_address(value)),
+            "--stop-at" => options.stop_conditions.push(StopCondition::ProgramCounterEquals(parse_address(value))),
+            "--max-instructions" => match parse_number(value) {
+                Some(budget) => options.stop_conditions.push(StopCondition::InstructionBudget(budget)),
+                None => exit_with_error(&format!("invalid number '{}'", value))
+            },
+            "--load-state" => options.load_state_path = Some(value.clone()),
+            "--save-state" => options.save_state_path = Some(value.clone()),
+            "--trace" => options.trace_path = Some(value.clone()),
+            "--trace-format" => options.trace_format = match value.as_str() {
+                "text" => trace::TraceFormat::Text,
+                "binary" => trace::TraceFormat::Binary,
+                _ => exit_with_error(&format!("unknown trace format '{}'", value))
+            },
+            "--symbols" => options.symbols_path = Some(value.clone()),
+            "--coverage" => options.coverage_path = Some(value.clone()),
+            "--coverage-format" => options.coverage_format = match value.as_str() {
+                "lcov" => coverage::CoverageFormat::Lcov,
+                "cobertura" => coverage::CoverageFormat::Cobertura,
+                _ => exit_with_error(&format!("unknown coverage format '{}'", value))
+            },
+            "--listing" => options.listing_path = Some(value.clone()),
+            "--profile" => options.profile_path = Some(value.clone()),
+            "--folded" => options.folded_path = Some(value.clone()),
+            "--acia" => options.acia_address = Some(parse_address(value)),
+            "--console" => options.console_address = Some(parse_address(value)),
+            "--semihosting" => options.semihosting_address = Some(parse_address(value)),
+            "--machine" => match MachineProfile::from_name(value) {
+                Some(profile) => options.machine_profile = Some(profile),
+                None if value.ends_with(".toml") || std::path::Path::new(value).exists() => options.machine_config_path = Some(value.clone()),
+                None => exit_with_error(&format!("unknown machine '{}'", value))
+            },
+            "--rom" => options.rom_path = Some(value.clone()),
+            "--serial" => options.serial_port = match value.as_str() {
+                "terminal" => Some(PortConfig::Terminal),
+                "none" => Some(PortConfig::None),
+                _ => exit_with_error(&format!("unknown serial port '{}', expected terminal or none", value))
+            },
+            "--serial-input" => serial_input = Some(PathBuf::from(value)),
+            "--serial-output" => serial_output = Some(PathBuf::from(value)),
+            "--port" => options.gdb_port = match value.parse() {
+                Ok(port) => port,
+                Err(_) => exit_with_error(&format!("invalid port '{}'", value))
+            },
+            _ => exit_with_error(&format!("unknown option {}\n\n{}", argument, USAGE))
+        }
+    }
+
+    // Files take the place of the terminal, as in a machine description
+    if serial_input.is_some() || serial_output.is_some() {
+        options.serial_port = Some(PortConfig::Files { input: serial_input, output: serial_output });
+    }
+
+    if options.serial_port.is_some() && !matches!(options.machine_profile, Some(MachineProfile::Swtpc | MachineProfile::Mek6800D2)) {
+        exit_with_error("only the swtpc and mek6800d2 machines have an ACIA to connect (--serial)");
+    }
+
+    if options.machine_profile.is_some() && options.rom_path.is_none() {
+        exit_with_error("the machine needs its monitor ROM (--rom)");
+    }
+
+    if options.program_path.is_none() && options.load_state_path.is_none() && !options.is_ready_made() {
+        exit_with_error(&format!("nothing to run\n\n{}", USAGE));
+    }
+
+    if options.coverage_path.is_some() && options.listing_path.is_none() {
+        exit_with_error("coverage reports need the listing of the program (--listing)");
+    }
+
+    options
+}
+
+/// Create the ready-made machine given on the command line, or a bare one
+fn build_profile_machine(options: &RunOptions) -> Machine {
+    let rom: Vec<u8>;
+    let path: &String;
+    let port: Box<dyn SerialPort>;
+
+    if let Some(path) = &options.machine_config_path {
+        return match MachineConfig::load_file(path).and_then(|config| config.build()) {
+            Ok(machine) => machine,
+            Err(err) => exit_with_error(&format!("cannot build the machine in {}: {:?}", path, err))
+        };
+    }
+
+    let profile = match options.machine_profile {
+        Some(profile) => profile,
+        None => return Machine::new()
+    };
+
+    // Checked when parsing the options
+    path = options.rom_path.as_ref().unwrap();
+    rom = match fs::read(path) {
+        Ok(rom) => rom,
+        Err(err) => exit_with_error(&format!("cannot read {}: {}", path, err))
+    };
+
+    // The keypad of the MEK6800D2 takes the terminal, so by default nothing is connected to its ACIA
+    port = match MachineConfig::build_port(options.serial_port.as_ref().unwrap_or(match profile {
+        MachineProfile::Mek6800D2 => &PortConfig::None,
+        _ => &PortConfig::Terminal
+    }), true) {
+        Ok(port) => port,
+        Err(err) => exit_with_error(&format!("cannot connect the serial port: {:?}", err))
+    };
+
+    let result = match profile {
+        MachineProfile::Swtpc => swtpc::build(&rom, port),
+        MachineProfile::Et3400 => et3400::build(&rom),
+        MachineProfile::Mek6800D2 => mek6800d2::build(&rom, port)
+    };
+
+    match result {
+        Ok(machine) => machine,
+        Err(err) => exit_with_error(&format!("cannot build the {} machine: {:?}", profile.get_name(), err))
+    }
+}
+
+/// Create a machine with the program and state given on the command line
+fn build_machine(options: &RunOptions) -> Machine {
+    let mut machine = build_profile_machine(options);
+
+    // Devices come first, a save state restores them too
+    if let Some(address) = options.acia_address {
+        machine.get_memory_manager_mut().attach_device(address, Box::new(Acia::new(Box::new(StdioPort::new()))));
+    }
+
+    if let Some(address) = options.console_address {
+        machine.get_memory_manager_mut().attach_device(address, Box::new(Console::new(Box::new(StdioPort::new()))));
+    }
+
+    if let Some(address) = options.semihosting_address {
+        machine.get_memory_manager_mut().attach_device(address, Box::new(Semihosting::new()));
+    }
+
+    if let Some(path) = &options.load_state_path {
+        match SaveState::read_from_file(path) {
+            Ok(state) => {
+                if let Err(err) = machine.load_state(&state) {
+                    exit_with_error(&format!("cannot restore {}: {:?}", path, err));
+                }
+            },
+            Err(err) => exit_with_error(&format!("cannot read {}: {:?}", path, err))
+        }
+    }
+
+    if let Some(path) = &options.program_path {
+        match fs::read(path) {
+            // A ready-made machine boots its monitor, the program only goes into memory
+            Ok(program) if options.is_ready_made() => machine.write_memory(options.load_address, &program),
+            Ok(program) => machine.load_program(options.load_address, &program),
+            Err(err) => exit_with_error(&format!("cannot read {}: {}", path, err))
+        }
+    }
+
+    if let Some(start_address) = options.start_address {
+        machine.get_processor_mut().set_program_counter(start_address);
+    }
+
+    if let Some(path) = &options.trace_path {
+        match trace::Tracer::to_file(options.trace_format, path) {
+            Ok(tracer) => machine.get_processor_mut().set_tracer(tracer),
+            Err(err) => exit_with_error(&format!("cannot create {}: {}", path, err))
+        }
+    }
+
+    if options.coverage_path.is_some() {
+        machine.get_processor_mut().add_observer(Box::new(coverage::Coverage::new()));
+    }
+
+    if options.profile_path.is_some() || options.folded_path.is_some() {
+        machine.get_processor_mut().add_observer(Box::new(Profiler::new()));
+    }
+
+    machine
+}
+
+/// Write the coverage report of the listing given on the command line
+fn write_coverage(machine: &Machine, options: &RunOptions, path: &str) {
+    let listing_path: &str = options.listing_path.as_ref().unwrap();
+    let source_path: String = std::path::Path::new(listing_path).with_extension("asm").to_string_lossy().into_owned();
+    let line_table: LineTable;
+    let report: String;
+
+    line_table = match LineTable::load_listing(listing_path) {
+        Ok(line_table) => line_table,
+        Err(err) => exit_with_error(&format!("cannot read {}: {}", listing_path, err))
+    };
+
+    report = machine.get_processor().find_observer::<coverage::Coverage>().unwrap()
+        .format_report(options.coverage_format, &line_table, machine.get_memory_manager(), &source_path);
+
+    write_file(path, report);
+}
+
+/// Finish the trace and write the save state and coverage report requested on the command line
+fn finish_machine(machine: &mut Machine, options: &RunOptions) {
+    if let Some(mut tracer) = machine.get_processor_mut().take_tracer() {
+        if let Err(err) = tracer.finish() {
+            exit_with_error(&format!("cannot write trace: {}", err));
+        }
+    }
+
+    if let Some(path) = &options.save_state_path {
+        if let Err(err) = machine.save_state().write_to_file(path) {
+            exit_with_error(&format!("cannot write {}: {}", path, err));
+        }
+    }
+
+    if let Some(path) = &options.coverage_path {
+        write_coverage(machine, options, path);
+    }
+
+    if let Some(profiler) = machine.get_processor().find_observer::<Profiler>() {
+        write_profile(profiler, options);
+    }
+}
+
+fn write_file(path: &str, contents: String) {
+    if let Err(err) = fs::write(path, contents) {
+        exit_with_error(&format!("cannot write {}: {}", path, err));
+    }
+}
+
+/// Write the profile report and folded stacks requested on the command line
+fn write_profile(profiler: &Profiler, options: &RunOptions) {
+    let symbols: SymbolTable = load_symbols(options);
+
+    if let Some(path) = &options.profile_path {
+        write_file(path, profiler.format_report(&symbols, PROFILE_LENGTH));
+    }
+
+    if let Some(path) = &options.folded_path {
+        write_file(path, profiler.to_folded(&symbols));
+    }
+}
+
+/// Run a machine in slices, at its clock rate when it has one, until it stops or Ctrl-C is
+/// pressed. For a board with a front panel, the display is redrawn when it changes and the keys
+/// typed are passed to the keypad.
+fn run_paced(machine: &mut Machine, options: &RunOptions, clock_rate: Option<u64>) -> RunResult {
+    let interval_cycles: u64 = match clock_rate {
+        Some(clock_rate) => (clock_rate * PACING_INTERVAL / 1000).max(1),
+        None => UNPACED_SLICE_CYCLES
+    };
+    let panel: Option<MachineProfile> = options.machine_profile.filter(|profile| profile.render_display(machine).is_some());
+    let mut port = StdioPort::new();
+    let mut statistics = RunStatistics::default();
+    let mut conditions: Vec<StopCondition>;
+    let mut displayed: Option<String> = None;
+    let mut display: String;
+    let mut started: Instant;
+    let mut result: RunResult;
+    let mut budget_exhausted: bool;
+
+    loop {
+        started = Instant::now();
+
+        // The budgets given on the command line are for the whole run
+        conditions = options.stop_conditions.iter().map(|condition| match *condition {
+            StopCondition::InstructionBudget(budget) => StopCondition::InstructionBudget(budget.saturating_sub(statistics.instructions)),
+            StopCondition::CycleBudget(budget) => StopCondition::CycleBudget(budget.saturating_sub(statistics.cycles)),
+            condition => condition
+        }).collect();
+        conditions.push(StopCondition::CycleBudget(interval_cycles));
+
+        result = machine.run(&conditions);
+        statistics.instructions += result.statistics.instructions;
+        statistics.cycles += result.statistics.cycles;
+
+        if let Some(profile) = panel {
+            // The keys typed go to the keypad, unless the ACIA took the terminal
+            if options.serial_port != Some(PortConfig::Terminal) {
+                while let Some(character) = port.receive() {
+                    profile.press_host_key(machine, character);
+                }
+            }
+
+            display = profile.render_display(machine).unwrap_or_default();
+
+            if displayed.as_ref() != Some(&display) {
+                // Draw over the previous display
+                if let Some(previous) = &displayed {
+                    print!("\x1b[{}A", previous.lines().count());
+                }
+
+                for line in display.lines() {
+                    println!("{}\x1b[K", line);
+                }
+
+                let _ = io::stdout().flush();
+                displayed = Some(display);
+            }
+        }
+
+        budget_exhausted = options.stop_conditions.iter().any(|condition| matches!(condition,
+                StopCondition::CycleBudget(budget) if statistics.cycles >= *budget));
+
+        if result.stop_reason != StopReason::CycleBudgetExhausted || budget_exhausted {
+            return RunResult { stop_reason: result.stop_reason, statistics };
+        }
+
+        if serial::take_interrupted() {
+            return RunResult { stop_reason: StopReason::Interrupted, statistics };
+        }
+
+        if clock_rate.is_some() {
+            thread::sleep(Duration::from_millis(PACING_INTERVAL).saturating_sub(started.elapsed()));
+        }
+    }
+}
+
+fn run_command(arguments: &[String]) {
+    let options = parse_run_options(arguments);
+    let mut machine = build_machine(&options);
+    let result: RunResult;
+
+    // Hand every key to the machine as it is typed, without echoing it. Not being on a terminal
+    // is fine.
+    let character_mode: Option<CharacterMode> = if options.is_ready_made() {
+        CharacterMode::enable().ok()
+    } else {
+        None
+    };
+
+    // Ctrl-C is only caught in character mode, and then noticed between slices
+    result = match (machine.get_clock_rate(), &character_mode) {
+        (None, None) => machine.run(&options.stop_conditions),
+        (clock_rate, _) => run_paced(&mut machine, &options, clock_rate)
+    };
+    drop(character_mode);
+
+    // A program exiting through semihosting has said all it wanted to
+    if let StopReason::Exited(exit_code) = result.stop_reason {
+        finish_machine(&mut machine, &options);
+        process::exit(exit_code as i32);
+    }
+
+    println!("Stopped: {:?} after {} instructions ({} cycles)",
+            result.stop_reason, result.statistics.instructions, result.statistics.cycles);
+    machine.get_state().print();
+
+    finish_machine(&mut machine, &options);
+
+    // Like a shell does for a program killed by SIGINT
+    if result.stop_reason == StopReason::Interrupted {
+        process::exit(130);
+    }
+}
+
+fn load_symbols(options: &RunOptions) -> SymbolTable {
+    match &options.symbols_path {
+        Some(path) => match SymbolTable::load_file(path) {
+            Ok(symbols) => symbols,
+            Err(err) => exit_with_error(&format!("cannot read {}: {}", path, err))
+        },
+        None => SymbolTable::new()
+    }
+}
+
+fn build_debugger(options: &RunOptions) -> Debugger {
+    Debugger::new(build_machine(options), load_symbols(options))
+}
+
+fn debug_command(arguments: &[String]) {
+    let options = parse_run_options(arguments);
+    let mut debugger: Debugger;
+    let mut line: String = String::new();
+    let mut last_command: String = String::new();
+    let stdin = io::stdin();
+
+    debugger = build_debugger(&options);
+    println!("{}", debugger.execute("regs"));
+    println!("{}", debugger.execute("disasm"));
+
+    while !debugger.should_quit() {
+        print!("> ");
+        io::stdout().flush().unwrap();
+
+        line.clear();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+
+        // An empty line repeats the previous command, like most monitors do
+        if !line.trim().is_empty() {
+            last_command = line.trim().to_string();
+        }
+
+        println!("{}", debugger.execute(&last_command));
+    }
+
+    finish_machine(debugger.get_machine_mut(), &options);
+}
+
+fn tui_command(arguments: &[String]) {
+    let options = parse_run_options(arguments);
+    let mut tui = Tui::new(build_debugger(&options));
+
+    if let Err(err) = tui.run_terminal() {
+        exit_with_error(&format!("terminal UI failed: {}", err));
+    }
+
+    finish_machine(tui.get_debugger_mut().get_machine_mut(), &options);
+}
+
+fn gdb_command(arguments: &[String]) {
+    let options = parse_run_options(arguments);
+    let address: String = format!("127.0.0.1:{}", options.gdb_port);
+    let mut stub = GdbStub::new(build_machine(&options));
+
+    println!("Waiting for a debugger on {}", address);
+
+    if let Err(err) = stub.listen(&address) {
+        exit_with_error(&format!("debugger connection failed: {}", err));
+    }
+
+    finish_machine(stub.get_machine_mut(), &options);
+}
+
+/// Serve the Debug Adapter Protocol over stdin and stdout. The program is given by the editor.
+fn dap_command() {
+    let mut server = DapServer::new(Box::new(io::stdout()));
+
+    if let Err(err) = server.serve(Box::new(io::stdin())) {
+        exit_with_error(&format!("debug adapter failed: {}", err));
+    }
+}
+
+fn trace_diff_command(arguments: &[String]) {
+    let traces: Vec<Vec<trace::TraceRecord>>;
+
+    if arguments.len() != 2 {
+        exit_with_error(USAGE);
+    }
+
+    traces = arguments.iter().map(|path| match trace::read_trace_file(path) {
+        Ok(records) => records,
+        Err(err) => exit_with_error(&format!("cannot read {}: {}", path, err))
+    }).collect();
+
+    match trace::diff_traces(&traces[0], &traces[1]) {
+        None => println!("Traces are identical ({} instructions)", traces[0].len()),
+        Some(divergence) => {
+            println!("Traces diverge at instruction {}", divergence.index);
+
+            for (path, record) in arguments.iter().zip([divergence.left, divergence.right].iter()) {
+                match record {
+                    Some(record) => println!("{}: {}", path, record.to_text()),
+                    None => println!("{}: <end of trace>", path)
+                }
+            }
+
+            process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    let arguments: Vec<String> = env::args().collect();
+
+    match arguments.get(1).map(|command| command.as_str()) {
+        Some("run") => run_command(&arguments[2..]),
+        Some("debug") => debug_command(&arguments[2..]),
+        Some("tui") => tui_command(&arguments[2..]),
+        Some("gdb") => gdb_command(&arguments[2..]),
+        Some("dap") => dap_command(),
+        Some("trace-diff") => trace_diff_command(&arguments[2..]),
+        Some("--help") | Some("-h") | Some("help") => println!("{}", USAGE),
+        _ => exit_with_error(USAGE)
+    }
+}
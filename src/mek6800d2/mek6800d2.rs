@@ -0,0 +1,286 @@
+use crate::acia;
+use crate::device;
+use crate::keypad;
+use crate::machine;
+use crate::pia;
+use crate::profile;
+use crate::save_state;
+use crate::serial;
+use crate::seven_segment;
+
+// The memory map: RAM from $0000 and the monitor's scratch RAM at $A000, the I/O, and the monitor
+// ROM at $E000, mirrored over the vectors
+pub const SYSTEM_PIA_ADDRESS: u16 = 0x8004;
+pub const ACIA_ADDRESS: u16 = 0x8008;
+pub const PANEL_ADDRESS: u16 = 0x8020;
+pub const ROM_ADDRESS: u16 = 0xE000;
+pub const ROM_SIZE: usize = 0x400;
+
+// The processor clock
+pub const CLOCK_RATE: u64 = 614_400;
+
+pub const DIGIT_COUNT: usize = 6;
+
+// The keys beside the hexadecimal ones
+pub const KEY_MEMORY: u8 = 16;
+pub const KEY_ESCAPE: u8 = 17;
+pub const KEY_REGISTER: u8 = 18;
+pub const KEY_GO: u8 = 19;
+pub const KEY_PUNCH: u8 = 20;
+pub const KEY_LOAD: u8 = 21;
+pub const KEY_TRACE: u8 = 22;
+pub const KEY_BREAKPOINT: u8 = 23;
+
+// The keys of every keypad column, from the one on row PA0 to the one on row PA3
+const KEYPAD_COLUMNS: [[u8; 4]; DIGIT_COUNT] = [
+    [0x0, 0x4, 0x8, 0xC],
+    [0x1, 0x5, 0x9, 0xD],
+    [0x2, 0x6, 0xA, 0xE],
+    [0x3, 0x7, 0xB, 0xF],
+    [KEY_MEMORY, KEY_ESCAPE, KEY_REGISTER, KEY_GO],
+    [KEY_PUNCH, KEY_LOAD, KEY_TRACE, KEY_BREAKPOINT]
+];
+
+// The key sense input, on PA7
+const KEY_SENSE: u8 = 0x80;
+
+// The display is multiplexed, the segments lit are worked out over frames of this many cycles
+// (20ms)
+const FRAME_CYCLES: u64 = CLOCK_RATE / 50;
+
+// A segment shows as lit when it was on for this fraction of the share of a frame its digit gets,
+// so the glimpses between setting the segments and selecting the next digit do not show
+const LIT_FRACTION: u64 = 8;
+
+/// The keyboard and display interface of the kit: a PIA scanning the six digits and the keypad
+///
+/// PA0-PA6 drive segments a to g of the digits selected by PB0-PB5, the leftmost on PB0. The
+/// keypad shares the lines: the keys of a column are scanned by selecting its digit, and the key on
+/// a row PA0-PA3 driven low pulls the sense input PA7 low while it is held. Its keys are the
+/// hexadecimal ones and the `KEY_*` constants. RESET and ABORT are not part of the keypad, see
+/// `press_reset` and `press_abort`.
+pub struct Panel {
+    pia: pia::Pia,
+    keys: keypad::Keys,
+    /// The cycles every segment of every digit was on in the current frame
+    lit_cycles: [[u64; 7]; DIGIT_COUNT],
+    frame_cycles: u64,
+    /// The segments lit in the last frame
+    segments: [u8; DIGIT_COUNT]
+}
+
+impl Default for Panel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Panel {
+    pub fn new() -> Panel {
+        Panel { pia: pia::Pia::new(), keys: keypad::Keys::new(), lit_cycles: [[0; 7]; DIGIT_COUNT], frame_cycles: 0, segments: [0; DIGIT_COUNT] }
+    }
+
+    pub fn get_pia(&self) -> &pia::Pia {
+        &self.pia
+    }
+
+    pub fn get_keys(&self) -> &keypad::Keys {
+        &self.keys
+    }
+
+    /// Hold, release or type keys
+    pub fn get_keys_mut(&mut self) -> &mut keypad::Keys {
+        &mut self.keys
+    }
+
+    /// The segments lit on every digit in the last frame, from the leftmost, as `seven_segment`
+    /// bitmasks
+    pub fn get_segments(&self) -> [u8; DIGIT_COUNT] {
+        self.segments
+    }
+
+    /// Draw the digits as ASCII art
+    pub fn render(&self) -> String {
+        seven_segment::render(&self.segments)
+    }
+
+    /// The lines driven by the PIA outputs: the segment lines and the digit lines
+    fn get_outputs(&self) -> (u8, u8) {
+        (self.pia.get_pins(pia::Port::A) & self.pia.get_data_direction(pia::Port::A),
+            self.pia.get_pins(pia::Port::B) & self.pia.get_data_direction(pia::Port::B))
+    }
+
+    /// Drive the key sense input from the keys on the scanned rows and columns
+    fn update_sense(&mut self) {
+        let (rows, columns) = self.get_outputs();
+        let mut sense: u8 = KEY_SENSE;
+
+        for (column, keys) in KEYPAD_COLUMNS.iter().enumerate() {
+            for (row, key) in keys.iter().enumerate() {
+                if columns & (1 << column) != 0 && rows & (1 << row) == 0 && self.keys.is_held(*key) {
+                    sense = 0;
+                }
+            }
+        }
+
+        self.pia.set_input(pia::Port::A, !KEY_SENSE | sense);
+    }
+
+    /// Account for the segments on during some cycles, and work out what the display shows at the
+    /// end of a frame
+    fn scan_display(&mut self, cycles: u64) {
+        let (segments, digits) = self.get_outputs();
+
+        for digit in 0..DIGIT_COUNT {
+            for segment in 0..7 {
+                if digits & (1 << digit) != 0 && segments & (1 << segment) != 0 {
+                    self.lit_cycles[digit][segment] += cycles;
+                }
+            }
+        }
+
+        self.frame_cycles += cycles;
+
+        if self.frame_cycles < FRAME_CYCLES {
+            return;
+        }
+
+        for digit in 0..DIGIT_COUNT {
+            self.segments[digit] = 0;
+
+            for segment in 0..7 {
+                if self.lit_cycles[digit][segment] * DIGIT_COUNT as u64 * LIT_FRACTION >= self.frame_cycles {
+                    self.segments[digit] |= 1 << segment;
+                }
+            }
+        }
+
+        self.lit_cycles = [[0; 7]; DIGIT_COUNT];
+        self.frame_cycles = 0;
+    }
+}
+
+impl device::Device for Panel {
+    fn name(&self) -> &str {
+        "panel"
+    }
+
+    fn size(&self) -> u16 {
+        self.pia.size()
+    }
+
+    fn read(&mut self, offset: u16) -> u8 {
+        self.update_sense();
+        self.pia.read(offset)
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        self.pia.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        self.pia.write(offset, value);
+        self.update_sense();
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        self.pia.tick(cycles);
+        self.keys.tick(cycles);
+        self.scan_display(cycles);
+        self.update_sense();
+    }
+
+    fn irq(&self) -> bool {
+        self.pia.irq()
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = self.pia.save();
+
+        data.extend_from_slice(&self.keys.get_held().to_be_bytes());
+        data.extend_from_slice(&self.segments);
+
+        data
+    }
+
+    fn restore(&mut self, data: &[u8]) -> Result<(), save_state::SaveStateError> {
+        let pia_size: usize = self.pia.save().len();
+
+        if data.len() < pia_size + 4 + DIGIT_COUNT {
+            return Err(save_state::SaveStateError::Truncated);
+        }
+
+        self.pia.restore(&data[..pia_size])?;
+        self.keys.set_held(u32::from_be_bytes([data[pia_size], data[pia_size + 1], data[pia_size + 2], data[pia_size + 3]]));
+        self.segments.copy_from_slice(&data[pia_size + 4..pia_size + 4 + DIGIT_COUNT]);
+
+        Ok(())
+    }
+}
+
+/// Build a Motorola MEK6800D2 evaluation kit with a 1K monitor ROM such as JBUG, the keyboard and
+/// display interface, the system PIA and an ACIA connected to `port` for the cassette or a
+/// terminal. The processor is reset, so running the machine boots the monitor.
+pub fn build(rom: &[u8], port: Box<dyn serial::SerialPort>) -> Result<machine::Machine, profile::ProfileError> {
+    let mut machine = machine::Machine::new();
+
+    profile::install_monitor_rom(&mut machine, ROM_ADDRESS, rom, ROM_SIZE)?;
+
+    machine.get_memory_manager_mut().attach_device(SYSTEM_PIA_ADDRESS, Box::new(pia::Pia::new()));
+    machine.get_memory_manager_mut().attach_device(ACIA_ADDRESS, Box::new(acia::Acia::new(port)));
+    machine.get_memory_manager_mut().attach_device(PANEL_ADDRESS, Box::new(Panel::new()));
+    machine.get_processor_mut().reset();
+    machine.set_clock_rate(Some(CLOCK_RATE));
+
+    Ok(machine)
+}
+
+/// Press the RESET key, which restarts the monitor
+pub fn press_reset(machine: &mut machine::Machine) {
+    machine.get_processor_mut().reset();
+}
+
+/// Press the ABORT key, which interrupts the program through the NMI
+pub fn press_abort(machine: &mut machine::Machine) {
+    machine.get_processor_mut().trigger_nmi();
+}
+
+/// Draw the display of a machine built by `build`
+pub fn render_display(machine: &machine::Machine) -> String {
+    machine.get_memory_manager().find_device::<Panel>().map(|panel| panel.render()).unwrap_or_default()
+}
+
+/// Type the key for a character typed on the host: 0-9 and a-f are the hexadecimal keys, m, x, r,
+/// g, p, l, n and v are M, EX, R, G, P, L, N and V, # is RESET and ! is ABORT. Returns false for
+/// other characters.
+pub fn press_host_key(machine: &mut machine::Machine, character: u8) -> bool {
+    let key: u8 = match character.to_ascii_lowercase() {
+        b'0'..=b'9' => character - b'0',
+        b'a'..=b'f' => character.to_ascii_lowercase() - b'a' + 10,
+        b'm' => KEY_MEMORY,
+        b'x' => KEY_ESCAPE,
+        b'r' => KEY_REGISTER,
+        b'g' => KEY_GO,
+        b'p' => KEY_PUNCH,
+        b'l' => KEY_LOAD,
+        b'n' => KEY_TRACE,
+        b'v' => KEY_BREAKPOINT,
+        b'#' => {
+            press_reset(machine);
+            return true;
+        },
+        b'!' => {
+            press_abort(machine);
+            return true;
+        },
+        _ => return false
+    };
+
+    match machine.get_memory_manager_mut().find_device_mut::<Panel>() {
+        Some(panel) => {
+            panel.get_keys_mut().type_keys(&[key]);
+            true
+        },
+        None => false
+    }
+}
@@ -1,34 +1,344 @@
+use std::any::Any;
+
+use crate::device;
+
 // The size of the memory manager buffer (2 ^ 16 because of the 16 bit address range)
-const BUFFER_SIZE: usize = 65536;
+pub const BUFFER_SIZE: usize = 65536;
+
+/// The processor input the interrupt output of a device is wired to
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InterruptLine {
+    Irq,
+    Nmi,
+    /// Left unconnected, e.g. on boards which only poll the device
+    None
+}
+
+/// A device attached at an address
+struct MappedDevice {
+    id: u32,
+    address: u16,
+    device: Box<dyn device::Device>,
+    interrupt_line: InterruptLine
+}
+
+impl MappedDevice {
+    fn contains(&self, address: u16) -> bool {
+        address.wrapping_sub(self.address) < self.device.size()
+    }
+}
+
+/// A range of addresses, e.g. a ROM or a mirror
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Region {
+    pub address: u16,
+    /// Up to the whole address space, hence more than 16 bits
+    pub size: u32
+}
+
+impl Region {
+    pub fn contains(&self, address: u16) -> bool {
+        (address.wrapping_sub(self.address) as u32) < self.size
+    }
+}
+
+/// A range of addresses which answers for another, as incomplete address decoding does
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Mirror {
+    pub region: Region,
+    /// The address the first byte of the region stands for
+    pub target: u16
+}
 
-#[derive(Clone, Copy)]
 pub struct MemoryManager {
     // TODO: Make the size of the buffer variable
-    buffer: [u8; BUFFER_SIZE]
+    buffer: [u8; BUFFER_SIZE],
+    devices: Vec<MappedDevice>,
+    next_device_id: u32,
+    read_only: Vec<Region>,
+    mirrors: Vec<Mirror>
+}
+
+impl Default for MemoryManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MemoryManager {
     pub fn new() -> MemoryManager {
-        let instance = MemoryManager {
-            buffer: [0; BUFFER_SIZE]
-        };
+        MemoryManager {
+            buffer: [0; BUFFER_SIZE],
+            devices: Vec::new(),
+            next_device_id: 1,
+            read_only: Vec::new(),
+            mirrors: Vec::new()
+        }
+    }
+
+    /// Load a ROM image, which the program cannot write to
+    pub fn load_rom(&mut self, address: u16, data: &[u8]) {
+        self.load(address, data);
+        self.read_only.push(Region { address, size: data.len() as u32 });
+    }
+
+    pub fn get_read_only_regions(&self) -> &[Region] {
+        &self.read_only
+    }
+
+    /// Make `size` bytes from `address` on answer for the bytes from `target` on. The mirror
+    /// shows what is at the target, and reads and writes go to the target (or to a device
+    /// attached there).
+    pub fn add_mirror(&mut self, address: u16, size: u32, target: u16) {
+        let mirror: Mirror = Mirror { region: Region { address, size }, target };
+
+        for offset in 0..size {
+            self.buffer[address.wrapping_add(offset as u16) as usize] = self.buffer[target.wrapping_add(offset as u16) as usize];
+        }
+
+        self.mirrors.push(mirror);
+    }
+
+    pub fn get_mirrors(&self) -> &[Mirror] {
+        &self.mirrors
+    }
+
+    /// Every address showing the same byte as an address: the address a mirror stands for, then
+    /// every mirror of it
+    pub fn get_aliases(&self, address: u16) -> Vec<u16> {
+        let resolved_address: u16 = self.resolve(address);
+        let mut aliases: Vec<u16> = vec![resolved_address];
+        let mut offset: u16;
+
+        for mirror in &self.mirrors {
+            offset = resolved_address.wrapping_sub(mirror.target);
+
+            if (offset as u32) < mirror.region.size {
+                aliases.push(mirror.region.address.wrapping_add(offset));
+            }
+        }
+
+        aliases
+    }
+
+    /// The address an access really goes to, following mirrors
+    fn resolve(&self, address: u16) -> u16 {
+        match self.mirrors.iter().find(|mirror| mirror.region.contains(address)) {
+            Some(mirror) => mirror.target.wrapping_add(address.wrapping_sub(mirror.region.address)),
+            None => address
+        }
+    }
+
+    /// Set a byte of the buffer, and its copies in the mirrors of it
+    fn store(&mut self, address: u16, value: u8) {
+        let mut offset: u16;
+
+        self.buffer[address as usize] = value;
+
+        for mirror in &self.mirrors {
+            offset = address.wrapping_sub(mirror.target);
+
+            if (offset as u32) < mirror.region.size {
+                self.buffer[mirror.region.address.wrapping_add(offset) as usize] = value;
+            }
+        }
+    }
+
+    /// Copy the registers of a device into the buffer, so `read` shows them without side effects
+    fn refresh(&mut self, index: usize) {
+        let address: u16 = self.devices[index].address;
+        let mut value: u8;
+
+        for offset in 0..self.devices[index].device.size() {
+            value = self.devices[index].device.peek(offset);
+            self.store(address.wrapping_add(offset), value);
+        }
+    }
+
+    /// Map the registers of a device from an address on, with its interrupt output wired to IRQ.
+    /// Returns the id to get it back with.
+    pub fn attach_device(&mut self, address: u16, device: Box<dyn device::Device>) -> u32 {
+        self.devices.push(MappedDevice { id: self.next_device_id, address, device, interrupt_line: InterruptLine::Irq });
+        self.next_device_id += 1;
+        self.refresh(self.devices.len() - 1);
+
+        self.next_device_id - 1
+    }
+
+    /// Wire the interrupt output of a device to another processor input
+    pub fn set_interrupt_line(&mut self, id: u32, interrupt_line: InterruptLine) {
+        if let Some(mapped_device) = self.devices.iter_mut().find(|mapped_device| mapped_device.id == id) {
+            mapped_device.interrupt_line = interrupt_line;
+        }
+    }
+
+    pub fn detach_device(&mut self, id: u32) -> Option<Box<dyn device::Device>> {
+        let index: usize = self.devices.iter().position(|mapped_device| mapped_device.id == id)?;
+
+        Some(self.devices.remove(index).device)
+    }
+
+    /// Borrow a device as its concrete type, or None if the id or the type does not match
+    pub fn get_device<T: device::Device>(&self, id: u32) -> Option<&T> {
+        let mapped_device: &MappedDevice = self.devices.iter().find(|mapped_device| mapped_device.id == id)?;
+        let device: &dyn Any = mapped_device.device.as_ref();
+
+        device.downcast_ref()
+    }
+
+    /// Mutably borrow a device as its concrete type. The registers shown by `read` are brought up
+    /// to date by the next access to the device, or by `refresh_devices`.
+    pub fn get_device_mut<T: device::Device>(&mut self, id: u32) -> Option<&mut T> {
+        let mapped_device: &mut MappedDevice = self.devices.iter_mut().find(|mapped_device| mapped_device.id == id)?;
+        let device: &mut dyn Any = mapped_device.device.as_mut();
+
+        device.downcast_mut()
+    }
+
+    /// Borrow the first attached device of a type
+    pub fn find_device<T: device::Device>(&self) -> Option<&T> {
+        self.devices.iter().find_map(|mapped_device| {
+            let device: &dyn Any = mapped_device.device.as_ref();
+
+            device.downcast_ref()
+        })
+    }
 
-        instance
+    pub fn find_device_mut<T: device::Device>(&mut self) -> Option<&mut T> {
+        self.devices.iter_mut().find_map(|mapped_device| {
+            let device: &mut dyn Any = mapped_device.device.as_mut();
+
+            device.downcast_mut()
+        })
+    }
+
+    /// The attached devices with the address each is mapped at, in the order they were attached
+    pub fn get_devices(&self) -> Vec<(u16, &dyn device::Device)> {
+        self.devices.iter().map(|mapped_device| (mapped_device.address, mapped_device.device.as_ref())).collect()
+    }
+
+    pub fn get_devices_mut(&mut self) -> Vec<(u16, &mut dyn device::Device)> {
+        self.devices.iter_mut().map(|mapped_device| (mapped_device.address, mapped_device.device.as_mut() as &mut dyn device::Device)).collect()
+    }
+
+    fn find_mapped_device(&self, address: u16) -> Option<usize> {
+        self.devices.iter().position(|mapped_device| mapped_device.contains(address))
     }
-    
+
+    /// Read memory without side effects. Device registers read as their `Device::peek` value.
+    /// The slice stops at $FFFF, so it is shorter than `size` for reads running past the top of
+    /// memory.
     pub fn read(&self, address: u16, size: u16) -> &[u8] {
         let start = address as usize;
-        let end = (address + size) as usize;
+        let end = (start + size as usize).min(BUFFER_SIZE);
 
         &self.buffer[start..end]
     }
 
+    /// Read a byte as the processor does, letting a device at the address react to the read
+    pub fn read_byte(&mut self, address: u16) -> u8 {
+        let resolved_address: u16 = self.resolve(address);
+        let mapped_device: &mut MappedDevice;
+        let value: u8;
+
+        match self.find_mapped_device(resolved_address) {
+            Some(index) => {
+                mapped_device = &mut self.devices[index];
+                value = mapped_device.device.read(resolved_address.wrapping_sub(mapped_device.address));
+                self.refresh(index);
+
+                value
+            },
+            None => self.buffer[address as usize]
+        }
+    }
+
+    /// Get the contents of the whole address space
+    pub fn dump(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Whether only plain memory is mapped, so accesses need no lookups
+    fn is_flat(&self) -> bool {
+        self.devices.is_empty() && self.read_only.is_empty() && self.mirrors.is_empty()
+    }
+
+    /// Write memory as the processor does, passing the bytes at device addresses to the devices.
+    /// Writes to ROM are ignored.
     pub fn write(&mut self, address: u16, data: &[u8]) {
-        let mut current_address = address as usize;
+        let start = address as usize;
+        let mut resolved_address: u16;
+        let mut mapped_device: &mut MappedDevice;
+
+        // Writes running past $FFFF wrap around through the slow path
+        if self.is_flat() && start + data.len() <= BUFFER_SIZE {
+            self.buffer[start..start + data.len()].copy_from_slice(data);
+            return;
+        }
+
+        for (offset, byte) in data.iter().enumerate() {
+            resolved_address = self.resolve((start + offset) as u16);
 
-        for byte in data {
-            self.buffer[current_address] = *byte;
-            current_address += 1;
+            match self.find_mapped_device(resolved_address) {
+                Some(index) => {
+                    mapped_device = &mut self.devices[index];
+                    mapped_device.device.write(resolved_address.wrapping_sub(mapped_device.address), *byte);
+                    self.refresh(index);
+                },
+                None if self.read_only.iter().any(|region| region.contains(resolved_address)) => {},
+                None => self.store(resolved_address, *byte)
+            }
         }
     }
+
+    /// Write memory behind the back of the devices, e.g. to restore a snapshot or load a program
+    /// into ROM. Bytes at device addresses are left alone.
+    pub fn load(&mut self, address: u16, data: &[u8]) {
+        let start = address as usize;
+        let mut resolved_address: u16;
+
+        // Writes running past $FFFF wrap around through the slow path
+        if self.is_flat() && start + data.len() <= BUFFER_SIZE {
+            self.buffer[start..start + data.len()].copy_from_slice(data);
+            return;
+        }
+
+        for (offset, byte) in data.iter().enumerate() {
+            resolved_address = self.resolve((start + offset) as u16);
+
+            if self.find_mapped_device(resolved_address).is_none() {
+                self.store(resolved_address, *byte);
+            }
+        }
+    }
+
+    /// Advance every device by a number of cycles
+    pub fn tick(&mut self, cycles: u64) {
+        for index in 0..self.devices.len() {
+            self.devices[index].device.tick(cycles);
+            self.refresh(index);
+        }
+    }
+
+    /// Copy the registers of every device into the memory shown by `read`
+    pub fn refresh_devices(&mut self) {
+        for index in 0..self.devices.len() {
+            self.refresh(index);
+        }
+    }
+
+    /// Whether any device wired to IRQ asserts its interrupt output
+    pub fn get_irq(&self) -> bool {
+        self.devices.iter().any(|mapped_device| mapped_device.interrupt_line == InterruptLine::Irq && mapped_device.device.irq())
+    }
+
+    /// Whether any device wired to NMI asserts its interrupt output
+    pub fn get_nmi(&self) -> bool {
+        self.devices.iter().any(|mapped_device| mapped_device.interrupt_line == InterruptLine::Nmi && mapped_device.device.irq())
+    }
+
+    /// The status the program exited with through a device, if it did since the last call
+    pub fn take_exit_code(&mut self) -> Option<u8> {
+        self.devices.iter_mut().find_map(|mapped_device| mapped_device.device.take_exit_code())
+    }
 }
@@ -0,0 +1,23 @@
+/// Parse a number typed by the user, the same way in every front end
+///
+/// `$` or `0x` introduces a hexadecimal number and `%` or `0b` a binary one. Anything else is
+/// decimal, so a bare `100` is always one hundred and never a symbol-looking `ADD` read as hex.
+pub fn parse_number(text: &str) -> Option<u64> {
+    if let Some(digits) = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")).or_else(|| text.strip_prefix("0X")) {
+        parse_digits(digits, 16)
+    } else if let Some(digits) = text.strip_prefix('%').or_else(|| text.strip_prefix("0b")).or_else(|| text.strip_prefix("0B")) {
+        parse_digits(digits, 2)
+    } else {
+        parse_digits(text, 10)
+    }
+}
+
+/// Parse digits in a fixed radix, without a prefix or a sign, e.g. the hexadecimal fields of a
+/// protocol
+pub fn parse_digits(text: &str, radix: u32) -> Option<u64> {
+    if text.is_empty() || !text.chars().all(|character| character.is_digit(radix)) {
+        return None;
+    }
+
+    u64::from_str_radix(text, radix).ok()
+}
@@ -0,0 +1,108 @@
+use std::any::Any;
+
+use crate::call_stack;
+use crate::disassembler;
+use crate::processor;
+
+/// The kinds of interrupt reported to observers
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InterruptKind {
+    Irq,
+    Nmi,
+    /// The SWI instruction
+    Software
+}
+
+/// Receives callbacks from a processor as it emulates
+///
+/// Every method does nothing by default, so an observer only implements the events it cares
+/// about. Observers are attached with `Processor::add_observer` and can be borrowed back (as
+/// their concrete type) with `Processor::get_observer` to read what they collected. Observers
+/// must be `Send` so machines can still be moved to other threads.
+pub trait Observer: Any + Send {
+    /// Called once the instruction at `state.program_counter` was fetched, before it runs (and
+    /// after any interrupt serviced in front of it was entered), with the frames of the call
+    /// stack it runs in, outermost first
+    fn before_instruction(&mut self, _instruction_info: &disassembler::InstructionInfo, _state: &processor::ProcessorState,
+            _call_stack: &[call_stack::Frame]) {}
+
+    /// Called after an instruction ran, with the state before and after it and the cycles it took
+    fn after_instruction(&mut self, _instruction_info: &disassembler::InstructionInfo, _before: &processor::ProcessorState,
+            _after: &processor::ProcessorState, _cycles: u64) {}
+
+    /// Called for every byte an instruction reads as data (operands and pulled stack bytes)
+    fn memory_read(&mut self, _address: u16, _value: u8) {}
+
+    /// Called for every byte an instruction or an interrupt writes, with the value it replaces
+    fn memory_write(&mut self, _address: u16, _value: u8, _previous_value: u8) {}
+
+    /// Called once an interrupt handler was entered, with the address of the interrupted
+    /// instruction (or of the SWI) and the cycles spent stacking the registers, which are not
+    /// part of any instruction. They are 0 for SWI, whose cycles are the instruction's, and when
+    /// WAI stacked the registers already.
+    fn interrupt(&mut self, _kind: InterruptKind, _from: u16, _state: &processor::ProcessorState, _cycles: u64) {}
+}
+
+/// The observers attached to a processor, identified by the id they were given
+#[derive(Default)]
+pub struct ObserverList {
+    observers: Vec<(u32, Box<dyn Observer>)>,
+    next_id: u32
+}
+
+impl ObserverList {
+    pub fn new() -> ObserverList {
+        ObserverList { observers: Vec::new(), next_id: 1 }
+    }
+
+    pub fn add(&mut self, observer: Box<dyn Observer>) -> u32 {
+        let id: u32 = self.next_id;
+
+        self.next_id += 1;
+        self.observers.push((id, observer));
+
+        id
+    }
+
+    pub fn remove(&mut self, id: u32) -> Option<Box<dyn Observer>> {
+        let index: usize = self.observers.iter().position(|(observer_id, _)| *observer_id == id)?;
+
+        Some(self.observers.remove(index).1)
+    }
+
+    /// Borrow an observer as its concrete type, or None if the id or the type does not match
+    pub fn get<T: Observer>(&self, id: u32) -> Option<&T> {
+        let (_, observer) = self.observers.iter().find(|(observer_id, _)| *observer_id == id)?;
+        let observer: &dyn Any = observer.as_ref();
+
+        observer.downcast_ref()
+    }
+
+    /// Borrow the first attached observer of a type
+    pub fn find<T: Observer>(&self) -> Option<&T> {
+        self.observers.iter().find_map(|(_, observer)| {
+            let observer: &dyn Any = observer.as_ref();
+
+            observer.downcast_ref()
+        })
+    }
+
+    pub fn get_mut<T: Observer>(&mut self, id: u32) -> Option<&mut T> {
+        let (_, observer) = self.observers.iter_mut().find(|(observer_id, _)| *observer_id == id)?;
+        let observer: &mut dyn Any = observer.as_mut();
+
+        observer.downcast_mut()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.observers.len()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn Observer>> {
+        self.observers.iter_mut().map(|(_, observer)| observer)
+    }
+}
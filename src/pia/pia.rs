@@ -0,0 +1,343 @@
+use crate::device;
+use crate::save_state;
+
+// Control register bits
+const CONTROL_C1_INTERRUPT_ENABLED: u8 = 0x01;
+const CONTROL_C1_RISING_EDGE: u8 = 0x02;
+const CONTROL_OUTPUT_REGISTER_SELECTED: u8 = 0x04;
+/// With C2 as an input, enables its interrupt. With C2 as an output, the level it is set to, or
+/// in handshake mode, whether it only pulses.
+const CONTROL_C2_BIT3: u8 = 0x08;
+/// With C2 as an input, selects the rising edge. With C2 as an output, selects set/reset mode
+/// over handshake mode.
+const CONTROL_C2_BIT4: u8 = 0x10;
+const CONTROL_C2_OUTPUT: u8 = 0x20;
+const CONTROL_WRITABLE_MASK: u8 = 0x3F;
+pub const CONTROL_IRQ2_FLAG: u8 = 0x40;
+pub const CONTROL_IRQ1_FLAG: u8 = 0x80;
+
+// The size of the saved state of one port
+const PORT_STATE_SIZE: usize = 10;
+
+/// The two sides of a PIA
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Port {
+    A,
+    B
+}
+
+/// The registers and control lines of one side of the PIA
+#[derive(Clone, Copy)]
+struct PortState {
+    output_register: u8,
+    data_direction: u8,
+    control: u8,
+    /// The levels external hardware drives on the pins, undriven pins are pulled high
+    input: u8,
+    c1: bool,
+    /// The level driven on C2 while it is an input
+    c2_input: bool,
+    /// The level of C2 while it is an output
+    c2_output: bool,
+    irq1: bool,
+    irq2: bool,
+    /// C2 was pulsed low, and goes back high on the next cycle
+    pulsing: bool
+}
+
+impl PortState {
+    fn new() -> PortState {
+        PortState {
+            output_register: 0,
+            data_direction: 0,
+            control: 0,
+            input: 0xFF,
+            c1: false,
+            c2_input: false,
+            c2_output: true,
+            irq1: false,
+            irq2: false,
+            pulsing: false
+        }
+    }
+
+    fn is_c2_output(&self) -> bool {
+        self.control & CONTROL_C2_OUTPUT != 0
+    }
+
+    /// C2 is an output in handshake (or pulse) mode, driven by data register accesses
+    fn is_c2_handshake(&self) -> bool {
+        self.is_c2_output() && self.control & CONTROL_C2_BIT4 == 0
+    }
+
+    fn get_pins(&self) -> u8 {
+        (self.output_register & self.data_direction) | (self.input & !self.data_direction)
+    }
+
+    fn get_control(&self) -> u8 {
+        let mut control: u8 = self.control;
+
+        if self.irq1 {
+            control |= CONTROL_IRQ1_FLAG;
+        }
+
+        if self.irq2 && !self.is_c2_output() {
+            control |= CONTROL_IRQ2_FLAG;
+        }
+
+        control
+    }
+
+    fn irq(&self) -> bool {
+        (self.irq1 && self.control & CONTROL_C1_INTERRUPT_ENABLED != 0) ||
+            (self.irq2 && self.control & CONTROL_C2_BIT3 != 0 && !self.is_c2_output())
+    }
+
+    fn peek(&self, register: u16) -> u8 {
+        match register {
+            0 if self.control & CONTROL_OUTPUT_REGISTER_SELECTED == 0 => self.data_direction,
+            0 => self.get_pins(),
+            _ => self.get_control()
+        }
+    }
+
+    /// Start a handshake on C2, after the data register was read (port A) or written (port B)
+    fn strobe_c2(&mut self) {
+        if self.is_c2_handshake() {
+            self.c2_output = false;
+            self.pulsing = self.control & CONTROL_C2_BIT3 != 0;
+        }
+    }
+
+    fn write_control(&mut self, value: u8) {
+        let was_handshake: bool = self.is_c2_handshake();
+
+        self.control = value & CONTROL_WRITABLE_MASK;
+
+        if self.is_c2_handshake() {
+            // A handshake starts out idle, one in progress carries on
+            if !was_handshake {
+                self.c2_output = true;
+                self.pulsing = false;
+            }
+        } else if self.is_c2_output() {
+            self.c2_output = self.control & CONTROL_C2_BIT3 != 0;
+        }
+    }
+
+    fn set_c1(&mut self, level: bool) {
+        let active_edge: bool = (self.control & CONTROL_C1_RISING_EDGE != 0) == level;
+
+        if level == self.c1 {
+            return;
+        }
+
+        self.c1 = level;
+
+        if active_edge {
+            self.irq1 = true;
+
+            // The peripheral acknowledged the handshake
+            if self.is_c2_handshake() && self.control & CONTROL_C2_BIT3 == 0 {
+                self.c2_output = true;
+            }
+        }
+    }
+
+    fn set_c2(&mut self, level: bool) {
+        if level == self.c2_input {
+            return;
+        }
+
+        self.c2_input = level;
+
+        if !self.is_c2_output() && (self.control & CONTROL_C2_BIT4 != 0) == level {
+            self.irq2 = true;
+        }
+    }
+
+    fn save(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(&[self.output_register, self.data_direction, self.control, self.input, self.c1 as u8, self.c2_input as u8,
+                self.c2_output as u8, self.irq1 as u8, self.irq2 as u8, self.pulsing as u8]);
+    }
+
+    fn restore(&mut self, data: &[u8]) {
+        self.output_register = data[0];
+        self.data_direction = data[1];
+        self.control = data[2];
+        self.input = data[3];
+        self.c1 = data[4] != 0;
+        self.c2_input = data[5] != 0;
+        self.c2_output = data[6] != 0;
+        self.irq1 = data[7] != 0;
+        self.irq2 = data[8] != 0;
+        self.pulsing = data[9] != 0;
+    }
+}
+
+/// An MC6821 peripheral interface adapter
+///
+/// The PIA takes four addresses: the data (or data direction) register and the control register
+/// of port A, then the same for port B. Bit 2 of a control register selects whether the data
+/// register or the data direction register is accessed. Outside code drives the input pins and
+/// the control lines with `set_input`, `set_c1` and `set_c2`, and sees the outputs with
+/// `get_pins` and `get_c2`. Both IRQ outputs are wired to the processor's IRQ input.
+pub struct Pia {
+    port_a: PortState,
+    port_b: PortState
+}
+
+impl Default for Pia {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pia {
+    pub fn new() -> Pia {
+        Pia { port_a: PortState::new(), port_b: PortState::new() }
+    }
+
+    fn get_port(&self, port: Port) -> &PortState {
+        match port {
+            Port::A => &self.port_a,
+            Port::B => &self.port_b
+        }
+    }
+
+    fn get_port_mut(&mut self, port: Port) -> &mut PortState {
+        match port {
+            Port::A => &mut self.port_a,
+            Port::B => &mut self.port_b
+        }
+    }
+
+    /// Drive the input pins of a port. Bits of pins configured as outputs are ignored.
+    pub fn set_input(&mut self, port: Port, value: u8) {
+        self.get_port_mut(port).input = value;
+    }
+
+    /// The levels on the pins of a port: the output register for outputs, the driven input for
+    /// inputs
+    pub fn get_pins(&self, port: Port) -> u8 {
+        self.get_port(port).get_pins()
+    }
+
+    pub fn get_output_register(&self, port: Port) -> u8 {
+        self.get_port(port).output_register
+    }
+
+    /// Which pins are outputs (set bits)
+    pub fn get_data_direction(&self, port: Port) -> u8 {
+        self.get_port(port).data_direction
+    }
+
+    /// The control register, with the interrupt flags in bits 6 and 7
+    pub fn get_control(&self, port: Port) -> u8 {
+        self.get_port(port).get_control()
+    }
+
+    /// Drive the C1 (interrupt) input of a port
+    pub fn set_c1(&mut self, port: Port, level: bool) {
+        self.get_port_mut(port).set_c1(level);
+    }
+
+    /// Drive the C2 line of a port, which only has an effect while it is an input
+    pub fn set_c2(&mut self, port: Port, level: bool) {
+        self.get_port_mut(port).set_c2(level);
+    }
+
+    /// The level of the C2 line of a port
+    pub fn get_c2(&self, port: Port) -> bool {
+        let port_state: &PortState = self.get_port(port);
+
+        if port_state.is_c2_output() { port_state.c2_output } else { port_state.c2_input }
+    }
+
+    /// The level of the IRQA or IRQB output (true when asserted)
+    pub fn get_irq(&self, port: Port) -> bool {
+        self.get_port(port).irq()
+    }
+}
+
+impl device::Device for Pia {
+    fn name(&self) -> &str {
+        "pia"
+    }
+
+    fn size(&self) -> u16 {
+        4
+    }
+
+    fn read(&mut self, offset: u16) -> u8 {
+        let port: Port = if offset & 2 == 0 { Port::A } else { Port::B };
+        let port_state: &mut PortState = self.get_port_mut(port);
+        let value: u8 = port_state.peek(offset & 1);
+
+        // Reading the data register acknowledges the interrupts of the port
+        if offset & 1 == 0 && port_state.control & CONTROL_OUTPUT_REGISTER_SELECTED != 0 {
+            port_state.irq1 = false;
+            port_state.irq2 = false;
+
+            if port == Port::A {
+                port_state.strobe_c2();
+            }
+        }
+
+        value
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        self.get_port(if offset & 2 == 0 { Port::A } else { Port::B }).peek(offset & 1)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        let port: Port = if offset & 2 == 0 { Port::A } else { Port::B };
+        let port_state: &mut PortState = self.get_port_mut(port);
+
+        if offset & 1 == 1 {
+            port_state.write_control(value);
+        } else if port_state.control & CONTROL_OUTPUT_REGISTER_SELECTED == 0 {
+            port_state.data_direction = value;
+        } else {
+            port_state.output_register = value;
+
+            if port == Port::B {
+                port_state.strobe_c2();
+            }
+        }
+    }
+
+    fn tick(&mut self, _cycles: u64) {
+        for port_state in [&mut self.port_a, &mut self.port_b] {
+            if port_state.pulsing {
+                port_state.pulsing = false;
+                port_state.c2_output = true;
+            }
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.port_a.irq() || self.port_b.irq()
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::with_capacity(PORT_STATE_SIZE * 2);
+
+        self.port_a.save(&mut data);
+        self.port_b.save(&mut data);
+
+        data
+    }
+
+    fn restore(&mut self, data: &[u8]) -> Result<(), save_state::SaveStateError> {
+        if data.len() < PORT_STATE_SIZE * 2 {
+            return Err(save_state::SaveStateError::Truncated);
+        }
+
+        self.port_a.restore(&data[..PORT_STATE_SIZE]);
+        self.port_b.restore(&data[PORT_STATE_SIZE..]);
+
+        Ok(())
+    }
+}
@@ -1,8 +1,26 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::breakpoints;
+use crate::call_stack;
 use crate::disassembler;
+use crate::instruction_cache;
 use crate::memory_manager;
+use crate::observer;
+use crate::rewind_buffer;
+use crate::trace;
 
 const MAX_INSTRUCTION_LENGTH: u16 = 3;
 
+// Addresses of the interrupt vectors
+pub const IRQ_VECTOR: u16 = 0xFFF8;
+pub const SOFTWARE_INTERRUPT_VECTOR: u16 = 0xFFFA;
+pub const NMI_VECTOR: u16 = 0xFFFC;
+pub const RESET_VECTOR: u16 = 0xFFFE;
+
+// Cycles it takes to stack the registers and fetch the vector of a hardware interrupt
+pub const INTERRUPT_CYCLES: u64 = 12;
+
 #[derive(Clone, Copy)]
 pub struct ProcessorState {
     pub accumulator_a: u8,
@@ -13,9 +31,55 @@ pub struct ProcessorState {
     pub condition_code_register: u8
 }
 
+/// The memory the processor runs against, either borrowed from the caller or owned by the
+/// processor itself
+enum MemoryHandle<'a> {
+    Borrowed(&'a mut memory_manager::MemoryManager),
+    Owned(Box<memory_manager::MemoryManager>)
+}
+
+impl<'a> MemoryHandle<'a> {
+    fn get(&self) -> &memory_manager::MemoryManager {
+        match self {
+            MemoryHandle::Borrowed(memory_manager) => memory_manager,
+            MemoryHandle::Owned(memory_manager) => memory_manager
+        }
+    }
+
+    fn get_mut(&mut self) -> &mut memory_manager::MemoryManager {
+        match self {
+            MemoryHandle::Borrowed(memory_manager) => memory_manager,
+            MemoryHandle::Owned(memory_manager) => memory_manager
+        }
+    }
+}
+
+/// The state of the interrupt inputs of the processor
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct InterruptState {
+    /// The level of the IRQ input, serviced as long as it is asserted and not masked
+    pub irq_line: bool,
+    /// An NMI edge was seen and has not been serviced yet
+    pub nmi_pending: bool,
+    /// The processor executed WAI and already stacked its registers
+    pub waiting_for_interrupt: bool,
+    /// Whether a device wired to NMI asserts it, to find the edges of the line
+    pub device_nmi_line: bool
+}
+
 pub struct Processor<'a> {
     state: ProcessorState,
-    memory_manager: Option<&'a mut memory_manager::MemoryManager>
+    interrupts: InterruptState,
+    memory_manager: Option<MemoryHandle<'a>>,
+    instruction_cache: Option<instruction_cache::InstructionCache>,
+    rewind_buffer: Option<rewind_buffer::RewindBuffer>,
+    tracer: Option<trace::Tracer>,
+    breakpoints: breakpoints::BreakpointManager,
+    /// The memory accesses of the last emulated instruction, only recorded while a watchpoint is set
+    memory_accesses: Vec<breakpoints::MemoryAccess>,
+    call_stack: call_stack::CallStack,
+    observers: observer::ObserverList,
+    cycles: u64
 }
 
 #[derive(Clone, Copy)]
@@ -40,6 +104,58 @@ pub enum EmulationError {
     DisassemblyError(disassembler::DisassemblyError)
 }
 
+/// Conditions which stop `Processor::run`, checked after every instruction
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StopCondition {
+    ProgramCounterEquals(u16),
+    InstructionBudget(u64),
+    CycleBudget(u64),
+    /// Stop on an instruction which jumps to itself (e.g. `BRA *`). A WAI waiting for an interrupt
+    /// is not one, since a device can still wake it.
+    InfiniteLoop,
+    /// Stop when a frame returns: the program counter is at `address` and the stack pointer is
+    /// above `stack_pointer`, so recursive calls returning to the same address are not mistaken
+    /// for it
+    ReturnTo { address: u16, stack_pointer: u16 }
+}
+
+/// The reason `Processor::run` returned
+#[derive(PartialEq, Eq, Debug)]
+pub enum StopReason {
+    ProgramCounterReached(u16),
+    InstructionBudgetExhausted,
+    CycleBudgetExhausted,
+    /// The address of the instruction jumping to itself
+    InfiniteLoop(u16),
+    /// The address a `StopCondition::ReturnTo` frame returned to
+    Returned(u16),
+    /// `Processor::step_over` ran an instruction which is not a call
+    Stepped,
+    /// The address of the opcode byte which could not be decoded (always stops the run)
+    IllegalOpcode(u16),
+    /// An execution breakpoint or a watchpoint fired
+    Breakpoint(breakpoints::BreakpointHit),
+    /// The program exited with a status through a device such as `semihosting::Semihosting`
+    /// (always stops the run)
+    Exited(u8),
+    /// The host asked the run to stop, e.g. because Ctrl-C was pressed
+    Interrupted,
+    Error(EmulationError)
+}
+
+/// Statistics about a single call to `Processor::run`
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct RunStatistics {
+    pub instructions: u64,
+    pub cycles: u64
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct RunResult {
+    pub stop_reason: StopReason,
+    pub statistics: RunStatistics
+}
+
 // Get a specific bit from a variable
 #[macro_export]
 macro_rules! get_bit {
@@ -116,20 +232,32 @@ impl ProcessorState {
     }
 
     pub fn print(&self) {
-        println!("Processor State:");
-        println!("[-] Program Counter: {:#x}", self.program_counter);
-        println!("[-] Accumulator A: {:#x}", self.accumulator_a);
-        println!("[-] Accumulator B: {:#x}", self.accumulator_b);
-        println!("[-] Index Register: {:#x}", self.index_register);
-        println!("[-] Stack Pointer: {:#x}", self.stack_pointer);
-        println!("[-] Condition Codes: C {}, V {}, Z {}, N {}, I {}, H {}\n",
+        println!("{}", self);
+    }
+}
+
+impl fmt::Display for ProcessorState {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(formatter, "Processor State:")?;
+        writeln!(formatter, "[-] Program Counter: {:#x}", self.program_counter)?;
+        writeln!(formatter, "[-] Accumulator A: {:#x}", self.accumulator_a)?;
+        writeln!(formatter, "[-] Accumulator B: {:#x}", self.accumulator_b)?;
+        writeln!(formatter, "[-] Index Register: {:#x}", self.index_register)?;
+        writeln!(formatter, "[-] Stack Pointer: {:#x}", self.stack_pointer)?;
+        writeln!(formatter, "[-] Condition Codes: C {}, V {}, Z {}, N {}, I {}, H {}",
                 self.get_condition_code_flag(ConditionCodeFlag::Carry) as u8,
                 self.get_condition_code_flag(ConditionCodeFlag::Overflow) as u8,
                 self.get_condition_code_flag(ConditionCodeFlag::Zero) as u8,
                 self.get_condition_code_flag(ConditionCodeFlag::Negative) as u8,
                 self.get_condition_code_flag(ConditionCodeFlag::InterruptMask) as u8,
                 self.get_condition_code_flag(ConditionCodeFlag::HalfCarry) as u8
-                );
+                )
+    }
+}
+
+impl<'a> Default for Processor<'a> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -137,12 +265,192 @@ impl<'a> Processor<'a> {
     pub fn new() -> Processor<'a> {
         Processor {
             state: ProcessorState::new_empty(),
-            memory_manager: None
+            interrupts: InterruptState::default(),
+            memory_manager: None,
+            instruction_cache: Some(instruction_cache::InstructionCache::new()),
+            rewind_buffer: None,
+            tracer: None,
+            breakpoints: breakpoints::BreakpointManager::new(),
+            memory_accesses: Vec::new(),
+            call_stack: call_stack::CallStack::new(),
+            observers: observer::ObserverList::new(),
+            cycles: 0
         }
     }
 
     pub fn set_memory_manager(&mut self, memory_manager: &'a mut memory_manager::MemoryManager) {
+        self.replace_memory_manager(MemoryHandle::Borrowed(memory_manager));
+    }
+
+    /// Move a memory manager into the processor, so the processor does not borrow anything
+    pub fn set_owned_memory_manager(&mut self, memory_manager: Box<memory_manager::MemoryManager>) {
+        self.replace_memory_manager(MemoryHandle::Owned(memory_manager));
+    }
+
+    fn replace_memory_manager(&mut self, memory_manager: MemoryHandle<'a>) {
         self.memory_manager = Some(memory_manager);
+
+        // Instructions decoded from the previous memory are meaningless now
+        if let Some(cache) = self.instruction_cache.as_mut() {
+            cache.clear();
+        }
+    }
+
+    pub fn get_memory_manager(&self) -> Option<&memory_manager::MemoryManager> {
+        self.memory_manager.as_ref().map(|memory_manager| memory_manager.get())
+    }
+
+    /// Get mutable access to the memory. Anything may be written through the returned reference,
+    /// so the instruction cache is flushed.
+    pub fn get_memory_manager_mut(&mut self) -> Option<&mut memory_manager::MemoryManager> {
+        if let Some(cache) = self.instruction_cache.as_mut() {
+            cache.clear();
+        }
+
+        self.memory_manager.as_mut().map(|memory_manager| memory_manager.get_mut())
+    }
+
+    /// Write to memory on behalf of the user, invalidating only the affected cached instructions
+    pub fn write_memory(&mut self, address: u16, data: &[u8]) -> Result<(), EmulationError> {
+        if self.memory_manager.is_none() {
+            return Err(EmulationError::NoMemoryManager);
+        }
+
+        self.store_to_memory(address, data);
+
+        Ok(())
+    }
+
+    /// Enable or disable caching of decoded instructions (enabled by default)
+    pub fn set_instruction_cache_enabled(&mut self, enable: bool) {
+        match (enable, self.instruction_cache.is_some()) {
+            (true, false) => self.instruction_cache = Some(instruction_cache::InstructionCache::new()),
+            (false, true) => self.instruction_cache = None,
+            _ => {}
+        }
+    }
+
+    pub fn get_instruction_cache(&self) -> Option<&instruction_cache::InstructionCache> {
+        self.instruction_cache.as_ref()
+    }
+
+    /// Start recording an undo log of the last `capacity` instructions, so they can be stepped back
+    pub fn enable_rewind(&mut self, capacity: usize) {
+        self.rewind_buffer = Some(rewind_buffer::RewindBuffer::new(capacity));
+    }
+
+    pub fn disable_rewind(&mut self) {
+        self.rewind_buffer = None;
+    }
+
+    pub fn get_rewind_buffer(&self) -> Option<&rewind_buffer::RewindBuffer> {
+        self.rewind_buffer.as_ref()
+    }
+
+    pub fn get_rewind_buffer_mut(&mut self) -> Option<&mut rewind_buffer::RewindBuffer> {
+        self.rewind_buffer.as_mut()
+    }
+
+    /// Log every emulated instruction to a tracer
+    pub fn set_tracer(&mut self, tracer: trace::Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    /// Stop tracing, returning the tracer so it can be finished
+    pub fn take_tracer(&mut self) -> Option<trace::Tracer> {
+        self.tracer.take()
+    }
+
+    pub fn get_breakpoints(&self) -> &breakpoints::BreakpointManager {
+        &self.breakpoints
+    }
+
+    /// Get the breakpoints and watchpoints checked by `run`
+    pub fn get_breakpoints_mut(&mut self) -> &mut breakpoints::BreakpointManager {
+        &mut self.breakpoints
+    }
+
+    /// Check the execution breakpoints against the current program counter, like `run` does
+    /// before every instruction but the first
+    pub fn check_breakpoints(&self) -> Option<breakpoints::BreakpointHit> {
+        self.breakpoints.check_execution(&self.state, self.memory_manager.as_ref()?.get())
+    }
+
+    /// The bytes read and written by the last emulated instruction. Accesses are only recorded
+    /// while an enabled watchpoint exists.
+    pub fn get_memory_accesses(&self) -> &[breakpoints::MemoryAccess] {
+        &self.memory_accesses
+    }
+
+    /// The calls and interrupts the program is currently in, followed on every JSR, BSR, SWI,
+    /// interrupt, RTS and RTI
+    pub fn get_call_stack(&self) -> &call_stack::CallStack {
+        &self.call_stack
+    }
+
+    pub fn get_call_stack_mut(&mut self) -> &mut call_stack::CallStack {
+        &mut self.call_stack
+    }
+
+    /// Attach an observer called back on every instruction, memory access and interrupt.
+    /// Returns the id to get it back with.
+    pub fn add_observer(&mut self, observer: Box<dyn observer::Observer>) -> u32 {
+        self.observers.add(observer)
+    }
+
+    pub fn remove_observer(&mut self, id: u32) -> Option<Box<dyn observer::Observer>> {
+        self.observers.remove(id)
+    }
+
+    /// Borrow an attached observer as its concrete type
+    pub fn get_observer<T: observer::Observer>(&self, id: u32) -> Option<&T> {
+        self.observers.get(id)
+    }
+
+    pub fn get_observer_mut<T: observer::Observer>(&mut self, id: u32) -> Option<&mut T> {
+        self.observers.get_mut(id)
+    }
+
+    /// Borrow the first attached observer of a type
+    pub fn find_observer<T: observer::Observer>(&self) -> Option<&T> {
+        self.observers.find()
+    }
+
+    /// Undo the last recorded instruction. Returns false if there is nothing left to undo.
+    /// Attached devices keep their state.
+    pub fn step_back(&mut self) -> bool {
+        let record: rewind_buffer::UndoRecord = match self.rewind_buffer.as_mut().and_then(|buffer| buffer.pop()) {
+            Some(record) => record,
+            None => return false
+        };
+
+        // Restore the bytes in reverse order, in case the instruction wrote the same byte twice
+        for (address, previous_value) in record.memory.iter().rev() {
+            self.memory_manager.as_mut().unwrap().get_mut().load(*address, &[*previous_value]);
+            self.invalidate_cache(*address, 1);
+        }
+
+        if let Some(frames) = record.call_stack {
+            self.call_stack.set_frames(frames);
+        }
+
+        self.state = record.state;
+        self.interrupts = record.interrupts;
+        self.cycles = record.cycles;
+
+        true
+    }
+
+    /// Step back until the program counter equals an address. Returns false if the undo log ran
+    /// out first, in which case the processor is left at the oldest recorded instruction.
+    pub fn run_back_to(&mut self, program_counter: u16) -> bool {
+        while self.step_back() {
+            if self.state.program_counter == program_counter {
+                return true;
+            }
+        }
+
+        false
     }
 
     pub fn set_program_counter(&mut self, program_counter: u16) {
@@ -153,6 +461,50 @@ impl<'a> Processor<'a> {
         self.state
     }
 
+    pub fn set_state(&mut self, state: ProcessorState) {
+        self.state = state;
+    }
+
+    /// The number of cycles emulated since the processor was created
+    pub fn get_cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn set_cycles(&mut self, cycles: u64) {
+        self.cycles = cycles;
+    }
+
+    pub fn get_interrupt_state(&self) -> InterruptState {
+        self.interrupts
+    }
+
+    pub fn set_interrupt_state(&mut self, interrupts: InterruptState) {
+        self.interrupts = interrupts;
+    }
+
+    /// Drive the (level sensitive) IRQ input
+    pub fn set_irq_line(&mut self, asserted: bool) {
+        self.interrupts.irq_line = asserted;
+    }
+
+    /// Signal a falling edge on the NMI input
+    pub fn trigger_nmi(&mut self) {
+        self.interrupts.nmi_pending = true;
+    }
+
+    /// Reset the processor like its RESET input does: mask interrupts and jump through the reset
+    /// vector
+    pub fn reset(&mut self) {
+        self.interrupts = InterruptState::default();
+        self.call_stack.clear();
+        self.state.set_condition_code_flag(ConditionCodeFlag::InterruptMask, true);
+
+        if let Some(memory_manager) = self.memory_manager.as_ref() {
+            self.state.program_counter = ((memory_manager.get().read(RESET_VECTOR, 1)[0] as u16) << 8) |
+                (memory_manager.get().read(RESET_VECTOR.wrapping_add(1), 1)[0] as u16);
+        }
+    }
+
     pub fn reset_state(&mut self) {
         self.state.accumulator_a = 0;
         self.state.accumulator_b = 0;
@@ -160,6 +512,7 @@ impl<'a> Processor<'a> {
         self.state.index_register = 0;
         self.state.condition_code_register = 0;
         self.state.program_counter = 0;
+        self.call_stack.clear();
     }
 
     fn set_negative_flag(&mut self, result: u8) {
@@ -178,11 +531,29 @@ impl<'a> Processor<'a> {
         self.state.set_condition_code_flag(ConditionCodeFlag::Zero, result == 0);
     }
 
-    fn resolve_operand(&self, instruction_info: &disassembler::InstructionInfo, operand_index: usize) -> AccessDetails {
+    /// Whether an instruction reads the memory operand `resolve_operand` looks up, rather than
+    /// only using its address
+    fn reads_operand(opcode: disassembler::Opcode) -> bool {
+        !matches!(opcode, disassembler::Opcode::StoreAccumulator | disassembler::Opcode::StoreStackPointer |
+                disassembler::Opcode::StoreIndexRegister | disassembler::Opcode::Jump | disassembler::Opcode::JumpToSubroutine)
+    }
+
+    fn record_read(&mut self, address: u16, value: u8) {
+        if self.breakpoints.has_watchpoints() {
+            self.memory_accesses.push(breakpoints::MemoryAccess { kind: breakpoints::AccessKind::Read, address, value, previous_value: value });
+        }
+
+        for observer in self.observers.iter_mut() {
+            observer.memory_read(address, value);
+        }
+    }
+
+    fn resolve_operand(&mut self, instruction_info: &disassembler::InstructionInfo, operand_index: usize) -> AccessDetails {
         let operands: &Vec<disassembler::OperandType> = instruction_info.operands.as_ref().unwrap();
         let mut access_details: AccessDetails = AccessDetails { address: None, value: None, value16: None };
-        let memory_manager = self.memory_manager.as_ref().unwrap();
+        let memory_manager = self.memory_manager.as_ref().unwrap().get();
         let offset_signed: i16;
+        let value: u8;
         
         if instruction_info.opcode_info.addressing_mode == disassembler::AddressingMode::Direct {
             if let disassembler::OperandType::Immediate8(addr) = operands[operand_index] {
@@ -212,13 +583,23 @@ impl<'a> Processor<'a> {
             }
         }
 
+        if let (Some(address), Some(_)) = (access_details.address, access_details.value) {
+            if Processor::reads_operand(instruction_info.opcode_info.opcode) {
+                value = self.read_from_bus(address);
+                access_details.value = Some(value);
+                self.record_read(address, value);
+            }
+        }
+
         access_details
     }
 
-    fn resolve_operand16(&self, instruction_info: &disassembler::InstructionInfo, operand_index: usize) -> AccessDetails {
+    fn resolve_operand16(&mut self, instruction_info: &disassembler::InstructionInfo, operand_index: usize) -> AccessDetails {
         let operands: &Vec<disassembler::OperandType> = instruction_info.operands.as_ref().unwrap();
         let mut access_details: AccessDetails = AccessDetails { address: None, value: None, value16: None };
-        let memory_manager = self.memory_manager.as_ref().unwrap();
+        let memory_manager = self.memory_manager.as_ref().unwrap().get();
+        let high_byte: u8;
+        let low_byte: u8;
         
         if instruction_info.opcode_info.addressing_mode == disassembler::AddressingMode::Extended {
             if let disassembler::OperandType::Immediate16(addr) = operands[operand_index] {
@@ -234,11 +615,19 @@ impl<'a> Processor<'a> {
                 access_details.address = Some(self.state.index_register.wrapping_add(offset as u16));
                 access_details.value16 = Some(
                     ((memory_manager.read(self.state.index_register.wrapping_add(offset as u16), 1)[0] as u16) << 8) |
-                    (memory_manager.read(self.state.index_register.wrapping_add(offset as u16), 1)[1] as u16)
+                    (memory_manager.read(self.state.index_register.wrapping_add(offset as u16).wrapping_add(1), 1)[0] as u16)
                     );
             }
         }
 
+        if let (Some(address), Some(_)) = (access_details.address, access_details.value16) {
+            high_byte = self.read_from_bus(address);
+            low_byte = self.read_from_bus(address.wrapping_add(1));
+            access_details.value16 = Some(((high_byte as u16) << 8) | (low_byte as u16));
+            self.record_read(address, high_byte);
+            self.record_read(address.wrapping_add(1), low_byte);
+        }
+
         access_details
     }
 
@@ -258,12 +647,71 @@ impl<'a> Processor<'a> {
         };
     }
 
+    /// Write memory on behalf of the emulated program, recording the accesses for watchpoints
     fn write_to_memory(&mut self, address: u16, data: &[u8]) {
-        self.memory_manager.as_mut().unwrap().write(address, data);
+        let watching: bool = self.breakpoints.has_watchpoints();
+        let mut previous_value: u8;
+
+        if watching || !self.observers.is_empty() {
+            for (offset, value) in data.iter().enumerate() {
+                previous_value = self.read_from_memory(address.wrapping_add(offset as u16), 1)[0];
+
+                if watching {
+                    self.memory_accesses.push(breakpoints::MemoryAccess {
+                        kind: breakpoints::AccessKind::Write,
+                        address: address.wrapping_add(offset as u16),
+                        value: *value,
+                        previous_value
+                    });
+                }
+
+                for observer in self.observers.iter_mut() {
+                    observer.memory_write(address.wrapping_add(offset as u16), *value, previous_value);
+                }
+            }
+        }
+
+        self.store_to_memory(address, data);
+    }
+
+    fn store_to_memory(&mut self, address: u16, data: &[u8]) {
+        if let Some(buffer) = self.rewind_buffer.as_mut() {
+            let memory_manager = self.memory_manager.as_ref().unwrap().get();
+
+            for offset in 0..data.len() as u16 {
+                buffer.record_write(address.wrapping_add(offset), memory_manager.read(address.wrapping_add(offset), 1)[0]);
+            }
+        }
+
+        self.memory_manager.as_mut().unwrap().get_mut().write(address, data);
+        self.invalidate_cache(address, data.len() as u16);
+    }
+
+    /// Drop the cached instructions overlapping written bytes
+    fn invalidate_cache(&mut self, address: u16, size: u16) {
+        let memory_manager = self.memory_manager.as_ref().unwrap().get();
+
+        if let Some(cache) = self.instruction_cache.as_mut() {
+            cache.invalidate(address, size);
+
+            // The same bytes may show up at other addresses too
+            if !memory_manager.get_mirrors().is_empty() {
+                for offset in 0..size {
+                    for alias in memory_manager.get_aliases(address.wrapping_add(offset)) {
+                        cache.invalidate(alias, 1);
+                    }
+                }
+            }
+        }
     }
 
     fn read_from_memory(&self, address: u16, size: u16) -> &[u8] {
-        self.memory_manager.as_ref().unwrap().read(address, size)
+        self.memory_manager.as_ref().unwrap().get().read(address, size)
+    }
+
+    /// Read a byte on behalf of the emulated program, so a device mapped there sees the read
+    fn read_from_bus(&mut self, address: u16) -> u8 {
+        self.memory_manager.as_mut().unwrap().get_mut().read_byte(address)
     }
 
     fn set_addition_condition_codes(&mut self, accumulator: u8, operand: u8, result: u8) {
@@ -572,7 +1020,7 @@ impl<'a> Processor<'a> {
     fn decrement_index_register_handler(&mut self) {
         self.state.index_register = self.state.index_register.wrapping_sub(1);
 
-        self.set_zero_flag(((self.state.index_register >> 8) as u8) | ((self.state.index_register as u8) & 0xff));
+        self.set_zero_flag(((self.state.index_register >> 8) as u8) | (self.state.index_register as u8));
     }
 
     fn xor_handler(&mut self, instruction_info: &disassembler::InstructionInfo) {
@@ -611,7 +1059,7 @@ impl<'a> Processor<'a> {
     fn increment_index_register_handler(&mut self) {
         self.state.index_register = self.state.index_register.wrapping_add(1);
 
-        self.set_zero_flag(((self.state.index_register >> 8) as u8) | ((self.state.index_register as u8) & 0xff));
+        self.set_zero_flag(((self.state.index_register >> 8) as u8) | (self.state.index_register as u8));
     }
 
     fn jump_handler(&mut self, instruction_info: &disassembler::InstructionInfo) {
@@ -656,6 +1104,7 @@ impl<'a> Processor<'a> {
 
             high_byte = self.read_from_memory(operand_address, 1)[0]; // TODO: Optimize this
             low_byte = self.read_from_memory(operand_address.wrapping_add(1), 1)[0];
+            self.record_read(operand_address.wrapping_add(1), low_byte);
 
             operand_value = ((high_byte as u16) << 8) | (low_byte as u16);
         }
@@ -694,7 +1143,8 @@ impl<'a> Processor<'a> {
         let result: u8;
 
         self.state.stack_pointer = self.state.stack_pointer.wrapping_add(1);
-        result = self.read_from_memory(self.state.stack_pointer, 1)[0];
+        result = self.read_from_bus(self.state.stack_pointer);
+        self.record_read(self.state.stack_pointer, result);
 
         result
     }
@@ -798,6 +1248,163 @@ impl<'a> Processor<'a> {
         self.state.set_condition_code_flag(ConditionCodeFlag::Overflow, self.state.get_condition_code_flag(ConditionCodeFlag::Carry) ^ self.state.get_condition_code_flag(ConditionCodeFlag::Negative));
     }
 
+    fn read_vector(&self, vector: u16) -> u16 {
+        ((self.read_from_memory(vector, 1)[0] as u16) << 8) | (self.read_from_memory(vector.wrapping_add(1), 1)[0] as u16)
+    }
+
+    /// Stack the registers the way the processor does before entering an interrupt handler
+    fn push_registers(&mut self, program_counter: u16) {
+        self.push(word_get_low_byte!(program_counter));
+        self.push(word_get_high_byte!(program_counter));
+        self.push(word_get_low_byte!(self.state.index_register));
+        self.push(word_get_high_byte!(self.state.index_register));
+        self.push(self.state.accumulator_a);
+        self.push(self.state.accumulator_b);
+        self.push(self.state.condition_code_register);
+    }
+
+    fn enter_interrupt(&mut self, vector: u16) {
+        let interrupted_address: u16 = self.state.program_counter;
+        let return_address: u16;
+        let stacking_cycles: u64;
+
+        // WAI already stacked the registers, with the address of the next instruction
+        if self.interrupts.waiting_for_interrupt {
+            self.interrupts.waiting_for_interrupt = false;
+            return_address = interrupted_address.wrapping_add(1);
+            stacking_cycles = 0;
+        } else {
+            self.push_registers(self.state.program_counter);
+            return_address = interrupted_address;
+            stacking_cycles = INTERRUPT_CYCLES;
+        }
+
+        self.cycles += stacking_cycles;
+
+        self.state.set_condition_code_flag(ConditionCodeFlag::InterruptMask, true);
+        self.state.program_counter = self.read_vector(vector);
+
+        self.enter_frame(call_stack::Frame {
+            kind: if vector == NMI_VECTOR { call_stack::FrameKind::Nmi } else { call_stack::FrameKind::Irq },
+            call_address: interrupted_address,
+            target: self.state.program_counter,
+            return_address,
+            stack_pointer: self.state.stack_pointer
+        });
+
+        for observer in self.observers.iter_mut() {
+            observer.interrupt(if vector == NMI_VECTOR { observer::InterruptKind::Nmi } else { observer::InterruptKind::Irq },
+                    interrupted_address, &self.state, stacking_cycles);
+        }
+    }
+
+    fn enter_frame(&mut self, frame: call_stack::Frame) {
+        if let Some(buffer) = self.rewind_buffer.as_mut() {
+            buffer.record_call_stack(self.call_stack.get_frames());
+        }
+
+        self.call_stack.enter(frame);
+    }
+
+    /// Follow the calls and returns of the instruction just emulated on the shadow call stack
+    fn update_call_stack(&mut self, opcode: disassembler::Opcode, instruction_address: u16, stack_pointer: u16) {
+        let kind: call_stack::FrameKind;
+        let return_address: u16;
+
+        match opcode {
+            disassembler::Opcode::JumpToSubroutine | disassembler::Opcode::BranchToSubroutine => {
+                kind = call_stack::FrameKind::Subroutine;
+                return_address = self.read_vector(self.state.stack_pointer.wrapping_add(1));
+            },
+            disassembler::Opcode::SoftwareInterrupt => {
+                kind = call_stack::FrameKind::SoftwareInterrupt;
+                return_address = instruction_address.wrapping_add(1);
+            },
+            disassembler::Opcode::Return | disassembler::Opcode::ReturnFromInterrupt => {
+                if let Some(buffer) = self.rewind_buffer.as_mut() {
+                    buffer.record_call_stack(self.call_stack.get_frames());
+                }
+
+                self.call_stack.leave(instruction_address, opcode == disassembler::Opcode::ReturnFromInterrupt, stack_pointer,
+                        self.state.program_counter);
+                return;
+            },
+            _ => return
+        }
+
+        self.enter_frame(call_stack::Frame {
+            kind,
+            call_address: instruction_address,
+            target: self.state.program_counter,
+            return_address,
+            stack_pointer: self.state.stack_pointer
+        });
+    }
+
+    /// Enter the handler of the highest priority pending interrupt, if there is one. Returns
+    /// whether an interrupt was entered.
+    fn service_interrupts(&mut self) -> bool {
+        let device_nmi_line: bool = self.memory_manager.as_ref().unwrap().get().get_nmi();
+
+        // NMI is edge triggered, devices interrupt when they start asserting it
+        if device_nmi_line && !self.interrupts.device_nmi_line {
+            self.interrupts.nmi_pending = true;
+        }
+
+        self.interrupts.device_nmi_line = device_nmi_line;
+
+        if self.interrupts.nmi_pending {
+            self.interrupts.nmi_pending = false;
+            self.enter_interrupt(NMI_VECTOR);
+        } else if (self.interrupts.irq_line || self.memory_manager.as_ref().unwrap().get().get_irq()) && !self.state.get_condition_code_flag(ConditionCodeFlag::InterruptMask) {
+            self.enter_interrupt(IRQ_VECTOR);
+        } else {
+            return false;
+        }
+
+        true
+    }
+
+    fn software_interrupt_handler(&mut self) {
+        let instruction_address: u16 = self.state.program_counter;
+
+        self.push_registers(instruction_address.wrapping_add(1));
+
+        self.state.set_condition_code_flag(ConditionCodeFlag::InterruptMask, true);
+        self.state.program_counter = self.read_vector(SOFTWARE_INTERRUPT_VECTOR);
+
+        for observer in self.observers.iter_mut() {
+            observer.interrupt(observer::InterruptKind::Software, instruction_address, &self.state, 0);
+        }
+    }
+
+    fn wait_for_interrupt_handler(&mut self) {
+        // The program counter stays on the WAI instruction until an interrupt arrives, but the
+        // address of the next instruction is the one returned to
+        if !self.interrupts.waiting_for_interrupt {
+            self.push_registers(self.state.program_counter.wrapping_add(1));
+            self.interrupts.waiting_for_interrupt = true;
+        }
+    }
+
+    fn return_from_interrupt_handler(&mut self) {
+        let program_counter_low: u8;
+        let program_counter_high: u8;
+        let index_register_low: u8;
+        let index_register_high: u8;
+
+        self.state.condition_code_register = self.pop();
+        self.state.accumulator_b = self.pop();
+        self.state.accumulator_a = self.pop();
+        index_register_high = self.pop();
+        index_register_low = self.pop();
+        program_counter_high = self.pop();
+        program_counter_low = self.pop();
+
+        self.state.index_register = ((index_register_high as u16) << 8) | (index_register_low as u16);
+        self.state.program_counter = ((program_counter_high as u16) << 8) | (program_counter_low as u16);
+    }
+
     fn return_handler(&mut self) {
         let program_counter_low: u8;
         let program_counter_high: u8;
@@ -886,28 +1493,96 @@ impl<'a> Processor<'a> {
         // TODO: Implement this
     }
 
+    /// Decode the instruction at the program counter, going through the instruction cache when
+    /// it is enabled
+    fn fetch_instruction(&mut self) -> Result<Arc<disassembler::InstructionInfo>, disassembler::DisassemblyError> {
+        let program_counter: u16 = self.state.program_counter;
+        let memory_manager: &memory_manager::MemoryManager = self.memory_manager.as_ref().unwrap().get();
+        let instruction_info: Arc<disassembler::InstructionInfo>;
+        let mut data_stream: [u8; MAX_INSTRUCTION_LENGTH as usize] = [0; MAX_INSTRUCTION_LENGTH as usize];
+
+        if let Some(cache) = self.instruction_cache.as_mut() {
+            if let Some(cached_instruction_info) = cache.get(program_counter) {
+                return Ok(cached_instruction_info);
+            }
+        }
+
+        // Instructions at the top of memory continue at $0000
+        for (offset, byte) in data_stream.iter_mut().enumerate() {
+            *byte = memory_manager.read(program_counter.wrapping_add(offset as u16), 1)[0];
+        }
+
+        instruction_info = Arc::new(disassembler::disassemble_instruction(&data_stream)?);
+
+        if let Some(cache) = self.instruction_cache.as_mut() {
+            cache.insert(program_counter, Arc::clone(&instruction_info));
+        }
+
+        Ok(instruction_info)
+    }
+
+    fn trace_instruction(&mut self, instruction_info: &disassembler::InstructionInfo) {
+        let mut bytes: Vec<u8> = Vec::with_capacity(MAX_INSTRUCTION_LENGTH as usize);
+        let record: trace::TraceRecord;
+
+        for offset in 0..instruction_info.opcode_info.instruction_length as u16 {
+            bytes.push(self.read_from_memory(self.state.program_counter.wrapping_add(offset), 1)[0]);
+        }
+
+        record = trace::TraceRecord::new(&self.state, &bytes, self.cycles);
+        self.tracer.as_mut().unwrap().record(&record);
+    }
+
+    /// Emulate the next instruction, or enter the handler of a pending interrupt and emulate its
+    /// first instruction. Returns the instruction emulated.
     pub fn emulate_instruction(&mut self) -> Result<disassembler::InstructionInfo, EmulationError> {
-        let instruction_info: disassembler::InstructionInfo;
-        let memory_manager: &mut memory_manager::MemoryManager;
-        let data_stream: &[u8]; 
+        self.execute_instruction().map(|instruction_info| (*instruction_info).clone())
+    }
+
+    /// `emulate_instruction`, returning the decoded instruction shared with the instruction cache
+    fn execute_instruction(&mut self) -> Result<Arc<disassembler::InstructionInfo>, EmulationError> {
+        let instruction_info: Arc<disassembler::InstructionInfo>;
+        let interrupted: bool;
+        let instruction_address: u16;
+        let stack_pointer: u16;
+        let previous_state: ProcessorState;
+        let start_cycles: u64 = self.cycles;
 
         // Return an error if there is no memory manager defined
         if self.memory_manager.is_none() {
             return Err(EmulationError::NoMemoryManager);
         }
-    
-        memory_manager = self.memory_manager.as_mut().unwrap();
 
-        // TODO: This has a bug. If we read from the end of the memory and the length of the final
-        //       instruction is less the MAX_INSTRUCTION_SIZE, we will would cause an error in the
-        //       memory manager
-        data_stream = memory_manager.read(self.state.program_counter, MAX_INSTRUCTION_LENGTH);
-        instruction_info = match disassembler::disassemble_instruction(data_stream) {
+        if let Some(buffer) = self.rewind_buffer.as_mut() {
+            buffer.begin(self.state, self.interrupts, self.cycles);
+        }
+
+        self.memory_accesses.clear();
+        interrupted = self.service_interrupts();
+        instruction_address = self.state.program_counter;
+        stack_pointer = self.state.stack_pointer;
+
+        instruction_info = match self.fetch_instruction() {
             Ok(info) => info,
-            Err(err) => return Err(EmulationError::DisassemblyError(err))
+            Err(err) => {
+                // Entering an interrupt handler still has to be undoable
+                if let Some(buffer) = self.rewind_buffer.as_mut() {
+                    if interrupted { buffer.commit() } else { buffer.abort() }
+                }
+
+                return Err(EmulationError::DisassemblyError(err));
+            }
         };
 
-        // println!("{:#x}", data_stream[0]);
+        if self.tracer.is_some() {
+            self.trace_instruction(&instruction_info);
+        }
+
+        previous_state = self.state;
+
+        for observer in self.observers.iter_mut() {
+            observer.before_instruction(&instruction_info, &self.state, self.call_stack.get_frames());
+        }
 
         // Emulate the instruction based on the opcode group
         match instruction_info.opcode_info.opcode {
@@ -924,7 +1599,7 @@ impl<'a> Processor<'a> {
             disassembler::Opcode::BranchIfGreaterThan => self.branch_conditionally_handler(&instruction_info, |state| !(state.get_condition_code_flag(ConditionCodeFlag::Zero) | (state.get_condition_code_flag(ConditionCodeFlag::Negative) ^ state.get_condition_code_flag(ConditionCodeFlag::Overflow)))),
             disassembler::Opcode::BranchIfHigherThan => self.branch_conditionally_handler(&instruction_info, |state| !(state.get_condition_code_flag(ConditionCodeFlag::Carry) | state.get_condition_code_flag(ConditionCodeFlag::Zero))),
             disassembler::Opcode::BitTest => self.bit_test_handler(&instruction_info),
-            disassembler::Opcode::BranchIfLessThanEqaul => self.branch_conditionally_handler(&instruction_info, |state| (state.get_condition_code_flag(ConditionCodeFlag::Zero) | (state.get_condition_code_flag(ConditionCodeFlag::Negative) ^ state.get_condition_code_flag(ConditionCodeFlag::Overflow)))),
+            disassembler::Opcode::BranchIfLessThanEqaul => self.branch_conditionally_handler(&instruction_info, |state| state.get_condition_code_flag(ConditionCodeFlag::Zero) | (state.get_condition_code_flag(ConditionCodeFlag::Negative) ^ state.get_condition_code_flag(ConditionCodeFlag::Overflow))),
             disassembler::Opcode::BranchIfLowerThanEqual => self.branch_conditionally_handler(&instruction_info, |state| state.get_condition_code_flag(ConditionCodeFlag::Carry) | state.get_condition_code_flag(ConditionCodeFlag::Zero)),
             disassembler::Opcode::BranchIfLessThan => self.branch_conditionally_handler(&instruction_info, |state| state.get_condition_code_flag(ConditionCodeFlag::Negative) ^ state.get_condition_code_flag(ConditionCodeFlag::Overflow)),
             disassembler::Opcode::BranchIfMinus => self.branch_conditionally_handler(&instruction_info, |state| state.get_condition_code_flag(ConditionCodeFlag::Negative)),
@@ -963,7 +1638,7 @@ impl<'a> Processor<'a> {
             disassembler::Opcode::Pop => self.pop_handler(&instruction_info),
             disassembler::Opcode::RotateLeft => self.rotate_left_handler(&instruction_info),
             disassembler::Opcode::RotateRight => self.rotate_right_handler(&instruction_info),
-            // TODO: Return from interrupt
+            disassembler::Opcode::ReturnFromInterrupt => self.return_from_interrupt_handler(),
             disassembler::Opcode::Return => self.return_handler(),
             disassembler::Opcode::SubtractBFromA => self.subtract_accumulators_handler(),
             disassembler::Opcode::SubtractWithCarry => self.subtract_with_carry_handler(&instruction_info),
@@ -974,7 +1649,7 @@ impl<'a> Processor<'a> {
             disassembler::Opcode::StoreStackPointer => self.store_stack_pointer_handler(&instruction_info),
             disassembler::Opcode::StoreIndexRegister => self.store_index_register_handler(&instruction_info),
             disassembler::Opcode::Subtract => self.subtract_handler(&instruction_info),
-            // TODO: Software interrupt
+            disassembler::Opcode::SoftwareInterrupt => self.software_interrupt_handler(),
             disassembler::Opcode::TransferAToB => self.transfer_a_to_b_handler(),
             disassembler::Opcode::TransferAToConditionCodes => self.transfer_a_to_condition_codes_handler(),
             disassembler::Opcode::TransferBToA => self.transfer_b_to_a_handler(),
@@ -982,8 +1657,7 @@ impl<'a> Processor<'a> {
             disassembler::Opcode::Test => self.test_handler(&instruction_info),
             disassembler::Opcode::TransferStackPointerToIndexRegister => self.transfer_stack_pointer_to_index_register_handler(),
             disassembler::Opcode::TransferIndexRegisterToStackPointer => self.transfer_index_register_to_stack_pointer_handler(),
-            // TODO: Wait for interrupt
-            _ => {}
+            disassembler::Opcode::WaitForInterrupt => self.wait_for_interrupt_handler()
         };
 
         // For most instructions (not branch or jump instructions), increment PC according to the
@@ -992,6 +1666,196 @@ impl<'a> Processor<'a> {
             self.increment_program_counter(&instruction_info);
         }
 
+        self.update_call_stack(instruction_info.opcode_info.opcode, instruction_address, stack_pointer);
+        self.cycles += instruction_info.opcode_info.cycles as u64;
+
+        // Devices are clocked by everything the instruction took, including entering an interrupt
+        self.memory_manager.as_mut().unwrap().get_mut().tick(self.cycles - start_cycles);
+
+        for observer in self.observers.iter_mut() {
+            observer.after_instruction(&instruction_info, &previous_state, &self.state, instruction_info.opcode_info.cycles as u64);
+        }
+
+        if let Some(buffer) = self.rewind_buffer.as_mut() {
+            buffer.commit();
+        }
+
         Ok(instruction_info)
     }
+
+    /// Check whether one of the stop conditions is met after an instruction was emulated
+    fn check_stop_conditions(&self, conditions: &[StopCondition], statistics: &RunStatistics, instruction_address: u16) -> Option<StopReason> {
+        for condition in conditions {
+            match *condition {
+                StopCondition::ProgramCounterEquals(address) => {
+                    if self.state.program_counter == address {
+                        return Some(StopReason::ProgramCounterReached(address));
+                    }
+                },
+                StopCondition::InstructionBudget(budget) => {
+                    if statistics.instructions >= budget {
+                        return Some(StopReason::InstructionBudgetExhausted);
+                    }
+                },
+                StopCondition::CycleBudget(budget) => {
+                    if statistics.cycles >= budget {
+                        return Some(StopReason::CycleBudgetExhausted);
+                    }
+                },
+                StopCondition::InfiniteLoop => {
+                    if self.state.program_counter == instruction_address && !self.interrupts.waiting_for_interrupt {
+                        return Some(StopReason::InfiniteLoop(instruction_address));
+                    }
+                },
+                StopCondition::ReturnTo { address, stack_pointer } => {
+                    if self.state.program_counter == address && call_stack::is_above(self.state.stack_pointer, stack_pointer) {
+                        return Some(StopReason::Returned(address));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Emulate instructions until one of the stop conditions is met, a breakpoint or watchpoint
+    /// fires or an instruction cannot be emulated. Without any condition the processor runs until
+    /// it hits an illegal opcode.
+    pub fn run(&mut self, conditions: &[StopCondition]) -> RunResult {
+        let mut statistics: RunStatistics = RunStatistics::default();
+        let mut instruction_address: u16;
+        let start_cycles: u64 = self.cycles;
+
+        loop {
+            instruction_address = self.state.program_counter;
+
+            // A breakpoint at the address the run starts from does not fire, so a run stopped by
+            // a breakpoint can be resumed
+            if statistics.instructions > 0 {
+                if let Some(hit) = self.check_breakpoints() {
+                    return RunResult { stop_reason: StopReason::Breakpoint(hit), statistics };
+                }
+            }
+
+            match self.execute_instruction() {
+                Ok(_) => {},
+                Err(EmulationError::DisassemblyError(_)) => {
+                    return RunResult { stop_reason: StopReason::IllegalOpcode(instruction_address), statistics };
+                },
+                Err(err) => return RunResult { stop_reason: StopReason::Error(err), statistics }
+            };
+
+            statistics.instructions += 1;
+            statistics.cycles = self.cycles - start_cycles;
+
+            if let Some(exit_code) = self.memory_manager.as_mut().unwrap().get_mut().take_exit_code() {
+                return RunResult { stop_reason: StopReason::Exited(exit_code), statistics };
+            }
+
+            if !self.memory_accesses.is_empty() {
+                if let Some(hit) = self.breakpoints.check_accesses(&self.memory_accesses, instruction_address, &self.state,
+                        self.memory_manager.as_ref().unwrap().get()) {
+                    return RunResult { stop_reason: StopReason::Breakpoint(hit), statistics };
+                }
+            }
+
+            if let Some(stop_reason) = self.check_stop_conditions(conditions, &statistics, instruction_address) {
+                return RunResult { stop_reason, statistics };
+            }
+        }
+    }
+
+    /// Decode the instruction at the program counter without emulating it
+    fn peek_instruction(&self) -> Option<disassembler::InstructionInfo> {
+        let memory_manager: &memory_manager::MemoryManager = self.memory_manager.as_ref()?.get();
+        let bytes: Vec<u8> = (0..MAX_INSTRUCTION_LENGTH)
+            .map(|offset| memory_manager.read(self.state.program_counter.wrapping_add(offset), 1)[0])
+            .collect();
+
+        disassembler::disassemble_instruction(&bytes).ok()
+    }
+
+    /// The condition which stops a run once the call at the program counter (JSR, BSR or SWI)
+    /// returned, or None if the instruction is not a call
+    pub fn get_step_over_condition(&self) -> Option<StopCondition> {
+        let instruction_info: disassembler::InstructionInfo = self.peek_instruction()?;
+
+        match instruction_info.opcode_info.opcode {
+            disassembler::Opcode::JumpToSubroutine | disassembler::Opcode::BranchToSubroutine | disassembler::Opcode::SoftwareInterrupt => {
+                Some(StopCondition::ReturnTo {
+                    address: self.state.program_counter.wrapping_add(instruction_info.opcode_info.instruction_length as u16),
+                    stack_pointer: self.state.stack_pointer.wrapping_sub(1)
+                })
+            },
+            _ => None
+        }
+    }
+
+    /// The condition which stops a run once the innermost frame of the call stack returned, or
+    /// None outside of any known call
+    pub fn get_step_out_condition(&self) -> Option<StopCondition> {
+        self.call_stack.get_frames().last().map(|frame| StopCondition::ReturnTo { address: frame.return_address, stack_pointer: frame.stack_pointer })
+    }
+
+    /// Run with the given conditions and an extra one, adding up the statistics of a previous run
+    fn run_with(&mut self, conditions: &[StopCondition], extra_condition: StopCondition, statistics: RunStatistics) -> RunResult {
+        let mut conditions: Vec<StopCondition> = conditions.to_vec();
+        let mut result: RunResult;
+
+        conditions.push(extra_condition);
+        result = self.run(&conditions);
+        result.statistics.instructions += statistics.instructions;
+        result.statistics.cycles += statistics.cycles;
+
+        result
+    }
+
+    /// Emulate a single instruction, running subroutine calls and software interrupts until they
+    /// return. Interrupt handlers entered on the way are run to completion as well. The run ends
+    /// with `StopReason::Returned` after a call, or `StopReason::Stepped` after any other
+    /// instruction, unless one of `conditions` or a breakpoint stops it first.
+    pub fn step_over(&mut self, conditions: &[StopCondition]) -> RunResult {
+        let program_counter: u16 = self.state.program_counter;
+        let stack_pointer: u16 = self.state.stack_pointer;
+        let mut result: RunResult = RunResult { stop_reason: StopReason::InstructionBudgetExhausted, statistics: RunStatistics::default() };
+        let mut frame: call_stack::Frame;
+
+        loop {
+            if let Some(condition) = self.get_step_over_condition() {
+                return self.run_with(conditions, condition, result.statistics);
+            }
+
+            result = self.run_with(conditions, StopCondition::InstructionBudget(1), result.statistics);
+
+            if result.stop_reason != StopReason::InstructionBudgetExhausted {
+                return result;
+            }
+
+            // The instruction was interrupted before it ran, so finish the handler and try again
+            frame = match self.call_stack.get_frames().last() {
+                Some(frame) if frame.kind.is_interrupt() && frame.call_address == program_counter
+                        && call_stack::is_above(stack_pointer, frame.stack_pointer) => *frame,
+                _ => return RunResult { stop_reason: StopReason::Stepped, statistics: result.statistics }
+            };
+
+            result = self.run_with(conditions, StopCondition::ReturnTo { address: frame.return_address, stack_pointer: frame.stack_pointer },
+                    result.statistics);
+
+            // WAI returns past itself
+            if !matches!(result.stop_reason, StopReason::Returned(_)) || self.state.program_counter != program_counter {
+                return result;
+            }
+
+            result.stop_reason = StopReason::InstructionBudgetExhausted;
+        }
+    }
+
+    /// Run until the innermost subroutine or interrupt handler returns, ending with
+    /// `StopReason::Returned` unless one of `conditions` or a breakpoint stops it first. Returns
+    /// None if the call stack is empty.
+    pub fn step_out(&mut self, conditions: &[StopCondition]) -> Option<RunResult> {
+        let condition: StopCondition = self.get_step_out_condition()?;
+
+        Some(self.run_with(conditions, condition, RunStatistics::default()))
+    }
 }
@@ -0,0 +1,82 @@
+use crate::et3400;
+use crate::machine;
+use crate::mek6800d2;
+use crate::memory_manager;
+
+/// Why a ready-made machine could not be built
+#[derive(Debug)]
+pub enum ProfileError {
+    /// The ROM image is not a power of two, or does not fit the ROM space of the board
+    RomSize(usize)
+}
+
+/// The ready-made machines
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MachineProfile {
+    /// The SWTPC 6800 with an MP-S serial card, running SWTBUG, see `swtpc`
+    Swtpc,
+    /// The Heathkit ET-3400 trainer, see `et3400`
+    Et3400,
+    /// The Motorola MEK6800D2 evaluation kit running JBUG, see `mek6800d2`
+    Mek6800D2
+}
+
+impl MachineProfile {
+    pub fn from_name(name: &str) -> Option<MachineProfile> {
+        match name {
+            "swtpc" => Some(MachineProfile::Swtpc),
+            "et3400" => Some(MachineProfile::Et3400),
+            "mek6800d2" => Some(MachineProfile::Mek6800D2),
+            _ => None
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            MachineProfile::Swtpc => "swtpc",
+            MachineProfile::Et3400 => "et3400",
+            MachineProfile::Mek6800D2 => "mek6800d2"
+        }
+    }
+
+    /// The display of a board with a front panel drawn as text, None for boards used through a
+    /// terminal
+    pub fn render_display(&self, machine: &machine::Machine) -> Option<String> {
+        match self {
+            MachineProfile::Swtpc => None,
+            MachineProfile::Et3400 => Some(et3400::render_display(machine)),
+            MachineProfile::Mek6800D2 => Some(mek6800d2::render_display(machine))
+        }
+    }
+
+    /// Pass a character typed on the host to the keypad of the board. Returns false if it stands
+    /// for no key.
+    pub fn press_host_key(&self, machine: &mut machine::Machine, character: u8) -> bool {
+        match self {
+            MachineProfile::Swtpc => false,
+            MachineProfile::Et3400 => et3400::press_host_key(machine, character),
+            MachineProfile::Mek6800D2 => mek6800d2::press_host_key(machine, character)
+        }
+    }
+}
+
+/// Map a monitor ROM at an address and mirror it at the top of the address space, where the
+/// processor finds its vectors. Boards get the mirror for free by leaving upper address lines
+/// undecoded. The ROM must be a power of two of at most `maximum_size` bytes.
+pub fn install_monitor_rom(machine: &mut machine::Machine, address: u16, rom: &[u8], maximum_size: usize) -> Result<(), ProfileError> {
+    let memory_manager: &mut memory_manager::MemoryManager = machine.get_memory_manager_mut();
+    let mirror_address: u16;
+
+    if !rom.len().is_power_of_two() || rom.len() > maximum_size {
+        return Err(ProfileError::RomSize(rom.len()));
+    }
+
+    memory_manager.load_rom(address, rom);
+    mirror_address = (memory_manager::BUFFER_SIZE - rom.len()) as u16;
+
+    if mirror_address != address {
+        memory_manager.add_mirror(mirror_address, rom.len() as u32, address);
+    }
+
+    Ok(())
+}
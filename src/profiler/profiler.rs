@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::call_stack;
+use crate::disassembler;
+use crate::observer;
+use crate::processor;
+use crate::symbols;
+
+/// The cycles spent in a routine, keyed by its entry address
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RoutineProfile {
+    pub address: u16,
+    /// How many times the routine was called (or the handler entered)
+    pub calls: u64,
+    /// Cycles spent in the routine itself
+    pub self_cycles: u64,
+    /// Cycles spent in the routine and everything it called
+    pub inclusive_cycles: u64
+}
+
+/// The cycles spent on the instruction at an address
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InstructionProfile {
+    pub address: u16,
+    pub executions: u64,
+    pub cycles: u64
+}
+
+/// Attributes the cycles of every instruction to its address, to the routine it is in and to
+/// the chain of calls leading there
+///
+/// Attach it to a processor with `Processor::add_observer`. The chain of calls is the call stack
+/// the processor follows for backtraces. Code running before the first call is attributed to the
+/// routine profiling started in.
+#[derive(Clone, Default)]
+pub struct Profiler {
+    /// The address profiling started at, standing for the code outside of any call
+    root: Option<u16>,
+    /// The frames of the call stack the current instruction runs in
+    frames: Vec<call_stack::Frame>,
+    /// The entry addresses of the routines on the stack, outermost (the start address) first
+    current_stack: Vec<u16>,
+    instructions: BTreeMap<u16, InstructionProfile>,
+    routines: BTreeMap<u16, RoutineProfile>,
+    stacks: BTreeMap<Vec<u16>, u64>,
+    total_cycles: u64,
+    total_instructions: u64,
+    /// The cycles an interrupt spent stacking the registers, charged to its handler
+    interrupt_cycles: u64
+}
+
+fn percentage(part: u64, total: u64) -> f64 {
+    if total == 0 { 0.0 } else { part as f64 * 100.0 / total as f64 }
+}
+
+/// Name a routine for folded stacks, which cannot contain spaces or semicolons
+fn frame_name(address: u16, symbols: &symbols::SymbolTable) -> String {
+    match symbols.get_nearest(address) {
+        Some((name, 0)) => String::from(name),
+        Some((name, offset)) => format!("{}+{}", name, offset),
+        None => format!("${:04X}", address)
+    }.replace([' ', ';'], "_")
+}
+
+impl observer::Observer for Profiler {
+    fn before_instruction(&mut self, _instruction_info: &disassembler::InstructionInfo, state: &processor::ProcessorState,
+            call_stack: &[call_stack::Frame]) {
+        if self.root.is_none() {
+            self.root = Some(state.program_counter);
+            self.enter_routine(state.program_counter);
+        }
+
+        // The instruction belongs to the routine it starts in, so a call is charged to the caller
+        // and a return to the callee
+        if call_stack != self.frames.as_slice() || self.current_stack.is_empty() {
+            self.follow(call_stack);
+        }
+
+        if self.interrupt_cycles > 0 {
+            self.charge(self.interrupt_cycles);
+            self.interrupt_cycles = 0;
+        }
+    }
+
+    fn after_instruction(&mut self, _instruction_info: &disassembler::InstructionInfo, before: &processor::ProcessorState,
+            _after: &processor::ProcessorState, cycles: u64) {
+        let profile: &mut InstructionProfile = self.instructions.entry(before.program_counter)
+            .or_insert(InstructionProfile { address: before.program_counter, executions: 0, cycles: 0 });
+
+        profile.executions += 1;
+        profile.cycles += cycles;
+        self.total_instructions += 1;
+        self.charge(cycles);
+    }
+
+    fn interrupt(&mut self, _kind: observer::InterruptKind, _from: u16, _state: &processor::ProcessorState, cycles: u64) {
+        self.interrupt_cycles += cycles;
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    fn enter_routine(&mut self, address: u16) {
+        self.routines.entry(address).or_insert(RoutineProfile { address, calls: 0, self_cycles: 0, inclusive_cycles: 0 }).calls += 1;
+    }
+
+    /// Take the routines on the stack from the frames of the call stack, counting a call for
+    /// every frame entered since the last instruction
+    fn follow(&mut self, call_stack: &[call_stack::Frame]) {
+        let kept: usize = self.frames.iter().zip(call_stack).take_while(|(old, new)| old == new).count();
+
+        for frame in &call_stack[kept..] {
+            self.enter_routine(frame.target);
+        }
+
+        self.frames.clear();
+        self.frames.extend_from_slice(call_stack);
+        self.current_stack.clear();
+        self.current_stack.extend(self.root);
+        self.current_stack.extend(call_stack.iter().map(|frame| frame.target));
+    }
+
+    /// Charge cycles to the innermost routine, to every routine on the stack and to the stack
+    fn charge(&mut self, cycles: u64) {
+        let innermost: u16 = *self.current_stack.last().unwrap();
+
+        self.total_cycles += cycles;
+        self.routines.get_mut(&innermost).unwrap().self_cycles += cycles;
+
+        // Recursive routines appear several times but spend the cycles only once
+        for (index, address) in self.current_stack.iter().enumerate() {
+            if !self.current_stack[..index].contains(address) {
+                self.routines.get_mut(address).unwrap().inclusive_cycles += cycles;
+            }
+        }
+
+        match self.stacks.get_mut(&self.current_stack) {
+            Some(stack_cycles) => *stack_cycles += cycles,
+            None => { self.stacks.insert(self.current_stack.clone(), cycles); }
+        }
+    }
+
+    pub fn get_total_cycles(&self) -> u64 {
+        self.total_cycles
+    }
+
+    pub fn get_total_instructions(&self) -> u64 {
+        self.total_instructions
+    }
+
+    /// The routines, the ones with the most inclusive cycles first
+    pub fn get_routines(&self) -> Vec<RoutineProfile> {
+        let mut routines: Vec<RoutineProfile> = self.routines.values().copied().collect();
+
+        routines.sort_by(|left, right| right.inclusive_cycles.cmp(&left.inclusive_cycles).then(left.address.cmp(&right.address)));
+        routines
+    }
+
+    /// The instructions, the ones with the most cycles first
+    pub fn get_instructions(&self) -> Vec<InstructionProfile> {
+        let mut instructions: Vec<InstructionProfile> = self.instructions.values().copied().collect();
+
+        instructions.sort_by(|left, right| right.cycles.cmp(&left.cycles).then(left.address.cmp(&right.address)));
+        instructions
+    }
+
+    /// Format the hottest routines and instructions (at most `count` of each) as a table
+    pub fn format_report(&self, symbols: &symbols::SymbolTable, count: usize) -> String {
+        let mut output: String = String::new();
+
+        writeln!(output, "{} cycles in {} instructions", self.total_cycles, self.total_instructions).unwrap();
+        writeln!(output).unwrap();
+        writeln!(output, "{:>12} {:>7} {:>12} {:>7} {:>8}  routine", "inclusive", "%", "self", "%", "calls").unwrap();
+
+        for routine in self.get_routines().iter().take(count) {
+            writeln!(output, "{:>12} {:>6.2}% {:>12} {:>6.2}% {:>8}  {}", routine.inclusive_cycles, percentage(routine.inclusive_cycles, self.total_cycles),
+                    routine.self_cycles, percentage(routine.self_cycles, self.total_cycles), routine.calls, symbols.format_address(routine.address)).unwrap();
+        }
+
+        writeln!(output).unwrap();
+        writeln!(output, "{:>12} {:>7} {:>12}  instruction", "cycles", "%", "executions").unwrap();
+
+        for instruction in self.get_instructions().iter().take(count) {
+            writeln!(output, "{:>12} {:>6.2}% {:>12}  {}", instruction.cycles, percentage(instruction.cycles, self.total_cycles),
+                    instruction.executions, symbols.format_address(instruction.address)).unwrap();
+        }
+
+        output
+    }
+
+    /// Format the cycles of every call chain as folded stacks (`outer;inner cycles`), the input
+    /// of `flamegraph.pl`, inferno and speedscope
+    pub fn to_folded(&self, symbols: &symbols::SymbolTable) -> String {
+        let mut output: String = String::new();
+
+        for (stack, cycles) in &self.stacks {
+            writeln!(output, "{} {}", stack.iter().map(|address| frame_name(*address, symbols)).collect::<Vec<String>>().join(";"), cycles).unwrap();
+        }
+
+        output
+    }
+}
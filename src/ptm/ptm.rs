@@ -0,0 +1,419 @@
+use crate::device;
+use crate::save_state;
+
+// Control register bits
+/// In control register 1, holds every timer in reset. In control register 2, selects control
+/// register 1 at offset 0 instead of control register 3. In control register 3, divides the
+/// clock of timer 3 by 8.
+const CONTROL_BIT0: u8 = 0x01;
+const CONTROL_INTERNAL_CLOCK: u8 = 0x02;
+const CONTROL_DUAL_8_BIT: u8 = 0x04;
+/// Measurement (frequency or pulse width comparison) instead of waveform synthesis
+const CONTROL_MEASUREMENT: u8 = 0x08;
+/// In waveform modes, only the gate and a reset initialize the counter, not writing the latches.
+/// In measurement modes, selects pulse width comparison over frequency comparison.
+const CONTROL_BIT4: u8 = 0x10;
+/// In waveform modes, single-shot instead of continuous. In measurement modes, interrupt when the
+/// gate period is longer than the time-out instead of shorter.
+const CONTROL_BIT5: u8 = 0x20;
+const CONTROL_INTERRUPT_ENABLED: u8 = 0x40;
+const CONTROL_OUTPUT_ENABLED: u8 = 0x80;
+
+pub const STATUS_INTERRUPT_REQUEST: u8 = 0x80;
+
+// The clock of timer 3 can be divided by this
+const PRESCALER: u8 = 8;
+
+// The size of the saved state of one timer
+const TIMER_STATE_SIZE: usize = 10;
+
+/// One of the three counters
+#[derive(Clone, Copy, Default)]
+struct Timer {
+    control: u8,
+    latch: u16,
+    counter: u16,
+    output: bool,
+    flag: bool,
+    /// The level of the gate input, which enables counting while low
+    gate: bool,
+    /// The counter timed out since it was initialized
+    timed_out: bool,
+    /// Clocks seen by the prescaler of timer 3
+    prescaler: u8
+}
+
+impl Timer {
+    fn is_measurement(&self) -> bool {
+        self.control & CONTROL_MEASUREMENT != 0
+    }
+
+    fn is_single_shot(&self) -> bool {
+        !self.is_measurement() && self.control & CONTROL_BIT5 != 0
+    }
+
+    fn is_dual_8_bit(&self) -> bool {
+        self.control & CONTROL_DUAL_8_BIT != 0
+    }
+
+    /// Load the counter from the latches and start a new period
+    fn initialize(&mut self) {
+        self.counter = self.latch;
+        self.flag = false;
+        self.timed_out = false;
+        self.output = self.is_single_shot() && !self.is_dual_8_bit();
+
+        if self.is_dual_8_bit() && !self.is_measurement() {
+            self.output = self.counter >> 8 == 0;
+        }
+    }
+
+    fn time_out(&mut self) {
+        if !self.is_measurement() {
+            self.flag = true;
+
+            // A single shot pulse ends at the first time-out, a continuous wave toggles
+            if !self.is_dual_8_bit() {
+                self.output = !self.is_single_shot() && !self.output;
+            }
+        } else if self.control & CONTROL_BIT5 != 0 && !self.timed_out {
+            // Measurement modes which interrupt on a long period do so at the first time-out
+            self.flag = true;
+        }
+
+        self.timed_out = true;
+    }
+
+    /// Count one clock
+    fn clock(&mut self) {
+        let mut low: u16;
+        let mut high: u16;
+
+        // A high gate holds the counter, except in frequency comparison where the gate only
+        // marks the periods
+        if self.gate && !(self.is_measurement() && self.control & CONTROL_BIT4 == 0) {
+            return;
+        }
+
+        if !self.is_dual_8_bit() {
+            if self.counter == 0 {
+                self.time_out();
+                self.counter = self.latch;
+            } else {
+                self.counter -= 1;
+            }
+
+            return;
+        }
+
+        low = self.counter & 0xFF;
+        high = self.counter >> 8;
+
+        if low == 0 {
+            if high == 0 {
+                self.time_out();
+                high = self.latch >> 8;
+            } else {
+                high -= 1;
+            }
+
+            low = self.latch & 0xFF;
+        } else {
+            low -= 1;
+        }
+
+        self.counter = (high << 8) | low;
+
+        // The output is high while the high byte is down to zero, once for a single shot
+        if !self.is_measurement() {
+            self.output = high == 0 && !(self.is_single_shot() && self.timed_out);
+        }
+    }
+
+    fn set_gate(&mut self, level: bool) {
+        let falling: bool = self.gate && !level;
+        let rising: bool = !self.gate && level;
+
+        self.gate = level;
+
+        if self.is_measurement() {
+            if self.control & CONTROL_BIT4 == 0 {
+                // Frequency comparison: the period runs from one falling edge to the next
+                if falling {
+                    if self.control & CONTROL_BIT5 == 0 && !self.timed_out {
+                        self.flag = true;
+                    }
+
+                    self.counter = self.latch;
+                    self.timed_out = false;
+                }
+            } else if falling {
+                // Pulse width comparison: the pulse runs while the gate is low
+                self.counter = self.latch;
+                self.timed_out = false;
+            } else if rising && self.control & CONTROL_BIT5 == 0 && !self.timed_out {
+                self.flag = true;
+            }
+        } else if falling {
+            self.initialize();
+        }
+    }
+
+    fn get_output(&self) -> bool {
+        self.control & CONTROL_OUTPUT_ENABLED != 0 && !self.is_measurement() && self.output
+    }
+
+    fn irq(&self) -> bool {
+        self.flag && self.control & CONTROL_INTERRUPT_ENABLED != 0
+    }
+
+    fn save(&self, data: &mut Vec<u8>) {
+        data.push(self.control);
+        data.extend_from_slice(&self.latch.to_be_bytes());
+        data.extend_from_slice(&self.counter.to_be_bytes());
+        data.extend_from_slice(&[self.output as u8, self.flag as u8, self.gate as u8, self.timed_out as u8, self.prescaler]);
+    }
+
+    fn restore(&mut self, data: &[u8]) {
+        self.control = data[0];
+        self.latch = ((data[1] as u16) << 8) | (data[2] as u16);
+        self.counter = ((data[3] as u16) << 8) | (data[4] as u16);
+        self.output = data[5] != 0;
+        self.flag = data[6] != 0;
+        self.gate = data[7] != 0;
+        self.timed_out = data[8] != 0;
+        self.prescaler = data[9];
+    }
+}
+
+/// An MC6840 programmable timer module
+///
+/// The PTM takes eight addresses: control register 1 or 3 (selected by bit 0 of control register
+/// 2) and the status register, control register 2, then the counter (read) or latches (write) of
+/// each timer, most significant byte first. Counters on the internal clock count the cycles the
+/// processor emulates. Outside code drives the gates and external clocks with `set_gate` and
+/// `clock_external`, and sees the outputs with `get_output`. Timers are numbered 1 to 3.
+#[derive(Clone, Default)]
+pub struct Ptm {
+    timers: [Timer; 3],
+    /// Holds the most significant byte written until the least significant one is
+    msb_buffer: u8,
+    /// Holds the least significant byte of the counter read last
+    lsb_buffer: u8,
+    /// The flags set when the status register was read, which reading a counter then clears
+    status_read_flags: u8
+}
+
+impl Ptm {
+    pub fn new() -> Ptm {
+        let mut ptm: Ptm = Ptm::default();
+
+        // Timers start out held in reset
+        ptm.timers[0].control = CONTROL_BIT0;
+        ptm.timers.iter_mut().for_each(Timer::initialize);
+
+        ptm
+    }
+
+    fn get_timer(&self, timer: usize) -> &Timer {
+        assert!((1..=3).contains(&timer), "the PTM has timers 1 to 3");
+        &self.timers[timer - 1]
+    }
+
+    fn get_timer_mut(&mut self, timer: usize) -> &mut Timer {
+        assert!((1..=3).contains(&timer), "the PTM has timers 1 to 3");
+        &mut self.timers[timer - 1]
+    }
+
+    fn in_reset(&self) -> bool {
+        self.timers[0].control & CONTROL_BIT0 != 0
+    }
+
+    pub fn get_status(&self) -> u8 {
+        let mut status: u8 = 0;
+
+        for (index, timer) in self.timers.iter().enumerate() {
+            if timer.flag {
+                status |= 1 << index;
+            }
+        }
+
+        if self.timers.iter().any(Timer::irq) {
+            status |= STATUS_INTERRUPT_REQUEST;
+        }
+
+        status
+    }
+
+    pub fn get_control(&self, timer: usize) -> u8 {
+        self.get_timer(timer).control
+    }
+
+    pub fn get_counter(&self, timer: usize) -> u16 {
+        self.get_timer(timer).counter
+    }
+
+    pub fn get_latch(&self, timer: usize) -> u16 {
+        self.get_timer(timer).latch
+    }
+
+    /// The level of the output of a timer, low while the output is disabled
+    pub fn get_output(&self, timer: usize) -> bool {
+        self.get_timer(timer).get_output()
+    }
+
+    /// Drive the (active low) gate input of a timer
+    pub fn set_gate(&mut self, timer: usize, level: bool) {
+        self.get_timer_mut(timer).set_gate(level);
+    }
+
+    /// Pulse the external clock input of a timer, which only counts while its internal clock is
+    /// not selected
+    pub fn clock_external(&mut self, timer: usize) {
+        if !self.in_reset() && self.get_timer(timer).control & CONTROL_INTERNAL_CLOCK == 0 {
+            self.get_timer_mut(timer).clock();
+        }
+    }
+
+    fn write_latches(&mut self, index: usize, value: u8) {
+        let in_reset: bool = self.in_reset();
+        let timer: &mut Timer = &mut self.timers[index];
+
+        timer.latch = ((self.msb_buffer as u16) << 8) | (value as u16);
+        timer.flag = false;
+
+        if in_reset || (!timer.is_measurement() && timer.control & CONTROL_BIT4 == 0) {
+            timer.initialize();
+        }
+    }
+
+    fn write_control(&mut self, index: usize, value: u8) {
+        let was_in_reset: bool = self.in_reset();
+
+        self.timers[index].control = value;
+
+        // Reset presets every counter from its latches, and holds it there
+        if index == 0 && value & CONTROL_BIT0 != 0 && !was_in_reset {
+            self.timers.iter_mut().for_each(Timer::initialize);
+        } else if self.in_reset() {
+            self.timers[index].initialize();
+        }
+    }
+}
+
+impl device::Device for Ptm {
+    fn name(&self) -> &str {
+        "ptm"
+    }
+
+    fn size(&self) -> u16 {
+        8
+    }
+
+    fn read(&mut self, offset: u16) -> u8 {
+        let index: usize;
+
+        match offset {
+            0 => 0,
+            1 => {
+                self.status_read_flags = self.get_status() & 0x07;
+                self.get_status()
+            },
+            2 | 4 | 6 => {
+                index = (offset as usize - 2) / 2;
+
+                // Reading the counter after the status acknowledges the interrupt
+                if self.status_read_flags & (1 << index) != 0 {
+                    self.timers[index].flag = false;
+                    self.status_read_flags &= !(1 << index);
+                }
+
+                self.lsb_buffer = self.timers[index].counter as u8;
+                (self.timers[index].counter >> 8) as u8
+            },
+            _ => self.lsb_buffer
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            0 => 0,
+            1 => self.get_status(),
+            2 | 4 | 6 => (self.timers[(offset as usize - 2) / 2].counter >> 8) as u8,
+            _ => self.lsb_buffer
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset {
+            0 if self.timers[1].control & CONTROL_BIT0 != 0 => self.write_control(0, value),
+            0 => self.write_control(2, value),
+            1 => self.write_control(1, value),
+            2 | 4 | 6 => self.msb_buffer = value,
+            _ => self.write_latches((offset as usize - 3) / 2, value)
+        }
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        let mut timer: &mut Timer;
+
+        if self.in_reset() {
+            return;
+        }
+
+        for index in 0..self.timers.len() {
+            timer = &mut self.timers[index];
+
+            if timer.control & CONTROL_INTERNAL_CLOCK == 0 {
+                continue;
+            }
+
+            for _ in 0..cycles {
+                // Control register 3 can divide the clock of timer 3
+                if index == 2 && timer.control & CONTROL_BIT0 != 0 {
+                    timer.prescaler += 1;
+
+                    if timer.prescaler < PRESCALER {
+                        continue;
+                    }
+
+                    timer.prescaler = 0;
+                }
+
+                timer.clock();
+            }
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.timers.iter().any(Timer::irq)
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::with_capacity(TIMER_STATE_SIZE * 3 + 3);
+
+        for timer in &self.timers {
+            timer.save(&mut data);
+        }
+
+        data.extend_from_slice(&[self.msb_buffer, self.lsb_buffer, self.status_read_flags]);
+
+        data
+    }
+
+    fn restore(&mut self, data: &[u8]) -> Result<(), save_state::SaveStateError> {
+        if data.len() < TIMER_STATE_SIZE * 3 + 3 {
+            return Err(save_state::SaveStateError::Truncated);
+        }
+
+        for (index, timer) in self.timers.iter_mut().enumerate() {
+            timer.restore(&data[index * TIMER_STATE_SIZE..]);
+        }
+
+        self.msb_buffer = data[TIMER_STATE_SIZE * 3];
+        self.lsb_buffer = data[TIMER_STATE_SIZE * 3 + 1];
+        self.status_read_flags = data[TIMER_STATE_SIZE * 3 + 2];
+
+        Ok(())
+    }
+}
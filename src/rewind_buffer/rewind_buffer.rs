@@ -0,0 +1,96 @@
+use std::collections::VecDeque;
+
+use crate::call_stack;
+use crate::processor;
+
+/// Everything needed to undo a single instruction
+pub struct UndoRecord {
+    pub state: processor::ProcessorState,
+    pub interrupts: processor::InterruptState,
+    pub cycles: u64,
+    /// The overwritten bytes as (address, previous value), in the order they were written
+    pub memory: Vec<(u16, u8)>,
+    /// The shadow call stack before the instruction, only kept for calls, returns and interrupts
+    pub call_stack: Option<Vec<call_stack::Frame>>
+}
+
+/// A bounded log of undo records for the most recently emulated instructions
+///
+/// Once the buffer is full the oldest record is dropped for every new one.
+pub struct RewindBuffer {
+    records: VecDeque<UndoRecord>,
+    capacity: usize,
+    current: Option<UndoRecord>
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> RewindBuffer {
+        RewindBuffer {
+            records: VecDeque::with_capacity(capacity),
+            capacity,
+            current: None
+        }
+    }
+
+    /// Start recording the instruction about to be emulated
+    pub fn begin(&mut self, state: processor::ProcessorState, interrupts: processor::InterruptState, cycles: u64) {
+        self.current = Some(UndoRecord { state, interrupts, cycles, memory: Vec::new(), call_stack: None });
+    }
+
+    /// Remember the previous value of a byte the current instruction overwrites
+    pub fn record_write(&mut self, address: u16, previous_value: u8) {
+        if let Some(record) = self.current.as_mut() {
+            record.memory.push((address, previous_value));
+        }
+    }
+
+    /// Remember the call stack before the current instruction changes it
+    pub fn record_call_stack(&mut self, frames: &[call_stack::Frame]) {
+        if let Some(record) = self.current.as_mut() {
+            if record.call_stack.is_none() {
+                record.call_stack = Some(frames.to_vec());
+            }
+        }
+    }
+
+    /// Finish recording the current instruction
+    pub fn commit(&mut self) {
+        if let Some(record) = self.current.take() {
+            if self.capacity == 0 {
+                return;
+            }
+
+            if self.records.len() == self.capacity {
+                self.records.pop_front();
+            }
+
+            self.records.push_back(record);
+        }
+    }
+
+    /// Drop the current instruction, used when it could not be emulated
+    pub fn abort(&mut self) {
+        self.current = None;
+    }
+
+    pub fn pop(&mut self) -> Option<UndoRecord> {
+        self.records.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.current = None;
+    }
+}
@@ -0,0 +1,196 @@
+use std::fs;
+use std::io;
+
+use crate::memory_manager;
+use crate::processor;
+
+// Every save state file starts with these bytes
+const MAGIC: &[u8; 8] = b"MOMUSAVE";
+
+/// The version written by `SaveState::to_bytes`, bumped whenever the layout changes
+pub const SAVE_STATE_VERSION: u16 = 1;
+
+/// Errors that can arise when restoring a save state
+#[derive(Debug)]
+pub enum SaveStateError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u16),
+    Truncated,
+    /// A device in the save state is not attached to the machine being restored
+    UnknownDevice(String)
+}
+
+impl From<io::Error> for SaveStateError {
+    fn from(error: io::Error) -> Self {
+        SaveStateError::Io(error)
+    }
+}
+
+/// The saved state of a single attached device
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DeviceState {
+    pub name: String,
+    pub data: Vec<u8>
+}
+
+/// A snapshot of a whole machine
+///
+/// The serialized layout (all values big endian) is the magic, the version, the registers
+/// (A, B, X, PC, SP, CCR), the cycle count, the interrupt flags, the full 64K of memory and finally
+/// a count prefixed list of named device states.
+#[derive(Clone)]
+pub struct SaveState {
+    pub processor_state: processor::ProcessorState,
+    pub cycles: u64,
+    pub interrupts: processor::InterruptState,
+    pub memory: Vec<u8>,
+    pub devices: Vec<DeviceState>
+}
+
+// Bits of the interrupt flags byte
+const IRQ_LINE_BIT: u8 = 0;
+const NMI_PENDING_BIT: u8 = 1;
+const WAITING_FOR_INTERRUPT_BIT: u8 = 2;
+const DEVICE_NMI_LINE_BIT: u8 = 3;
+
+/// Reads values from a byte slice, failing with `SaveStateError::Truncated` at its end
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, size: usize) -> Result<&'a [u8], SaveStateError> {
+        let start = self.position;
+
+        if self.data.len() - start < size {
+            return Err(SaveStateError::Truncated);
+        }
+
+        self.position += size;
+
+        Ok(&self.data[start..self.position])
+    }
+
+    fn u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SaveStateError> {
+        let bytes = self.bytes(2)?;
+
+        Ok(((bytes[0] as u16) << 8) | (bytes[1] as u16))
+    }
+
+    fn u32(&mut self) -> Result<u32, SaveStateError> {
+        let bytes = self.bytes(4)?;
+
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(((self.u32()? as u64) << 32) | (self.u32()? as u64))
+    }
+}
+
+impl SaveState {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(memory_manager::BUFFER_SIZE + 64);
+        let state = &self.processor_state;
+        let mut interrupt_flags: u8 = 0;
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&SAVE_STATE_VERSION.to_be_bytes());
+
+        bytes.push(state.accumulator_a);
+        bytes.push(state.accumulator_b);
+        bytes.extend_from_slice(&state.index_register.to_be_bytes());
+        bytes.extend_from_slice(&state.program_counter.to_be_bytes());
+        bytes.extend_from_slice(&state.stack_pointer.to_be_bytes());
+        bytes.push(state.condition_code_register);
+
+        bytes.extend_from_slice(&self.cycles.to_be_bytes());
+
+        interrupt_flags |= (self.interrupts.irq_line as u8) << IRQ_LINE_BIT;
+        interrupt_flags |= (self.interrupts.nmi_pending as u8) << NMI_PENDING_BIT;
+        interrupt_flags |= (self.interrupts.waiting_for_interrupt as u8) << WAITING_FOR_INTERRUPT_BIT;
+        interrupt_flags |= (self.interrupts.device_nmi_line as u8) << DEVICE_NMI_LINE_BIT;
+        bytes.push(interrupt_flags);
+
+        bytes.extend_from_slice(&self.memory);
+
+        bytes.extend_from_slice(&(self.devices.len() as u16).to_be_bytes());
+        for device in &self.devices {
+            bytes.extend_from_slice(&(device.name.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(device.name.as_bytes());
+            bytes.extend_from_slice(&(device.data.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(&device.data);
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SaveState, SaveStateError> {
+        let mut reader = Reader { data: bytes, position: 0 };
+        let mut processor_state = processor::ProcessorState::new_empty();
+        let mut devices: Vec<DeviceState> = Vec::new();
+        let version: u16;
+        let cycles: u64;
+        let interrupt_flags: u8;
+        let memory: Vec<u8>;
+        let device_count: u16;
+        let mut name_length: usize;
+        let mut data_length: usize;
+        let mut name: String;
+
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(SaveStateError::BadMagic);
+        }
+
+        version = reader.u16()?;
+        if version != SAVE_STATE_VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+
+        processor_state.accumulator_a = reader.u8()?;
+        processor_state.accumulator_b = reader.u8()?;
+        processor_state.index_register = reader.u16()?;
+        processor_state.program_counter = reader.u16()?;
+        processor_state.stack_pointer = reader.u16()?;
+        processor_state.condition_code_register = reader.u8()?;
+
+        cycles = reader.u64()?;
+        interrupt_flags = reader.u8()?;
+        memory = reader.bytes(memory_manager::BUFFER_SIZE)?.to_vec();
+
+        device_count = reader.u16()?;
+        for _ in 0..device_count {
+            name_length = reader.u16()? as usize;
+            name = String::from_utf8_lossy(reader.bytes(name_length)?).into_owned();
+            data_length = reader.u32()? as usize;
+            devices.push(DeviceState { name, data: reader.bytes(data_length)?.to_vec() });
+        }
+
+        Ok(SaveState {
+            processor_state,
+            cycles,
+            interrupts: processor::InterruptState {
+                irq_line: crate::get_bit!(interrupt_flags, IRQ_LINE_BIT),
+                nmi_pending: crate::get_bit!(interrupt_flags, NMI_PENDING_BIT),
+                waiting_for_interrupt: crate::get_bit!(interrupt_flags, WAITING_FOR_INTERRUPT_BIT),
+                device_nmi_line: crate::get_bit!(interrupt_flags, DEVICE_NMI_LINE_BIT)
+            },
+            memory,
+            devices
+        })
+    }
+
+    pub fn write_to_file(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn read_from_file(path: &str) -> Result<SaveState, SaveStateError> {
+        SaveState::from_bytes(&fs::read(path)?)
+    }
+}
@@ -0,0 +1,224 @@
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+
+use crate::device;
+use crate::save_state;
+use crate::serial;
+
+// Register offsets
+const DATA_REGISTER: u16 = 0;
+const STATUS_REGISTER: u16 = 1;
+const HANDLE_REGISTER: u16 = 2;
+const NAME_REGISTER: u16 = 3;
+const COMMAND_REGISTER: u16 = 4;
+const EXIT_REGISTER: u16 = 5;
+
+// Status register bits, set by the last data access or command
+pub const STATUS_END_OF_FILE: u8 = 0x80;
+pub const STATUS_ERROR: u8 = 0x40;
+
+// Commands
+pub const COMMAND_OPEN_READ: u8 = 1;
+pub const COMMAND_OPEN_WRITE: u8 = 2;
+pub const COMMAND_OPEN_APPEND: u8 = 3;
+pub const COMMAND_CLOSE: u8 = 4;
+
+// The handles always open: the console (stdin and stdout) and stderr. Opened files get the
+// handles after them.
+pub const CONSOLE_HANDLE: u8 = 0;
+pub const ERROR_HANDLE: u8 = 1;
+const FIRST_FILE_HANDLE: u8 = 2;
+
+/// A device letting programs use the host: print, read input, work on files and exit with a
+/// status code
+///
+/// The registers are, from its address:
+///
+/// - DATA: reading takes the next byte of the selected handle, writing outputs a byte to it
+/// - STATUS: `STATUS_END_OF_FILE` when a read found no more data, `STATUS_ERROR` when the last
+///   access or command failed
+/// - HANDLE: the handle DATA works on, `CONSOLE_HANDLE` by default. Opening a file selects it.
+/// - NAME: the file name is written here a byte at a time before opening it
+/// - COMMAND: one of the `COMMAND_*` constants, opening the file named or closing the handle
+/// - EXIT: writing a status stops the run with `processor::StopReason::Exited`
+///
+/// Reading the console blocks until input is typed or stdin ends. File names are relative to
+/// the working directory of the emulator, which gives programs access to any file it can open.
+pub struct Semihosting {
+    input: Box<dyn io::Read + Send>,
+    output: Box<dyn io::Write + Send>,
+    /// The files opened, a handle of `FIRST_FILE_HANDLE + n` for the nth
+    files: Vec<Option<fs::File>>,
+    name: Vec<u8>,
+    handle: u8,
+    status: u8,
+    data: u8,
+    exit_code: Option<u8>
+}
+
+impl Default for Semihosting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Semihosting {
+    /// A device with stdin and stdout as the console
+    pub fn new() -> Semihosting {
+        Semihosting::with_console(Box::new(serial::StdinReader), Box::new(io::stdout()))
+    }
+
+    pub fn with_console(input: Box<dyn io::Read + Send>, output: Box<dyn io::Write + Send>) -> Semihosting {
+        Semihosting { input, output, files: Vec::new(), name: Vec::new(), handle: CONSOLE_HANDLE, status: 0, data: 0, exit_code: None }
+    }
+
+    pub fn get_status(&self) -> u8 {
+        self.status
+    }
+
+    /// The status the program exited with, if it did and the run has not reported it yet
+    pub fn get_exit_code(&self) -> Option<u8> {
+        self.exit_code
+    }
+
+    fn get_file(&mut self) -> Option<&mut fs::File> {
+        let index: usize = self.handle.checked_sub(FIRST_FILE_HANDLE)? as usize;
+
+        self.files.get_mut(index)?.as_mut()
+    }
+
+    fn read_data(&mut self) -> u8 {
+        let mut byte: [u8; 1] = [0];
+        let result: io::Result<usize> = match self.handle {
+            CONSOLE_HANDLE => self.input.read(&mut byte),
+            _ => match self.get_file() {
+                Some(file) => file.read(&mut byte),
+                None => Err(io::Error::from(io::ErrorKind::InvalidInput))
+            }
+        };
+
+        self.status = match result {
+            Ok(0) => STATUS_END_OF_FILE,
+            Ok(_) => 0,
+            Err(_) => STATUS_ERROR
+        };
+        self.data = if self.status == 0 { byte[0] } else { 0 };
+
+        self.data
+    }
+
+    fn write_data(&mut self, value: u8) {
+        let result: io::Result<()> = match self.handle {
+            CONSOLE_HANDLE => self.output.write_all(&[value]).and_then(|_| self.output.flush()),
+            ERROR_HANDLE => io::stderr().write_all(&[value]),
+            _ => match self.get_file() {
+                Some(file) => file.write_all(&[value]),
+                None => Err(io::Error::from(io::ErrorKind::InvalidInput))
+            }
+        };
+
+        self.data = value;
+        self.status = if result.is_ok() { 0 } else { STATUS_ERROR };
+    }
+
+    fn open(&mut self, options: &fs::OpenOptions) -> io::Result<()> {
+        let path: String = String::from_utf8_lossy(&self.name).into_owned();
+        let file: fs::File = options.open(path)?;
+        let index: usize = match self.files.iter().position(|file| file.is_none()) {
+            Some(index) => index,
+            None if self.files.len() < (u8::MAX - FIRST_FILE_HANDLE) as usize => {
+                self.files.push(None);
+                self.files.len() - 1
+            },
+            None => return Err(io::Error::other("too many open files"))
+        };
+
+        self.files[index] = Some(file);
+        self.handle = FIRST_FILE_HANDLE + index as u8;
+
+        Ok(())
+    }
+
+    fn run_command(&mut self, command: u8) {
+        let result: io::Result<()> = match command {
+            COMMAND_OPEN_READ => self.open(fs::OpenOptions::new().read(true)),
+            COMMAND_OPEN_WRITE => self.open(fs::OpenOptions::new().write(true).create(true).truncate(true)),
+            COMMAND_OPEN_APPEND => self.open(fs::OpenOptions::new().append(true).create(true)),
+            COMMAND_CLOSE => match self.handle.checked_sub(FIRST_FILE_HANDLE).and_then(|index| self.files.get_mut(index as usize)) {
+                Some(file) if file.is_some() => {
+                    *file = None;
+                    self.handle = CONSOLE_HANDLE;
+                    Ok(())
+                },
+                _ => Err(io::Error::from(io::ErrorKind::InvalidInput))
+            },
+            _ => Err(io::Error::from(io::ErrorKind::InvalidInput))
+        };
+
+        self.name.clear();
+        self.status = if result.is_ok() { 0 } else { STATUS_ERROR };
+    }
+}
+
+impl device::Device for Semihosting {
+    fn name(&self) -> &str {
+        "semihosting"
+    }
+
+    fn size(&self) -> u16 {
+        EXIT_REGISTER + 1
+    }
+
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset {
+            DATA_REGISTER => self.read_data(),
+            _ => self.peek(offset)
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            DATA_REGISTER => self.data,
+            STATUS_REGISTER => self.status,
+            HANDLE_REGISTER => self.handle,
+            EXIT_REGISTER => self.exit_code.unwrap_or(0),
+            _ => 0
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset {
+            DATA_REGISTER => self.write_data(value),
+            HANDLE_REGISTER => self.handle = value,
+            NAME_REGISTER => self.name.push(value),
+            COMMAND_REGISTER => self.run_command(value),
+            EXIT_REGISTER => self.exit_code = Some(value),
+            _ => {}
+        }
+    }
+
+    fn take_exit_code(&mut self) -> Option<u8> {
+        self.exit_code.take()
+    }
+
+    /// The open files are not part of the state, they are closed by restoring it
+    fn save(&self) -> Vec<u8> {
+        vec![self.data, self.status, self.handle, self.exit_code.is_some() as u8, self.exit_code.unwrap_or(0)]
+    }
+
+    fn restore(&mut self, data: &[u8]) -> Result<(), save_state::SaveStateError> {
+        if data.len() < 5 {
+            return Err(save_state::SaveStateError::Truncated);
+        }
+
+        self.data = data[0];
+        self.status = data[1];
+        self.handle = data[2];
+        self.exit_code = if data[3] != 0 { Some(data[4]) } else { None };
+        self.files.clear();
+        self.name.clear();
+
+        Ok(())
+    }
+}
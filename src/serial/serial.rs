@@ -0,0 +1,261 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::thread;
+
+/// The other end of a serial device, e.g. a terminal
+pub trait SerialPort: Send {
+    /// The next byte sent to the device, if one is waiting. Must not block.
+    fn receive(&mut self) -> Option<u8>;
+
+    /// Accept a byte sent by the device
+    fn transmit(&mut self, byte: u8);
+}
+
+#[derive(Default)]
+struct Buffers {
+    input: VecDeque<u8>,
+    output: Vec<u8>
+}
+
+/// A port backed by memory, mostly for tests
+///
+/// Clones share the same buffers, so a clone kept by the caller can feed input to and collect the
+/// output of a device which owns the other one.
+#[derive(Clone, Default)]
+pub struct BufferPort {
+    buffers: Arc<Mutex<Buffers>>
+}
+
+impl BufferPort {
+    pub fn new() -> BufferPort {
+        BufferPort::default()
+    }
+
+    /// Queue bytes for the device to receive
+    pub fn push_input(&self, data: &[u8]) {
+        self.buffers.lock().unwrap().input.extend(data);
+    }
+
+    /// The bytes queued which the device did not receive yet
+    pub fn get_pending_input(&self) -> usize {
+        self.buffers.lock().unwrap().input.len()
+    }
+
+    /// Take the bytes the device transmitted so far
+    pub fn take_output(&self) -> Vec<u8> {
+        std::mem::take(&mut self.buffers.lock().unwrap().output)
+    }
+}
+
+impl SerialPort for BufferPort {
+    fn receive(&mut self) -> Option<u8> {
+        self.buffers.lock().unwrap().input.pop_front()
+    }
+
+    fn transmit(&mut self, byte: u8) {
+        self.buffers.lock().unwrap().output.push(byte);
+    }
+}
+
+/// The bytes read from stdin by a background thread, shared by every `StdioPort`
+fn get_stdin_receiver() -> &'static Mutex<mpsc::Receiver<u8>> {
+    static RECEIVER: OnceLock<Mutex<mpsc::Receiver<u8>>> = OnceLock::new();
+
+    RECEIVER.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut byte: [u8; 1] = [0];
+
+            while let Ok(1) = io::stdin().read(&mut byte) {
+                if sender.send(byte[0]).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Mutex::new(receiver)
+    })
+}
+
+/// Reads stdin through the thread shared with `StdioPort`, blocking until a byte is typed. Reads
+/// return 0 bytes at the end of stdin.
+#[derive(Clone, Copy, Default)]
+pub struct StdinReader;
+
+impl Read for StdinReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        match get_stdin_receiver().lock().unwrap().recv() {
+            Ok(byte) => {
+                buffer[0] = byte;
+                Ok(1)
+            },
+            Err(_) => Ok(0)
+        }
+    }
+}
+
+// The signals sent for Ctrl-C and when the terminal is resized, the same numbers on Linux and
+// the BSDs, and the handler restoring the default action
+const SIGINT: i32 = 2;
+const SIGWINCH: i32 = 28;
+const SIG_DFL: usize = 0;
+
+extern "C" {
+    fn signal(signal_number: i32, handler: usize) -> usize;
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_interrupt(_signal_number: i32) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn handle_resize(_signal_number: i32) {
+    RESIZED.store(true, Ordering::SeqCst);
+}
+
+/// Whether Ctrl-C was pressed since the last call, while a `CharacterMode` was enabled
+pub fn take_interrupted() -> bool {
+    INTERRUPTED.swap(false, Ordering::SeqCst)
+}
+
+/// Start watching for the terminal being resized, reported by `take_resized`
+pub fn watch_resize() {
+    // The handler only stores to an atomic, which is safe to do from a signal handler
+    unsafe {
+        signal(SIGWINCH, handle_resize as extern "C" fn(i32) as usize);
+    }
+}
+
+/// Whether the terminal was resized since the last call, once `watch_resize` was called
+pub fn take_resized() -> bool {
+    RESIZED.swap(false, Ordering::SeqCst)
+}
+
+/// Run `stty` on the terminal connected to stdin
+pub fn stty(arguments: &[&str]) -> io::Result<String> {
+    let output = process::Command::new("stty").args(arguments).stdin(process::Stdio::inherit()).output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Keeps the terminal in character mode, without line editing or echo, until dropped
+///
+/// Ctrl-C does not kill the process meanwhile, so the terminal is always given back. It is
+/// reported by `take_interrupted` instead, for the program to stop on its own.
+pub struct CharacterMode {
+    saved_settings: String
+}
+
+impl CharacterMode {
+    /// Switch the terminal connected to stdin to character mode. Fails if stdin is not a terminal.
+    pub fn enable() -> io::Result<CharacterMode> {
+        CharacterMode::with_settings(&["-icanon", "-echo", "min", "1"])
+    }
+
+    /// Apply other `stty` settings, e.g. raw mode, which are undone the same way when dropped
+    pub fn with_settings(settings: &[&str]) -> io::Result<CharacterMode> {
+        let saved_settings: String = stty(&["-g"])?;
+
+        stty(settings)?;
+
+        // The handler only stores to an atomic, which is safe to do from a signal handler
+        unsafe {
+            signal(SIGINT, handle_interrupt as extern "C" fn(i32) as usize);
+        }
+
+        Ok(CharacterMode { saved_settings })
+    }
+}
+
+impl Drop for CharacterMode {
+    fn drop(&mut self) {
+        unsafe {
+            signal(SIGINT, SIG_DFL);
+        }
+
+        let _ = stty(&[&self.saved_settings]);
+    }
+}
+
+/// A port connected to the terminal: input is read from stdin without blocking the emulation and
+/// output is written to stdout
+#[derive(Clone, Copy, Default)]
+pub struct StdioPort {
+    /// Turn the line feeds typed on the host into carriage returns, which most monitors expect
+    pub translate_newlines: bool
+}
+
+impl StdioPort {
+    pub fn new() -> StdioPort {
+        StdioPort::default()
+    }
+}
+
+impl SerialPort for StdioPort {
+    fn receive(&mut self) -> Option<u8> {
+        match get_stdin_receiver().lock().unwrap().try_recv().ok()? {
+            b'\n' if self.translate_newlines => Some(b'\r'),
+            byte => Some(byte)
+        }
+    }
+
+    fn transmit(&mut self, byte: u8) {
+        let mut stdout = io::stdout();
+
+        // The terminal going away must not stop the emulation
+        let _ = stdout.write_all(&[byte]);
+        let _ = stdout.flush();
+    }
+}
+
+/// A port reading its input from one file and writing its output to another
+pub struct FilePort {
+    input: VecDeque<u8>,
+    output: Option<fs::File>
+}
+
+impl FilePort {
+    /// Open the files, either of which may be left out. The output file is truncated.
+    pub fn new(input_path: Option<&str>, output_path: Option<&str>) -> io::Result<FilePort> {
+        let mut input: VecDeque<u8> = VecDeque::new();
+        let mut output: Option<fs::File> = None;
+
+        if let Some(path) = input_path {
+            input = fs::read(path)?.into();
+        }
+
+        if let Some(path) = output_path {
+            output = Some(fs::File::create(path)?);
+        }
+
+        Ok(FilePort { input, output })
+    }
+}
+
+impl SerialPort for FilePort {
+    fn receive(&mut self) -> Option<u8> {
+        self.input.pop_front()
+    }
+
+    fn transmit(&mut self, byte: u8) {
+        if let Some(file) = self.output.as_mut() {
+            let _ = file.write_all(&[byte]);
+        }
+    }
+}
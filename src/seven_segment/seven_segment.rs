@@ -0,0 +1,51 @@
+// The segments of a digit, as bitmasks of its segment byte:
+//  a
+// f b
+//  g
+// e c
+//  d  dp
+pub const SEGMENT_A: u8 = 0x01;
+pub const SEGMENT_B: u8 = 0x02;
+pub const SEGMENT_C: u8 = 0x04;
+pub const SEGMENT_D: u8 = 0x08;
+pub const SEGMENT_E: u8 = 0x10;
+pub const SEGMENT_F: u8 = 0x20;
+pub const SEGMENT_G: u8 = 0x40;
+pub const DECIMAL_POINT: u8 = 0x80;
+
+// The segments showing the hexadecimal digits, with b and d in lower case as on most trainers
+const HEX_DIGITS: [u8; 16] = [0x3F, 0x06, 0x5B, 0x4F, 0x66, 0x6D, 0x7D, 0x07, 0x7F, 0x6F, 0x77, 0x7C, 0x39, 0x5E, 0x79, 0x71];
+
+/// The segments showing a hexadecimal digit (only the low 4 bits are used)
+pub fn encode_hex(value: u8) -> u8 {
+    HEX_DIGITS[(value & 0x0F) as usize]
+}
+
+/// The hexadecimal digit shown by some segments, if they show one. The decimal point is ignored.
+pub fn decode_hex(segments: u8) -> Option<u8> {
+    HEX_DIGITS.iter().position(|digit| *digit == segments & !DECIMAL_POINT).map(|value| value as u8)
+}
+
+/// Draw a row of digits as three lines of ASCII art, e.g. ` _ ` / `|_|` / `|_|.` for 8.
+pub fn render(digits: &[u8]) -> String {
+    let mut lines: [String; 3] = [String::new(), String::new(), String::new()];
+    let segment = |segments: u8, mask: u8, character: char| if segments & mask != 0 { character } else { ' ' };
+
+    for segments in digits.iter().copied() {
+        lines[0].push(' ');
+        lines[0].push(segment(segments, SEGMENT_A, '_'));
+        lines[0].push_str("  ");
+
+        lines[1].push(segment(segments, SEGMENT_F, '|'));
+        lines[1].push(segment(segments, SEGMENT_G, '_'));
+        lines[1].push(segment(segments, SEGMENT_B, '|'));
+        lines[1].push(' ');
+
+        lines[2].push(segment(segments, SEGMENT_E, '|'));
+        lines[2].push(segment(segments, SEGMENT_D, '_'));
+        lines[2].push(segment(segments, SEGMENT_C, '|'));
+        lines[2].push(segment(segments, DECIMAL_POINT, '.'));
+    }
+
+    lines.iter().map(|line| line.trim_end()).collect::<Vec<&str>>().join("\n")
+}
@@ -0,0 +1,39 @@
+use crate::acia;
+use crate::machine;
+use crate::profile;
+use crate::serial;
+
+// Where the MP-A processor board decodes the monitor ROM, and the most it decodes
+pub const ROM_ADDRESS: u16 = 0xE000;
+pub const MAXIMUM_ROM_SIZE: usize = 0x2000;
+
+// The processor clock, derived from the baud rate crystal
+pub const CLOCK_RATE: u64 = 898_000;
+
+// The SS-50 bus has eight I/O slots of four addresses from $8000
+pub const IO_ADDRESS: u16 = 0x8000;
+pub const IO_SLOT_SIZE: u16 = 4;
+
+// SWTBUG talks to the MP-S serial card in I/O slot 1. MIKBUG bit-bangs an MP-C card (a PIA)
+// there instead, which is not emulated.
+pub const CONSOLE_SLOT: u16 = 1;
+pub const ACIA_ADDRESS: u16 = IO_ADDRESS + CONSOLE_SLOT * IO_SLOT_SIZE;
+
+/// Build an SWTPC 6800 with RAM everywhere else, a monitor ROM for the MP-S such as SWTBUG at
+/// $E000 (and mirrored over the vectors), and an MP-S serial card connected to `port` in I/O
+/// slot 1.
+///
+/// The processor is reset, so running the machine boots the monitor. The MP-S decodes all four
+/// addresses of its slot, so the ACIA registers repeat at $8006.
+pub fn build(rom: &[u8], port: Box<dyn serial::SerialPort>) -> Result<machine::Machine, profile::ProfileError> {
+    let mut machine = machine::Machine::new();
+
+    profile::install_monitor_rom(&mut machine, ROM_ADDRESS, rom, MAXIMUM_ROM_SIZE)?;
+
+    machine.get_memory_manager_mut().attach_device(ACIA_ADDRESS, Box::new(acia::Acia::new(port)));
+    machine.get_memory_manager_mut().add_mirror(ACIA_ADDRESS + 2, 2, ACIA_ADDRESS);
+    machine.get_processor_mut().reset();
+    machine.set_clock_rate(Some(CLOCK_RATE));
+
+    Ok(machine)
+}
@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+/// Maps symbol names to addresses and back
+#[derive(Default)]
+pub struct SymbolTable {
+    addresses: HashMap<String, u16>,
+    names: BTreeMap<u16, String>
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    /// Parse a symbol listing with a symbol name followed by its hexadecimal address on every
+    /// line, like the cross reference printed by `as0 -l cre`. Lines that do not look like that are
+    /// ignored, so a whole assembler listing can be given.
+    pub fn parse(text: &str) -> SymbolTable {
+        let mut table = SymbolTable::new();
+        let mut tokens: std::str::SplitWhitespace;
+
+        for line in text.lines() {
+            tokens = line.split_whitespace();
+
+            if let (Some(name), Some(address)) = (tokens.next(), tokens.next()) {
+                if !is_symbol_name(name) || address.len() != 4 {
+                    continue;
+                }
+
+                if let Ok(address) = u16::from_str_radix(address, 16) {
+                    table.insert(name, address);
+                }
+            }
+        }
+
+        table
+    }
+
+    pub fn load_file(path: &str) -> io::Result<SymbolTable> {
+        Ok(SymbolTable::parse(&fs::read_to_string(path)?))
+    }
+
+    pub fn insert(&mut self, name: &str, address: u16) {
+        self.addresses.insert(String::from(name), address);
+        self.names.entry(address).or_insert_with(|| String::from(name));
+    }
+
+    pub fn get_address(&self, name: &str) -> Option<u16> {
+        self.addresses.get(name).copied()
+    }
+
+    /// Get the name of the symbol defined exactly at an address
+    pub fn get_name(&self, address: u16) -> Option<&str> {
+        self.names.get(&address).map(|name| name.as_str())
+    }
+
+    /// Find the closest symbol at or before an address, with the distance from it
+    pub fn get_nearest(&self, address: u16) -> Option<(&str, u16)> {
+        self.names.range(..=address).next_back().map(|(symbol_address, name)| (name.as_str(), address - symbol_address))
+    }
+
+    /// Format an address as `symbol+offset` when a symbol is known, or as hexadecimal otherwise
+    pub fn format_address(&self, address: u16) -> String {
+        match self.get_nearest(address) {
+            Some((name, 0)) => format!("{} (${:04X})", name, address),
+            Some((name, offset)) => format!("{}+{} (${:04X})", name, offset, address),
+            None => format!("${:04X}", address)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+}
+
+fn is_symbol_name(text: &str) -> bool {
+    let mut characters = text.chars();
+
+    match characters.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' || first == '.' => {},
+        _ => return false
+    }
+
+    characters.all(|character| character.is_ascii_alphanumeric() || character == '_' || character == '.')
+}
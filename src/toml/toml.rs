@@ -0,0 +1,348 @@
+/// A TOML document, such as a machine description
+///
+/// Only the subset configuration files need is read: tables, arrays of tables, `key = value`
+/// pairs with bare or quoted keys, strings, integers (also hexadecimal, octal and binary),
+/// booleans, and arrays of these written on one line.
+#[derive(Clone, PartialEq, Debug)]
+pub enum TomlValue {
+    Bool(bool),
+    Integer(i64),
+    String(String),
+    Array(Vec<TomlValue>),
+    /// Keys in the order they appeared
+    Table(Vec<(String, TomlValue)>)
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    position: usize
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.position < self.text.len() && (self.text[self.position] == b' ' || self.text[self.position] == b'\t') {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.position).copied()
+    }
+
+    /// Whether only whitespace and a comment are left
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+
+        matches!(self.peek(), None | Some(b'#'))
+    }
+
+    fn parse_key(&mut self) -> Result<String, String> {
+        let start: usize;
+
+        self.skip_whitespace();
+
+        if let Some(b'"') | Some(b'\'') = self.peek() {
+            return self.parse_string();
+        }
+
+        start = self.position;
+
+        while let Some(b'A'..=b'Z') | Some(b'a'..=b'z') | Some(b'0'..=b'9') | Some(b'_') | Some(b'-') = self.peek() {
+            self.position += 1;
+        }
+
+        if start == self.position {
+            return Err(String::from("Expected a key"));
+        }
+
+        Ok(String::from_utf8_lossy(&self.text[start..self.position]).into_owned())
+    }
+
+    /// A table header path, e.g. `a.b` in `[a.b]`
+    fn parse_path(&mut self) -> Result<Vec<String>, String> {
+        let mut path: Vec<String> = vec![self.parse_key()?];
+
+        loop {
+            self.skip_whitespace();
+
+            if self.peek() != Some(b'.') {
+                return Ok(path);
+            }
+
+            self.position += 1;
+            path.push(self.parse_key()?);
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        let quote: u8 = self.text[self.position];
+        let mut bytes: Vec<u8> = Vec::new();
+
+        self.position += 1;
+
+        loop {
+            match self.peek() {
+                None => return Err(String::from("Unterminated string")),
+                Some(byte) if byte == quote => {
+                    self.position += 1;
+                    return Ok(String::from_utf8_lossy(&bytes).into_owned());
+                },
+                // Literal strings have no escapes
+                Some(b'\\') if quote == b'"' => {
+                    self.position += 1;
+                    bytes.push(match self.peek() {
+                        Some(b'"') => b'"',
+                        Some(b'\\') => b'\\',
+                        Some(b'n') => b'\n',
+                        Some(b't') => b'\t',
+                        Some(b'r') => b'\r',
+                        _ => return Err(String::from("Unsupported escape in string"))
+                    });
+                    self.position += 1;
+                },
+                Some(byte) => {
+                    bytes.push(byte);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    fn parse_integer(&mut self) -> Result<i64, String> {
+        let start: usize = self.position;
+        let text: String;
+        let negative: bool;
+        let digits: &str;
+
+        while let Some(byte) = self.peek() {
+            if !(byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'+' || byte == b'-') {
+                break;
+            }
+
+            self.position += 1;
+        }
+
+        text = String::from_utf8_lossy(&self.text[start..self.position]).replace('_', "");
+        negative = text.starts_with('-');
+        digits = text.trim_start_matches(['+', '-']);
+
+        let value = if let Some(hex) = digits.strip_prefix("0x") {
+            i64::from_str_radix(hex, 16)
+        } else if let Some(octal) = digits.strip_prefix("0o") {
+            i64::from_str_radix(octal, 8)
+        } else if let Some(binary) = digits.strip_prefix("0b") {
+            i64::from_str_radix(binary, 2)
+        } else {
+            digits.parse()
+        };
+
+        match value {
+            Ok(value) if negative => Ok(-value),
+            Ok(value) => Ok(value),
+            Err(_) => Err(format!("Invalid value '{}'", text))
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Vec<TomlValue>, String> {
+        let mut values: Vec<TomlValue> = Vec::new();
+
+        self.position += 1;
+
+        loop {
+            self.skip_whitespace();
+
+            if self.peek() == Some(b']') {
+                self.position += 1;
+                return Ok(values);
+            }
+
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {},
+                _ => return Err(String::from("Expected ',' or ']' (arrays must be on one line)"))
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<TomlValue, String> {
+        let rest: &[u8];
+
+        self.skip_whitespace();
+        rest = &self.text[self.position..];
+
+        match self.peek() {
+            Some(b'"') | Some(b'\'') => Ok(TomlValue::String(self.parse_string()?)),
+            Some(b'[') => Ok(TomlValue::Array(self.parse_array()?)),
+            Some(b'{') => Err(String::from("Inline tables are not supported")),
+            _ if rest.starts_with(b"true") => {
+                self.position += 4;
+                Ok(TomlValue::Bool(true))
+            },
+            _ if rest.starts_with(b"false") => {
+                self.position += 5;
+                Ok(TomlValue::Bool(false))
+            },
+            Some(_) => Ok(TomlValue::Integer(self.parse_integer()?)),
+            None => Err(String::from("Expected a value"))
+        }
+    }
+}
+
+/// Walk down a path of tables from a table, creating the missing ones. Arrays of tables stand
+/// for their last table, as in TOML.
+fn get_table_mut<'a>(mut table: &'a mut Vec<(String, TomlValue)>, path: &[String]) -> Result<&'a mut Vec<(String, TomlValue)>, String> {
+    let mut index: usize;
+
+    for key in path {
+        index = match table.iter().position(|(name, _)| name == key) {
+            Some(index) => index,
+            None => {
+                table.push((key.clone(), TomlValue::Table(Vec::new())));
+                table.len() - 1
+            }
+        };
+
+        table = match &mut table[index].1 {
+            TomlValue::Table(child) => child,
+            TomlValue::Array(values) => match values.last_mut() {
+                Some(TomlValue::Table(child)) => child,
+                _ => return Err(format!("'{}' is not a table", key))
+            },
+            _ => return Err(format!("'{}' is not a table", key))
+        };
+    }
+
+    Ok(table)
+}
+
+/// Apply one line of a document to the root table, where `path` is the table of the last header
+fn parse_line(root: &mut Vec<(String, TomlValue)>, path: &mut Vec<String>, line: &str) -> Result<(), String> {
+    let mut parser = Parser { text: line.as_bytes(), position: 0 };
+    let table: &mut Vec<(String, TomlValue)>;
+    let key: String;
+    let value: TomlValue;
+    let array_key: String;
+
+    if parser.at_end() {
+        return Ok(());
+    }
+
+    if line.trim_start().starts_with("[[") {
+        parser.position = line.find("[[").unwrap() + 2;
+        *path = parser.parse_path()?;
+
+        if !line[parser.position..].trim_start().starts_with("]]") {
+            return Err(String::from("Expected ']]'"));
+        }
+
+        parser.position = line.rfind("]]").unwrap() + 2;
+        array_key = path.pop().unwrap();
+        table = get_table_mut(root, path)?;
+        path.push(array_key.clone());
+
+        match table.iter_mut().find(|(name, _)| *name == array_key) {
+            Some((_, TomlValue::Array(values))) => values.push(TomlValue::Table(Vec::new())),
+            Some(_) => return Err(format!("'{}' is not an array of tables", array_key)),
+            None => table.push((array_key, TomlValue::Array(vec![TomlValue::Table(Vec::new())])))
+        }
+    } else if parser.peek() == Some(b'[') {
+        parser.position += 1;
+        *path = parser.parse_path()?;
+        parser.skip_whitespace();
+
+        if parser.peek() != Some(b']') {
+            return Err(String::from("Expected ']'"));
+        }
+
+        parser.position += 1;
+        get_table_mut(root, path)?;
+    } else {
+        key = parser.parse_key()?;
+        parser.skip_whitespace();
+
+        if parser.peek() != Some(b'=') {
+            return Err(String::from("Expected '=' (dotted keys are not supported)"));
+        }
+
+        parser.position += 1;
+        value = parser.parse_value()?;
+        table = get_table_mut(root, path)?;
+
+        if table.iter().any(|(name, _)| *name == key) {
+            return Err(format!("Duplicate key '{}'", key));
+        }
+
+        table.push((key, value));
+    }
+
+    if !parser.at_end() {
+        return Err(String::from("Unexpected text after the value"));
+    }
+
+    Ok(())
+}
+
+impl TomlValue {
+    /// Parse a document into its root table
+    pub fn parse(text: &str) -> Result<TomlValue, String> {
+        let mut root: Vec<(String, TomlValue)> = Vec::new();
+        let mut path: Vec<String> = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            if let Err(message) = parse_line(&mut root, &mut path, line) {
+                return Err(format!("{} on line {}", message, index + 1));
+            }
+        }
+
+        Ok(TomlValue::Table(root))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&TomlValue> {
+        match self {
+            TomlValue::Table(entries) => entries.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TomlValue::String(text) => Some(text),
+            _ => None
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            TomlValue::Integer(value) => Some(*value),
+            _ => None
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_i64().filter(|value| *value >= 0).map(|value| value as u64)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            TomlValue::Bool(value) => Some(*value),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[TomlValue]> {
+        match self {
+            TomlValue::Array(values) => Some(values),
+            _ => None
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&[(String, TomlValue)]> {
+        match self {
+            TomlValue::Table(entries) => Some(entries),
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+
+use crate::disassembler;
+use crate::processor;
+
+// Binary traces start with these bytes followed by a big endian version
+const BINARY_MAGIC: &[u8; 8] = b"MOMUTRCE";
+const BINARY_VERSION: u16 = 1;
+
+// The size of a single record in a binary trace
+const BINARY_RECORD_SIZE: usize = 21;
+
+/// The on-disk representation of a trace
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TraceFormat {
+    /// One human readable (and diffable) line per instruction
+    Text,
+    /// Fixed size records, for very long runs
+    Binary
+}
+
+/// The state of the processor right before an instruction was emulated
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TraceRecord {
+    pub program_counter: u16,
+    /// The raw bytes of the instruction
+    pub bytes: Vec<u8>,
+    pub accumulator_a: u8,
+    pub accumulator_b: u8,
+    pub index_register: u16,
+    pub stack_pointer: u16,
+    pub condition_code_register: u8,
+    pub cycles: u64
+}
+
+/// The first record at which two traces differ. A missing record means that trace ended first.
+#[derive(PartialEq, Eq, Debug)]
+pub struct TraceDivergence {
+    pub index: usize,
+    pub left: Option<TraceRecord>,
+    pub right: Option<TraceRecord>
+}
+
+/// Writes a trace record for every instruction the processor emulates
+pub struct Tracer {
+    format: TraceFormat,
+    writer: Box<dyn Write + Send>,
+    error: Option<io::Error>
+}
+
+impl TraceRecord {
+    pub fn new(state: &processor::ProcessorState, bytes: &[u8], cycles: u64) -> TraceRecord {
+        TraceRecord {
+            program_counter: state.program_counter,
+            bytes: bytes.to_vec(),
+            accumulator_a: state.accumulator_a,
+            accumulator_b: state.accumulator_b,
+            index_register: state.index_register,
+            stack_pointer: state.stack_pointer,
+            condition_code_register: state.condition_code_register,
+            cycles
+        }
+    }
+
+    /// The condition codes as letters (H I N Z V C), with a dot for every cleared flag
+    fn format_flags(&self) -> String {
+        "HINZVC".chars().enumerate()
+            .map(|(index, flag)| if crate::get_bit!(self.condition_code_register, 5 - index) { flag } else { '.' })
+            .collect()
+    }
+
+    /// Format the record as a single line of a text trace
+    pub fn to_text(&self) -> String {
+        let bytes: Vec<String> = self.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let disassembly: String = match disassembler::disassemble_instruction(&self.bytes) {
+            Ok(instruction_info) => disassembler::format_instruction(&instruction_info, self.program_counter),
+            Err(_) => String::from("???")
+        };
+
+        format!("{:04X}  {:<8}  {:<12}  A={:02X} B={:02X} X={:04X} SP={:04X} CCR={:02X} {} CYC={}",
+                self.program_counter, bytes.join(" "), disassembly, self.accumulator_a, self.accumulator_b,
+                self.index_register, self.stack_pointer, self.condition_code_register, self.format_flags(), self.cycles)
+    }
+
+    /// Parse a line written by `to_text`
+    pub fn from_text(line: &str) -> Option<TraceRecord> {
+        let registers_start = line.find("  A=")?;
+        let mut record = TraceRecord {
+            program_counter: u16::from_str_radix(line.get(0..4)?, 16).ok()?,
+            bytes: Vec::new(),
+            accumulator_a: 0,
+            accumulator_b: 0,
+            index_register: 0,
+            stack_pointer: 0,
+            condition_code_register: 0,
+            cycles: 0
+        };
+
+        for byte in line.get(6..14)?.split_whitespace() {
+            record.bytes.push(u8::from_str_radix(byte, 16).ok()?);
+        }
+
+        for field in line[registers_start..].split_whitespace() {
+            if let Some((name, value)) = field.split_once('=') {
+                match name {
+                    "A" => record.accumulator_a = u8::from_str_radix(value, 16).ok()?,
+                    "B" => record.accumulator_b = u8::from_str_radix(value, 16).ok()?,
+                    "X" => record.index_register = u16::from_str_radix(value, 16).ok()?,
+                    "SP" => record.stack_pointer = u16::from_str_radix(value, 16).ok()?,
+                    "CCR" => record.condition_code_register = u8::from_str_radix(value, 16).ok()?,
+                    "CYC" => record.cycles = value.parse().ok()?,
+                    _ => {}
+                }
+            }
+        }
+
+        Some(record)
+    }
+
+    pub fn to_binary(&self) -> [u8; BINARY_RECORD_SIZE] {
+        let mut record = [0; BINARY_RECORD_SIZE];
+
+        record[0..2].copy_from_slice(&self.program_counter.to_be_bytes());
+        record[2] = self.bytes.len() as u8;
+        record[3..3 + self.bytes.len()].copy_from_slice(&self.bytes);
+        record[6] = self.accumulator_a;
+        record[7] = self.accumulator_b;
+        record[8..10].copy_from_slice(&self.index_register.to_be_bytes());
+        record[10..12].copy_from_slice(&self.stack_pointer.to_be_bytes());
+        record[12] = self.condition_code_register;
+        record[13..21].copy_from_slice(&self.cycles.to_be_bytes());
+
+        record
+    }
+
+    pub fn from_binary(record: &[u8]) -> Option<TraceRecord> {
+        let length: usize = *record.get(2)? as usize;
+        let mut cycles: [u8; 8] = [0; 8];
+
+        if record.len() < BINARY_RECORD_SIZE || length > 3 {
+            return None;
+        }
+
+        cycles.copy_from_slice(&record[13..21]);
+
+        Some(TraceRecord {
+            program_counter: ((record[0] as u16) << 8) | (record[1] as u16),
+            bytes: record[3..3 + length].to_vec(),
+            accumulator_a: record[6],
+            accumulator_b: record[7],
+            index_register: ((record[8] as u16) << 8) | (record[9] as u16),
+            stack_pointer: ((record[10] as u16) << 8) | (record[11] as u16),
+            condition_code_register: record[12],
+            cycles: u64::from_be_bytes(cycles)
+        })
+    }
+}
+
+impl Tracer {
+    pub fn new(format: TraceFormat, mut writer: Box<dyn Write + Send>) -> Tracer {
+        let mut error: Option<io::Error> = None;
+
+        if format == TraceFormat::Binary {
+            if let Err(err) = writer.write_all(BINARY_MAGIC).and_then(|_| writer.write_all(&BINARY_VERSION.to_be_bytes())) {
+                error = Some(err);
+            }
+        }
+
+        Tracer { format, writer, error }
+    }
+
+    pub fn to_file(format: TraceFormat, path: &str) -> io::Result<Tracer> {
+        Ok(Tracer::new(format, Box::new(io::BufWriter::new(fs::File::create(path)?))))
+    }
+
+    /// Write a record. After the first write error the tracer stops writing and keeps the error
+    /// for `finish` to report.
+    pub fn record(&mut self, record: &TraceRecord) {
+        let result: io::Result<()>;
+
+        if self.error.is_some() {
+            return;
+        }
+
+        result = match self.format {
+            TraceFormat::Text => writeln!(self.writer, "{}", record.to_text()),
+            TraceFormat::Binary => self.writer.write_all(&record.to_binary())
+        };
+
+        if let Err(err) = result {
+            self.error = Some(err);
+        }
+    }
+
+    /// Flush the trace, returning the first error that happened while writing it
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+
+        self.writer.flush()
+    }
+}
+
+/// Parse a trace in either format, detecting binary traces by their header
+pub fn parse_trace(data: &[u8]) -> Result<Vec<TraceRecord>, String> {
+    let mut records: Vec<TraceRecord> = Vec::new();
+
+    if data.starts_with(BINARY_MAGIC) {
+        if data.len() < BINARY_MAGIC.len() + 2 || data[BINARY_MAGIC.len()..BINARY_MAGIC.len() + 2] != BINARY_VERSION.to_be_bytes() {
+            return Err(String::from("unsupported binary trace version"));
+        }
+
+        for (index, chunk) in data[BINARY_MAGIC.len() + 2..].chunks(BINARY_RECORD_SIZE).enumerate() {
+            match TraceRecord::from_binary(chunk) {
+                Some(record) => records.push(record),
+                None => return Err(format!("malformed record {}", index))
+            }
+        }
+    } else {
+        for (index, line) in String::from_utf8_lossy(data).lines().enumerate() {
+            match TraceRecord::from_text(line) {
+                Some(record) => records.push(record),
+                None => return Err(format!("malformed line {}", index + 1))
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+pub fn read_trace_file(path: &str) -> Result<Vec<TraceRecord>, String> {
+    match fs::read(path) {
+        Ok(data) => parse_trace(&data),
+        Err(err) => Err(err.to_string())
+    }
+}
+
+/// Find the first record at which two traces differ
+pub fn diff_traces(left: &[TraceRecord], right: &[TraceRecord]) -> Option<TraceDivergence> {
+    let mut index: usize = 0;
+
+    while index < left.len() || index < right.len() {
+        if left.get(index) != right.get(index) {
+            return Some(TraceDivergence { index, left: left.get(index).cloned(), right: right.get(index).cloned() });
+        }
+
+        index += 1;
+    }
+
+    None
+}
@@ -0,0 +1,459 @@
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::time;
+
+use crate::debugger;
+use crate::processor;
+use crate::serial;
+use crate::serial::stty;
+
+// How many instructions run between redraws and checks for keys while the program runs
+const RUN_SLICE: u64 = 20000;
+
+// How often the screen is refreshed while the program runs
+const REFRESH_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+// The width of the register and stack column
+const SIDE_PANE_WIDTH: usize = 24;
+
+// The number of rows of 16 bytes in the memory pane
+const MEMORY_ROWS: usize = 8;
+
+// How far Page Up and Page Down scroll the memory pane
+const MEMORY_PAGE: u16 = 0x80;
+
+// The smallest screen the layout works with
+const MINIMUM_WIDTH: usize = 64;
+const MINIMUM_HEIGHT: usize = 20;
+
+const HELP: &str = "s step  n next  f finish  c continue  p pause  b break  up/down cursor  PgUp/PgDn memory  : command  q quit";
+
+/// Keeps the terminal in raw mode on the alternate screen until dropped, so it is given back even
+/// when the debugger panics
+struct Screen {
+    _mode: serial::CharacterMode
+}
+
+impl Screen {
+    fn enter() -> io::Result<Screen> {
+        let mode: serial::CharacterMode = serial::CharacterMode::with_settings(&["raw", "-echo", "min", "0", "time", "1"])?;
+
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+
+        Ok(Screen { _mode: mode })
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+    }
+}
+
+/// The number of columns and rows of the terminal, or 80 by 24 when it cannot be told
+fn get_terminal_size() -> (usize, usize) {
+    match stty(&["size"]).ok().and_then(|size| size.split_once(' ').map(|(rows, columns)| (rows.to_string(), columns.to_string()))) {
+        Some((rows, columns)) => (columns.parse().unwrap_or(80), rows.parse().unwrap_or(24)),
+        None => (80, 24)
+    }
+}
+
+/// A key press, decoded from the bytes the terminal sends
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Key {
+    Character(char),
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Enter,
+    Backspace,
+    Escape
+}
+
+/// A full screen terminal debugger
+///
+/// The screen shows the disassembly around a cursor which follows the program counter, the
+/// registers and flags, the stack and a scrollable hex view of memory. `:` opens a prompt for
+/// any command of the line debugger.
+pub struct Tui {
+    debugger: debugger::Debugger,
+    cursor: u16,
+    memory_address: u16,
+    status: String,
+    /// Multi-line output of the last command, shown instead of the memory pane
+    output: Vec<String>,
+    /// The command being typed after `:`
+    command: Option<String>,
+    running: bool,
+    /// The run was just resumed, so a breakpoint at the program counter must not fire
+    resuming: bool,
+    quit: bool
+}
+
+/// Decode the bytes read from a terminal in raw mode
+pub fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys: Vec<Key> = Vec::new();
+    let mut index: usize = 0;
+
+    while index < bytes.len() {
+        match &bytes[index..] {
+            [0x1B, b'[', b'A', ..] => { keys.push(Key::Up); index += 3; },
+            [0x1B, b'[', b'B', ..] => { keys.push(Key::Down); index += 3; },
+            [0x1B, b'[', b'5', b'~', ..] => { keys.push(Key::PageUp); index += 4; },
+            [0x1B, b'[', b'6', b'~', ..] => { keys.push(Key::PageDown); index += 4; },
+            [0x1B, b'[', ..] => {
+                // Skip any other escape sequence up to its final byte
+                index += 2;
+                while index < bytes.len() && !(0x40..=0x7E).contains(&bytes[index]) {
+                    index += 1;
+                }
+                index += 1;
+            },
+            [0x1B, ..] => { keys.push(Key::Escape); index += 1; },
+            [b'\r', ..] | [b'\n', ..] => { keys.push(Key::Enter); index += 1; },
+            [0x7F, ..] | [0x08, ..] => { keys.push(Key::Backspace); index += 1; },
+            [byte, ..] => { keys.push(Key::Character(*byte as char)); index += 1; },
+            [] => break
+        }
+    }
+
+    keys
+}
+
+/// Cut or pad a line to exactly `width` characters
+fn fit(text: &str, width: usize) -> String {
+    let mut line: String = text.chars().take(width).collect();
+    let length: usize = line.chars().count();
+
+    line.extend(std::iter::repeat_n(' ', width - length));
+    line
+}
+
+fn pane_title(title: &str, width: usize) -> String {
+    fit(&format!("-- {} {}", title, "-".repeat(width)), width)
+}
+
+impl Tui {
+    pub fn new(debugger: debugger::Debugger) -> Tui {
+        let program_counter: u16 = debugger.get_machine().get_state().program_counter;
+
+        Tui {
+            debugger,
+            cursor: program_counter,
+            memory_address: 0,
+            status: String::new(),
+            output: Vec::new(),
+            command: None,
+            running: false,
+            resuming: false,
+            quit: false
+        }
+    }
+
+    pub fn get_debugger(&self) -> &debugger::Debugger {
+        &self.debugger
+    }
+
+    pub fn get_debugger_mut(&mut self) -> &mut debugger::Debugger {
+        &mut self.debugger
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    pub fn get_status(&self) -> &str {
+        &self.status
+    }
+
+    /// Run a command of the line debugger, showing its output
+    fn execute(&mut self, command: &str) {
+        let output: String = self.debugger.execute(command);
+        let lines: Vec<String> = output.lines().map(String::from).collect();
+
+        self.status = lines.first().cloned().unwrap_or_default();
+        self.output = if lines.len() > 1 { lines } else { Vec::new() };
+        self.cursor = self.debugger.get_machine().get_state().program_counter;
+    }
+
+    /// Execute a command without showing the registers it prints, which are on screen anyway
+    fn execute_quietly(&mut self, command: &str) {
+        let output: String = self.debugger.execute(command);
+
+        self.output.clear();
+        self.status = match output.lines().next() {
+            Some(line) if !line.starts_with("PC=") => String::from(line),
+            _ => String::new()
+        };
+        self.cursor = self.debugger.get_machine().get_state().program_counter;
+    }
+
+    fn toggle_breakpoint(&mut self) {
+        let cursor: u16 = self.cursor;
+
+        if self.debugger.get_machine().get_processor().get_breakpoints().has_breakpoint_at(cursor) {
+            self.execute_quietly(&format!("bd ${:04X}", cursor));
+        } else {
+            self.execute_quietly(&format!("b ${:04X}", cursor));
+        }
+
+        self.cursor = cursor;
+    }
+
+    /// Move the cursor by one instruction
+    fn move_cursor(&mut self, down: bool) {
+        if down {
+            self.cursor = self.cursor.wrapping_add(self.debugger.format_instruction_line(self.cursor).1);
+        } else {
+            let start: u16 = self.debugger.find_disassembly_start(self.cursor);
+
+            // Walk forward from a known instruction boundary to the one right before the cursor
+            let mut address: u16 = start;
+            let mut previous: u16 = self.cursor.wrapping_sub(1);
+
+            while address != self.cursor && self.cursor.wrapping_sub(address) <= self.cursor.wrapping_sub(start) {
+                previous = address;
+                address = address.wrapping_add(self.debugger.format_instruction_line(address).1);
+            }
+
+            self.cursor = previous;
+        }
+    }
+
+    pub fn handle_key(&mut self, key: Key) {
+        if let Some(command) = self.command.as_mut() {
+            match key {
+                Key::Character(character) if !character.is_control() => command.push(character),
+                Key::Backspace => { command.pop(); },
+                Key::Enter => {
+                    let command: String = self.command.take().unwrap();
+                    self.execute(&command);
+                },
+                Key::Escape | Key::Character('\u{3}') => self.command = None,
+                _ => {}
+            }
+
+            return;
+        }
+
+        if self.running {
+            if matches!(key, Key::Character('p') | Key::Character(' ') | Key::Character('\u{3}') | Key::Escape) {
+                self.running = false;
+                self.status = String::from("Paused");
+                self.cursor = self.debugger.get_machine().get_state().program_counter;
+            }
+
+            return;
+        }
+
+        match key {
+            Key::Character('s') => self.execute_quietly("s"),
+            Key::Character('n') => self.execute_quietly("n"),
+            Key::Character('f') => self.execute_quietly("f"),
+            Key::Character('c') => {
+                self.running = true;
+                self.resuming = true;
+                self.status = String::from("Running");
+                self.output.clear();
+            },
+            Key::Character('b') => self.toggle_breakpoint(),
+            Key::Character(':') => self.command = Some(String::new()),
+            Key::Character('q') | Key::Character('\u{3}') => self.quit = true,
+            Key::Up => self.move_cursor(false),
+            Key::Down => self.move_cursor(true),
+            Key::PageUp => self.memory_address = self.memory_address.wrapping_sub(MEMORY_PAGE),
+            Key::PageDown => self.memory_address = self.memory_address.wrapping_add(MEMORY_PAGE),
+            Key::Escape => self.output.clear(),
+            _ => {}
+        }
+    }
+
+    /// Emulate a bounded number of instructions of a running program
+    pub fn run_slice(&mut self) {
+        let result: processor::RunResult;
+
+        if !self.running {
+            return;
+        }
+
+        // `run` never fires a breakpoint on its first instruction, which is only wanted when the
+        // program was just resumed
+        result = match self.debugger.get_machine().get_processor().check_breakpoints().filter(|_| !self.resuming) {
+            Some(hit) => processor::RunResult { stop_reason: processor::StopReason::Breakpoint(hit), statistics: processor::RunStatistics::default() },
+            None => self.debugger.get_machine_mut().run(&[processor::StopCondition::InstructionBudget(RUN_SLICE), processor::StopCondition::InfiniteLoop])
+        };
+
+        self.resuming = false;
+
+        if result.stop_reason != processor::StopReason::InstructionBudgetExhausted {
+            self.running = false;
+            self.status = self.debugger.describe_stop_reason(&result.stop_reason);
+            self.cursor = self.debugger.get_machine().get_state().program_counter;
+        }
+    }
+
+    fn render_disassembly(&self, width: usize, height: usize) -> Vec<String> {
+        let mut lines: Vec<String> = vec![pane_title("Disassembly", width)];
+        let mut address: u16 = self.debugger.find_disassembly_start(self.cursor);
+        let mut line: String;
+        let mut length: u16;
+
+        while lines.len() < height {
+            (line, length) = self.debugger.format_instruction_line(address);
+            line = fit(&line, width);
+
+            if address == self.cursor {
+                line = format!("\x1b[7m{}\x1b[0m", line);
+            }
+
+            lines.push(line);
+            address = address.wrapping_add(length);
+        }
+
+        lines
+    }
+
+    fn render_side_pane(&self, width: usize, height: usize) -> Vec<String> {
+        let state: processor::ProcessorState = self.debugger.get_machine().get_state();
+        let memory = self.debugger.get_machine().get_memory_manager();
+        let mut lines: Vec<String> = vec![
+            pane_title("Registers", width),
+            format!(" A  ${:02X}     B  ${:02X}", state.accumulator_a, state.accumulator_b),
+            format!(" X  ${:04X}", state.index_register),
+            format!(" PC ${:04X}", state.program_counter),
+            format!(" SP ${:04X}", state.stack_pointer),
+            format!(" CCR ${:02X}", state.condition_code_register),
+            String::from(" H I N Z V C"),
+            format!(" {}", (0..6).rev().map(|bit| (crate::get_bit!(state.condition_code_register, bit) as u8).to_string()).collect::<Vec<String>>().join(" ")),
+            pane_title("Stack", width)
+        ];
+        let mut address: u16 = state.stack_pointer;
+
+        while lines.len() < height {
+            address = address.wrapping_add(1);
+            lines.push(format!(" {:04X}  {:02X}", address, memory.read(address, 1)[0]));
+        }
+
+        lines.truncate(height);
+        lines.into_iter().map(|line| fit(&line, width)).collect()
+    }
+
+    fn render_bottom_pane(&self, width: usize) -> Vec<String> {
+        let mut lines: Vec<String>;
+
+        if self.output.is_empty() {
+            lines = vec![pane_title(&format!("Memory ${:04X}", self.memory_address), width)];
+            lines.extend(debugger::hex_dump(self.debugger.get_machine().get_memory_manager(), self.memory_address, (MEMORY_ROWS * 16) as u32)
+                    .lines().map(String::from));
+        } else {
+            lines = vec![pane_title("Output (Esc to close)", width)];
+            lines.extend(self.output.iter().take(MEMORY_ROWS).cloned());
+        }
+
+        lines.resize(MEMORY_ROWS + 1, String::new());
+        lines.into_iter().map(|line| fit(&line, width)).collect()
+    }
+
+    /// Draw the whole screen as lines of (at most) `width` visible characters
+    pub fn render(&self, width: usize, height: usize) -> Vec<String> {
+        let width: usize = width.max(MINIMUM_WIDTH);
+        let height: usize = height.max(MINIMUM_HEIGHT);
+        let top_height: usize = height - MEMORY_ROWS - 3;
+        let disassembly: Vec<String> = self.render_disassembly(width - SIDE_PANE_WIDTH - 1, top_height);
+        let side_pane: Vec<String> = self.render_side_pane(SIDE_PANE_WIDTH, top_height);
+        let mut lines: Vec<String> = Vec::with_capacity(height);
+        let status: String;
+
+        for (left, right) in disassembly.iter().zip(side_pane.iter()) {
+            lines.push(format!("{}|{}", left, right));
+        }
+
+        lines.extend(self.render_bottom_pane(width));
+
+        status = match &self.command {
+            Some(command) => format!(":{}", command),
+            None => self.status.clone()
+        };
+
+        lines.push(fit(&status, width));
+        lines.push(format!("\x1b[7m{}\x1b[0m", fit(HELP, width)));
+
+        lines
+    }
+
+    fn draw(&self, terminal: &mut dyn Write, width: usize, height: usize) -> io::Result<()> {
+        let lines: Vec<String> = self.render(width, height);
+
+        write!(terminal, "\x1b[H{}", lines.join("\r\n"))?;
+        terminal.flush()
+    }
+
+    /// Take over the terminal until the user quits. Needs `stty`, so it works on any Unix
+    /// terminal, including over SSH.
+    pub fn run_terminal(&mut self) -> io::Result<()> {
+        let _screen: Screen = Screen::enter()?;
+
+        serial::watch_resize();
+
+        self.terminal_loop()
+    }
+
+    fn terminal_loop(&mut self) -> io::Result<()> {
+        let mut stdin = io::stdin();
+        let mut stdout = io::stdout();
+        let mut buffer: [u8; 64] = [0; 64];
+        let mut size: (usize, usize) = get_terminal_size();
+        let mut last_draw: time::Instant = time::Instant::now();
+        let mut dirty: bool = true;
+        let mut was_running: bool = false;
+        let mut count: usize;
+
+        while !self.quit {
+            // The size is only queried again when the terminal says it changed, or on Ctrl-L
+            if serial::take_resized() {
+                size = get_terminal_size();
+                write!(stdout, "\x1b[2J")?;
+                dirty = true;
+            }
+
+            if dirty || (self.running && last_draw.elapsed() >= REFRESH_INTERVAL) {
+                self.draw(&mut stdout, size.0, size.1)?;
+                last_draw = time::Instant::now();
+                dirty = false;
+            }
+
+            // Reads wait for a tenth of a second when stopped, and return at once while running
+            if self.running != was_running {
+                stty(if self.running { &["time", "0"] } else { &["time", "1"] })?;
+                was_running = self.running;
+            }
+
+            count = stdin.read(&mut buffer)?;
+
+            for key in parse_keys(&buffer[..count]) {
+                if key == Key::Character('\u{c}') {
+                    size = get_terminal_size();
+                    write!(stdout, "\x1b[2J")?;
+                } else {
+                    self.handle_key(key);
+                }
+
+                dirty = true;
+            }
+
+            if self.running {
+                self.run_slice();
+                dirty |= !self.running;
+            }
+        }
+
+        Ok(())
+    }
+}
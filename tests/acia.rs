@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod acia_tests {
+    use momulator::acia::*;
+    use momulator::machine::*;
+    use momulator::memory_manager::*;
+    use momulator::processor::*;
+    use momulator::serial::*;
+
+    const ACIA_ADDRESS: u16 = 0x8004;
+
+    fn build_machine(port: &BufferPort) -> Machine {
+        let mut machine = Machine::new();
+
+        machine.get_memory_manager_mut().attach_device(ACIA_ADDRESS, Box::new(Acia::new(Box::new(port.clone()))));
+
+        machine
+    }
+
+    #[test]
+    fn test_polled_transfers() {
+        let port = BufferPort::new();
+        let mut machine = build_machine(&port);
+
+        // Reset the ACIA, print "HI" waiting for TDRE, then echo one byte waiting for RDRF
+        machine.load_program(0, &[
+            0x86, 0x03, 0xB7, 0x80, 0x04, 0x86, 0x15, 0xB7, 0x80, 0x04, 0xCE, 0x00, 0x30,
+            0xB6, 0x80, 0x04, 0x85, 0x02, 0x27, 0xF9, 0xA6, 0x00, 0x27, 0x07, 0xB7, 0x80, 0x05, 0x08, 0x20, 0xEF,
+            0xB6, 0x80, 0x04, 0x84, 0x01, 0x27, 0xF9, 0xB6, 0x80, 0x05, 0xB7, 0x80, 0x05, 0x20, 0xFE
+        ]);
+        machine.write_memory(0x30, b"HI\0");
+        port.push_input(b"x");
+
+        machine.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(10000)]);
+
+        assert_eq!(port.take_output(), b"HIx".to_vec());
+        assert_eq!(port.get_pending_input(), 0);
+        assert_eq!(machine.read_memory(ACIA_ADDRESS, 1)[0], STATUS_TRANSMIT_DATA_REGISTER_EMPTY);
+    }
+
+    #[test]
+    fn test_receive_interrupt() {
+        let port = BufferPort::new();
+        let mut machine = build_machine(&port);
+
+        // Enable the receive interrupt and wait for it, the handler stores the byte at $50
+        machine.load_program(0, &[0x8E, 0x00, 0xFF, 0x86, 0x03, 0xB7, 0x80, 0x04, 0x86, 0x95, 0xB7, 0x80, 0x04, 0x0E, 0x3E, 0x20, 0xFE]);
+        machine.write_memory(0x40, &[0xB6, 0x80, 0x05, 0x97, 0x50, 0x3B]);
+        machine.write_memory(IRQ_VECTOR, &[0x00, 0x40]);
+
+        assert_eq!(machine.run(&[StopCondition::InstructionBudget(100)]).stop_reason, StopReason::InstructionBudgetExhausted);
+
+        port.push_input(b"A");
+
+        assert_eq!(machine.run(&[StopCondition::ProgramCounterEquals(0x0F), StopCondition::InstructionBudget(100)]).stop_reason,
+                StopReason::ProgramCounterReached(0x0F));
+        assert_eq!(machine.read_memory(0x50, 1)[0], b'A');
+        assert_eq!(machine.get_memory_manager().get_irq(), false);
+    }
+
+    #[test]
+    fn test_character_pacing() {
+        let port = BufferPort::new();
+        let mut memory_manager = MemoryManager::new();
+        let mut acia = Acia::new(Box::new(port.clone()));
+
+        acia.set_character_cycles(100);
+        memory_manager.attach_device(ACIA_ADDRESS, Box::new(acia));
+        assert_eq!(memory_manager.read(ACIA_ADDRESS, 1)[0], 0);
+
+        memory_manager.write(ACIA_ADDRESS, &[0x15]);
+        memory_manager.write(ACIA_ADDRESS + 1, b"A");
+        assert_eq!(memory_manager.read(ACIA_ADDRESS, 1)[0] & STATUS_TRANSMIT_DATA_REGISTER_EMPTY, 0);
+
+        memory_manager.tick(100);
+        assert_eq!(memory_manager.read(ACIA_ADDRESS, 1)[0], STATUS_TRANSMIT_DATA_REGISTER_EMPTY);
+        assert_eq!(port.take_output(), b"A".to_vec());
+
+        port.push_input(b"BC");
+        memory_manager.tick(1);
+        assert_eq!(memory_manager.read_byte(ACIA_ADDRESS + 1), b'B');
+        memory_manager.tick(50);
+        assert_eq!(memory_manager.read_byte(ACIA_ADDRESS) & STATUS_RECEIVE_DATA_REGISTER_FULL, 0);
+        memory_manager.tick(50);
+        assert_eq!(memory_manager.read_byte(ACIA_ADDRESS + 1), b'C');
+    }
+}
@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod breakpoints_tests {
+    use momulator::breakpoints::*;
+    use momulator::machine::*;
+    use momulator::processor::*;
+
+    // LDAA #$10, loop: STAA $80, DECA, BNE loop, LDAB $80, BRA *
+    const PROGRAM: [u8; 11] = [0x86, 0x10, 0x97, 0x80, 0x4A, 0x26, 0xFB, 0xD6, 0x80, 0x20, 0xFE];
+
+    fn create_machine() -> Machine {
+        let mut machine = Machine::new();
+
+        machine.load_program(0, &PROGRAM);
+        machine
+    }
+
+    #[test]
+    fn test_conditions() {
+        let mut state = ProcessorState::new_empty();
+        let mut memory = momulator::memory_manager::MemoryManager::new();
+
+        state.accumulator_a = 0xFF;
+        state.index_register = 0x1234;
+        state.set_condition_code_flag(ConditionCodeFlag::Zero, true);
+        memory.write(0x80, &[0x42]);
+
+        assert_eq!(Condition::parse("A == $FF && Z").unwrap().evaluate(&state, &memory), true);
+        assert_eq!(Condition::parse("a == 255 && !z").unwrap().evaluate(&state, &memory), false);
+        assert_eq!(Condition::parse("[$80] == $42 || C").unwrap().evaluate(&state, &memory), true);
+        assert_eq!(Condition::parse("(X & $FF) - 4 == $30").unwrap().evaluate(&state, &memory), true);
+        assert_eq!(Condition::parse("[X - $11B4] >= 0x42").unwrap().evaluate(&state, &memory), true);
+        assert_eq!(Condition::parse("[$80] == %1000010 && (A & 0b1111) == 15").unwrap().evaluate(&state, &memory), true);
+        assert_eq!(Condition::parse("A == %102").is_err(), true);
+        assert_eq!(Condition::parse("A == ").is_err(), true);
+        assert_eq!(Condition::parse("Q > 1").is_err(), true);
+        assert_eq!(Condition::parse("(A").is_err(), true);
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let mut machine = create_machine();
+        let result: RunResult;
+        let id: u32;
+
+        id = machine.get_processor_mut().get_breakpoints_mut().add_breakpoint(0x4, Some(Condition::parse("A == 3").unwrap()));
+        result = machine.run(&[StopCondition::InfiniteLoop]);
+
+        assert_eq!(result.stop_reason, StopReason::Breakpoint(BreakpointHit { id, program_counter: 0x4, access: None }));
+        assert_eq!(machine.get_state().accumulator_a, 3);
+
+        // Continuing does not fire the breakpoint the run stopped on, and disabled breakpoints
+        // never fire
+        assert_eq!(machine.get_processor_mut().get_breakpoints_mut().set_enabled(id, false), true);
+        assert_eq!(machine.run(&[StopCondition::InfiniteLoop]).stop_reason, StopReason::InfiniteLoop(0x9));
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut machine = create_machine();
+        let mut result: RunResult;
+        let write_id: u32;
+        let read_id: u32;
+
+        write_id = machine.get_processor_mut().get_breakpoints_mut().add_watchpoint(0x80, 0x80, WatchKind::Write, Some(Condition::parse("[$80] == $0E").unwrap()));
+        read_id = machine.get_processor_mut().get_breakpoints_mut().add_watchpoint(0x7F, 0x81, WatchKind::Read, None);
+
+        result = machine.run(&[StopCondition::InfiniteLoop]);
+        assert_eq!(result.stop_reason, StopReason::Breakpoint(BreakpointHit {
+            id: write_id,
+            program_counter: 0x2,
+            access: Some(MemoryAccess { kind: AccessKind::Write, address: 0x80, value: 0x0E, previous_value: 0x0F })
+        }));
+
+        // STAA only writes its operand, so the read watchpoint fires on LDAB
+        result = machine.run(&[StopCondition::InfiniteLoop]);
+        assert_eq!(result.stop_reason, StopReason::Breakpoint(BreakpointHit {
+            id: read_id,
+            program_counter: 0x7,
+            access: Some(MemoryAccess { kind: AccessKind::Read, address: 0x80, value: 0x01, previous_value: 0x01 })
+        }));
+
+        assert_eq!(machine.get_processor_mut().get_breakpoints_mut().remove(read_id), true);
+        assert_eq!(machine.get_processor_mut().get_breakpoints_mut().add_watchpoint(0x80, 0x80, WatchKind::Change, None) > read_id, true);
+        assert_eq!(machine.run(&[StopCondition::InfiniteLoop]).stop_reason, StopReason::InfiniteLoop(0x9));
+    }
+}
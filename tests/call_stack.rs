@@ -0,0 +1,84 @@
+#[cfg(test)]
+mod call_stack_tests {
+    use momulator::call_stack::*;
+    use momulator::machine::*;
+    use momulator::processor::*;
+    use momulator::symbols::*;
+
+    // main: JSR outer, BRA *
+    // outer: BSR inner, RTS
+    // inner: the given bytes
+    fn create_machine(inner: &[u8]) -> Machine {
+        let mut machine = Machine::new();
+        let mut state: ProcessorState;
+
+        machine.load_program(0x100, &[0xBD, 0x01, 0x10, 0x20, 0xFE]);
+        machine.load_program(0x110, &[0x8D, 0x01, 0x39]);
+        machine.load_program(0x113, inner);
+        state = machine.get_state();
+        state.program_counter = 0x100;
+        state.stack_pointer = 0x1FF;
+        machine.get_processor_mut().set_state(state);
+
+        machine
+    }
+
+    #[test]
+    fn test_nested_calls() {
+        let mut machine = create_machine(&[0x01, 0x39]);
+        let mut symbols = SymbolTable::new();
+
+        symbols.insert("main", 0x100);
+        symbols.insert("outer", 0x110);
+        symbols.insert("inner", 0x113);
+        machine.get_processor_mut().enable_rewind(16);
+
+        machine.run(&[StopCondition::ProgramCounterEquals(0x113)]);
+        assert_eq!(machine.get_processor().get_call_stack().get_frames(), &[
+            Frame { kind: FrameKind::Subroutine, call_address: 0x100, target: 0x110, return_address: 0x103, stack_pointer: 0x1FD },
+            Frame { kind: FrameKind::Subroutine, call_address: 0x110, target: 0x113, return_address: 0x112, stack_pointer: 0x1FB }
+        ]);
+        assert_eq!(machine.get_processor().get_call_stack().format_backtrace(0x113, &symbols),
+                "#0  inner ($0113)\n#1  outer ($0110)\n#2  main ($0100)");
+
+        // Returning pops the frames, and stepping back over a return brings them back
+        machine.run(&[StopCondition::ProgramCounterEquals(0x112)]);
+        assert_eq!(machine.get_processor().get_call_stack().depth(), 1);
+        assert_eq!(machine.get_processor_mut().step_back(), true);
+        assert_eq!(machine.get_processor().get_call_stack().depth(), 2);
+
+        machine.run(&[StopCondition::InfiniteLoop]);
+        assert_eq!(machine.get_processor().get_call_stack().depth(), 0);
+        assert_eq!(machine.get_processor().get_call_stack().get_mismatches().is_empty(), true);
+    }
+
+    #[test]
+    fn test_mismatched_return() {
+        // inner: INS, INS, RTS, which drops its own return address and returns to main
+        let mut machine = create_machine(&[0x31, 0x31, 0x39]);
+        let mismatch: StackMismatch;
+
+        machine.run(&[StopCondition::InfiniteLoop]);
+        assert_eq!(machine.get_state().program_counter, 0x103);
+        assert_eq!(machine.get_processor().get_call_stack().depth(), 0);
+
+        mismatch = machine.get_processor().get_call_stack().get_mismatches()[0];
+        assert_eq!(mismatch, StackMismatch { address: 0x115, return_address: 0x103, expected: Some(0x112), discarded: 1 });
+        assert_eq!(mismatch.to_string(), "Return at $0115 went to $0103, expected $0112 (1 frame dropped)");
+    }
+
+    #[test]
+    fn test_interrupts() {
+        let mut call_stack = CallStack::new();
+
+        call_stack.enter(Frame { kind: FrameKind::Subroutine, call_address: 0x100, target: 0x200, return_address: 0x103, stack_pointer: 0x1FD });
+        call_stack.enter(Frame { kind: FrameKind::Irq, call_address: 0x205, target: 0xE000, return_address: 0x205, stack_pointer: 0x1F6 });
+        assert_eq!(call_stack.format_backtrace(0xE010, &SymbolTable::new()), "#0  $E010\n#1  $0205  <IRQ>\n#2  $0100");
+
+        // An RTS cannot leave an interrupt handler
+        assert_eq!(call_stack.leave(0xE010, false, 0x1F6, 0x205).is_some(), true);
+        assert_eq!(call_stack.depth(), 1);
+        assert_eq!(call_stack.leave(0x210, false, 0x1FD, 0x103), None);
+        assert_eq!(call_stack.depth(), 0);
+    }
+}
@@ -0,0 +1,38 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use momulator::machine::*;
+use momulator::observer::*;
+use momulator::processor::*;
+
+/// Run the program in a machine until it loops forever, with an observer watching it. Returns the
+/// machine and the id of the observer.
+pub fn run_observed(mut machine: Machine, observer: Box<dyn Observer>) -> (Machine, u32) {
+    let id: u32 = machine.get_processor_mut().add_observer(observer);
+
+    machine.run(&[StopCondition::InfiniteLoop]);
+
+    (machine, id)
+}
+
+/// Run tests/test.bin on the command line with the semihosting device it exits through and some
+/// more arguments, from the root of the repository
+pub fn run_test_program(arguments: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_momulator"))
+        .args(["run", "tests/test.bin", "--semihosting", "$FF00"])
+        .args(arguments)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .unwrap()
+}
+
+/// A directory of its own for the files a test writes
+pub fn create_directory(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("momulator_{}_{}", name, std::process::id()));
+
+    fs::create_dir_all(&directory).unwrap();
+
+    directory
+}
@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod console_tests {
+    use std::path::Path;
+
+    use momulator::console::*;
+    use momulator::machine::*;
+    use momulator::machine_config::*;
+    use momulator::processor::*;
+    use momulator::serial::*;
+
+    const CONSOLE_ADDRESS: u16 = 0xC000;
+
+    fn build_machine(port: &BufferPort) -> Machine {
+        let mut machine = Machine::new();
+
+        machine.get_memory_manager_mut().attach_device(CONSOLE_ADDRESS, Box::new(Console::new(Box::new(port.clone()))));
+
+        machine
+    }
+
+    #[test]
+    fn test_hello_world() {
+        let port = BufferPort::new();
+        let mut machine = build_machine(&port);
+
+        // Print the string at $20 up to its terminating 0
+        machine.load_program(0, &[0xCE, 0x00, 0x20, 0xA6, 0x00, 0x27, 0x06, 0xB7, 0xC0, 0x00, 0x08, 0x20, 0xF6, 0x20, 0xFE]);
+        machine.write_memory(0x20, b"Hello, world!\n\0");
+        machine.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(200)]);
+
+        assert_eq!(port.take_output(), b"Hello, world!\n".to_vec());
+    }
+
+    #[test]
+    fn test_input_does_not_block() {
+        let port = BufferPort::new();
+        let mut machine = build_machine(&port);
+        let memory_manager = machine.get_memory_manager_mut();
+
+        assert_eq!(memory_manager.read_byte(CONSOLE_ADDRESS + 1), 0);
+        assert_eq!(memory_manager.read_byte(CONSOLE_ADDRESS), 0);
+
+        port.push_input(b"ab");
+
+        assert_eq!(memory_manager.read_byte(CONSOLE_ADDRESS + 1), STATUS_INPUT_READY);
+        assert_eq!(memory_manager.read_byte(CONSOLE_ADDRESS), b'a');
+        assert_eq!(memory_manager.read_byte(CONSOLE_ADDRESS), b'b');
+        assert_eq!(memory_manager.read_byte(CONSOLE_ADDRESS + 1), 0);
+    }
+
+    #[test]
+    fn test_machine_config() {
+        let config = MachineConfig::parse("
+            [[device]]
+            type = \"console\"
+            address = 0xC000
+        ", Path::new("")).unwrap();
+        let machine = config.build().unwrap();
+
+        assert_eq!(config.devices[0].kind, DeviceKind::Console(PortConfig::Terminal));
+        assert_eq!(machine.get_memory_manager().find_device::<Console>().is_some(), true);
+    }
+}
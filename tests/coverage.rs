@@ -0,0 +1,80 @@
+mod common;
+
+#[cfg(test)]
+mod coverage_tests {
+    use std::fs;
+
+    use momulator::coverage::*;
+    use momulator::line_table::*;
+    use momulator::machine::*;
+
+    use super::common;
+
+    // 1: LDAA #1, 2: BEQ +1, 3: INCA, 4: BNE *, 5: NOP, 6: BEQ *
+    fn run_program() -> (Machine, LineTable, u32) {
+        let mut machine = Machine::new();
+        let mut line_table = LineTable::new();
+        let id: u32;
+
+        machine.load_program(0, &[0x86, 0x01, 0x27, 0x01, 0x4C, 0x26, 0xFE, 0x01, 0x27, 0xFE]);
+
+        for (line, address) in [(1, 0), (2, 2), (3, 4), (4, 5), (5, 7), (6, 8)] {
+            line_table.insert(line, address);
+        }
+
+        (machine, id) = common::run_observed(machine, Box::new(Coverage::new()));
+
+        (machine, line_table, id)
+    }
+
+    #[test]
+    fn test_collect_coverage() {
+        let (machine, _, id) = run_program();
+        let coverage: &Coverage = machine.get_processor().get_observer(id).unwrap();
+
+        assert_eq!(coverage.get_addresses(), vec![0, 2, 4, 5]);
+        assert_eq!(coverage.get_hits(5), 1);
+        assert_eq!(coverage.get_hits(7), 0);
+        assert_eq!(coverage.get_branch(2), Some(BranchCounts { taken: 0, not_taken: 1 }));
+        assert_eq!(coverage.get_branch(5), Some(BranchCounts { taken: 1, not_taken: 0 }));
+        assert_eq!(coverage.get_branch(4), None);
+    }
+
+    #[test]
+    fn test_reports() {
+        let (machine, line_table, id) = run_program();
+        let coverage: &Coverage = machine.get_processor().get_observer(id).unwrap();
+        let cobertura: String;
+
+        assert_eq!(coverage.to_lcov(&line_table, machine.get_memory_manager(), "test.asm"),
+                "TN:\nSF:test.asm\n\
+                BRDA:2,0,0,0\nBRDA:2,0,1,1\nBRDA:4,0,0,1\nBRDA:4,0,1,0\nBRDA:6,0,0,-\nBRDA:6,0,1,-\nBRF:6\nBRH:2\n\
+                DA:1,1\nDA:2,1\nDA:3,1\nDA:4,1\nDA:5,0\nDA:6,0\nLF:6\nLH:4\nend_of_record\n");
+
+        cobertura = coverage.to_cobertura(&line_table, machine.get_memory_manager(), "src/test.asm");
+        assert_eq!(cobertura.contains("lines-covered=\"4\" lines-valid=\"6\" branches-covered=\"2\" branches-valid=\"6\""), true);
+        assert_eq!(cobertura.contains("<class name=\"test.asm\" filename=\"src/test.asm\""), true);
+        assert_eq!(cobertura.contains("<line number=\"2\" hits=\"1\" branch=\"true\" condition-coverage=\"50% (1/2)\"/>"), true);
+        assert_eq!(cobertura.contains("<line number=\"5\" hits=\"0\" branch=\"false\"/>"), true);
+    }
+
+    /// Cover tests/test.asm from its as0 listing, the way the command line does
+    #[test]
+    fn test_command_line_coverage() {
+        let directory = common::create_directory("coverage");
+        let report_path = directory.join("test.info");
+        let output = common::run_test_program(&["--listing", "tests/test.lst", "--coverage", report_path.to_str().unwrap()]);
+        let report: String;
+
+        assert_eq!(output.status.code(), Some(0));
+
+        report = fs::read_to_string(report_path).unwrap();
+        assert_eq!(report.starts_with("TN:\nSF:tests/test.asm\n"), true);
+
+        // Every instruction of the listing is a line, the error exit is never reached
+        assert_eq!(report.contains("\nLF:404\n"), true);
+        assert_eq!(report.contains("\nDA:10,1\n"), true);
+        assert_eq!(report.contains("\nDA:562,0\n"), true);
+        assert_eq!(report.contains("\nDA:566,1\n"), true);
+    }
+}
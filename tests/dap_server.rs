@@ -0,0 +1,140 @@
+#[cfg(test)]
+mod dap_server_tests {
+    use std::env;
+    use std::fs;
+    use std::io;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use momulator::dap_server::*;
+    use momulator::json::*;
+
+    const PROGRAM: [u8; 12] = [0x86, 0x01, 0xBD, 0x01, 0x0A, 0x97, 0x80, 0x20, 0xFE, 0x00, 0x4C, 0x39];
+
+    const LISTING: &str = "0001                         \t\tnam dap_test
+0002 0100                    \t\torg $100
+0003                         main
+0004 0100 86 01              \tldaa #$01
+0005 0102 bd 01 0a           \tjsr increment
+0006 0105 97 80              \tstaa $80
+0007 0107 20 fe              \tbra *
+0008 0109 00                 \tfcb 0
+0009                         increment
+0010 010a 4c                 \tinca
+0011 010b 39                 \trts
+main       0100 *0003
+increment  010a *0009 0005
+";
+
+    // A writer which can still be read after it was moved into the server
+    #[derive(Clone)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        // Take the messages written since the last call
+        fn take_messages(&self) -> Vec<JsonValue> {
+            let data: Vec<u8> = self.0.lock().unwrap().drain(..).collect();
+            let text = String::from_utf8(data).unwrap();
+            let mut messages: Vec<JsonValue> = Vec::new();
+            let mut rest: &str = &text;
+            let mut length: usize;
+
+            while let Some(header_end) = rest.find("\r\n\r\n") {
+                length = rest[..header_end].trim_start_matches("Content-Length: ").parse().unwrap();
+                messages.push(JsonValue::parse(&rest[header_end + 4..header_end + 4 + length]).unwrap());
+                rest = &rest[header_end + 4 + length..];
+            }
+
+            messages
+        }
+    }
+
+    fn request(server: &mut DapServer, sequence: u64, command: &str, arguments: &str) {
+        let message = format!(r#"{{"seq":{},"type":"request","command":"{}","arguments":{}}}"#, sequence, command, arguments);
+
+        server.handle_message(&JsonValue::parse(&message).unwrap()).unwrap();
+
+        while server.is_running() {
+            server.run_slice().unwrap();
+        }
+    }
+
+    fn body(message: &JsonValue) -> &JsonValue {
+        message.get("body").unwrap()
+    }
+
+    #[test]
+    fn test_debug_session() {
+        let directory = env::temp_dir().join(format!("momulator_dap_{}", std::process::id()));
+        let program_path = directory.join("dap_test.bin");
+        let listing_path = directory.join("dap_test.lst");
+        let buffer = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+        let mut server = DapServer::new(Box::new(buffer.clone()));
+        let mut messages: Vec<JsonValue>;
+
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(&program_path, PROGRAM).unwrap();
+        fs::write(&listing_path, LISTING).unwrap();
+
+        request(&mut server, 1, "initialize", r#"{"adapterID":"momulator"}"#);
+        messages = buffer.take_messages();
+        assert_eq!(body(&messages[0]).get("supportsConditionalBreakpoints"), Some(&JsonValue::Bool(true)));
+        assert_eq!(messages[1].get("event").unwrap().as_str(), Some("initialized"));
+
+        request(&mut server, 2, "launch", &format!(r#"{{"program":{},"listing":{},"loadAddress":"$100","stopOnEntry":true}}"#,
+                JsonValue::from(program_path.to_str().unwrap()), JsonValue::from(listing_path.to_str().unwrap())));
+        request(&mut server, 3, "setBreakpoints", r#"{"source":{"path":"dap_test.asm"},"breakpoints":[{"line":9},{"line":6,"condition":"A == 8"}]}"#);
+        messages = buffer.take_messages();
+        assert_eq!(messages[0].get("success"), Some(&JsonValue::Bool(true)));
+        assert_eq!(body(&messages[1]).get("breakpoints").unwrap().to_string(),
+                r#"[{"id":1,"verified":true,"line":10},{"id":2,"verified":true,"line":6}]"#);
+
+        request(&mut server, 4, "configurationDone", "{}");
+        messages = buffer.take_messages();
+        assert_eq!(body(&messages[1]).get("reason").unwrap().as_str(), Some("entry"));
+
+        request(&mut server, 5, "stackTrace", r#"{"threadId":1}"#);
+        messages = buffer.take_messages();
+        let frame = &body(&messages[0]).get("stackFrames").unwrap().as_array().unwrap()[0];
+        assert_eq!(frame.get("line").unwrap().as_u64(), Some(4));
+        assert_eq!(frame.get("name").unwrap().as_str(), Some("main ($0100)"));
+        assert_eq!(frame.get("source").unwrap().get("name").unwrap().as_str(), Some("dap_test.asm"));
+
+        // Stop in the subroutine, then step over INCA and hit the conditional breakpoint
+        request(&mut server, 6, "continue", r#"{"threadId":1}"#);
+        messages = buffer.take_messages();
+        assert_eq!(body(&messages[1]).to_string(), r#"{"reason":"breakpoint","threadId":1,"allThreadsStopped":true,"hitBreakpointIds":[1]}"#);
+
+        request(&mut server, 7, "setVariable", r#"{"variablesReference":1,"name":"A","value":"7"}"#);
+        request(&mut server, 8, "next", r#"{"threadId":1}"#);
+        request(&mut server, 9, "continue", r#"{"threadId":1}"#);
+        messages = buffer.take_messages();
+        assert_eq!(body(&messages[0]).get("value").unwrap().as_str(), Some("$07"));
+        assert_eq!(body(&messages[2]).get("reason").unwrap().as_str(), Some("step"));
+        assert_eq!(body(&messages[4]).get("hitBreakpointIds").unwrap().to_string(), "[2]");
+
+        request(&mut server, 10, "variables", r#"{"variablesReference":1}"#);
+        request(&mut server, 11, "readMemory", r#"{"memoryReference":"0x0100","count":3}"#);
+        request(&mut server, 12, "evaluate", r#"{"expression":"A"}"#);
+        messages = buffer.take_messages();
+        assert_eq!(body(&messages[0]).get("variables").unwrap().as_array().unwrap()[3].get("value").unwrap().as_str(), Some("$0105"));
+        assert_eq!(body(&messages[1]).get("data").unwrap().as_str(), Some("hgG9"));
+        assert_eq!(messages[2].get("success"), Some(&JsonValue::Bool(false)));
+
+        request(&mut server, 13, "disconnect", "{}");
+        assert_eq!(server.should_quit(), true);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod debugger_tests {
+    use momulator::debugger::*;
+    use momulator::machine::*;
+    use momulator::symbols::*;
+
+    // main: LDAA #$01, JSR increment, STAA $80, BRA *
+    // increment: INCA, RTS
+    fn create_debugger() -> Debugger {
+        let mut machine = Machine::new();
+        let mut symbols = SymbolTable::new();
+        let mut state: momulator::processor::ProcessorState;
+
+        machine.load_program(0x100, &[0x86, 0x01, 0xBD, 0x01, 0x0A, 0x97, 0x80, 0x20, 0xFE, 0x00, 0x4C, 0x39]);
+        state = machine.get_state();
+        state.stack_pointer = 0x1FF;
+        machine.get_processor_mut().set_state(state);
+        symbols.insert("main", 0x100);
+        symbols.insert("increment", 0x10A);
+
+        Debugger::new(machine, symbols)
+    }
+
+    #[test]
+    fn test_breakpoints_and_stepping() {
+        let mut debugger = create_debugger();
+        let output: String;
+
+        assert_eq!(debugger.execute("b increment"), "Breakpoint 1 set at increment ($010A)");
+        output = debugger.execute("c");
+        assert_eq!(output.starts_with("Breakpoint 1 at increment ($010A)"), true);
+        assert_eq!(debugger.get_machine().get_state().accumulator_a, 1);
+
+        debugger.execute("s 2");
+        assert_eq!(debugger.get_machine().get_state().program_counter, 0x105);
+        assert_eq!(debugger.execute("bd $10A"), "Breakpoint removed from increment ($010A)");
+
+        debugger.execute("c");
+        assert_eq!(debugger.execute("m $80 1"), "0080  02                                               .");
+    }
+
+    #[test]
+    fn test_conditional_breakpoints_and_watchpoints() {
+        let mut debugger = create_debugger();
+        let output: String;
+
+        debugger.execute("b increment if A == 2");
+        assert_eq!(debugger.execute("w $80 change"), "Watchpoint 2 set on changes of $0080");
+        assert_eq!(debugger.execute("bl"), "#1  break at increment ($010A) if A == 2\n#2  watch changes of $0080");
+
+        output = debugger.execute("c");
+        assert_eq!(output.starts_with("Watchpoint 2: main+5 ($0105) wrote $02 to $0080 (was $00)"), true);
+        assert_eq!(debugger.execute("bd #2"), "Removed #2");
+        assert_eq!(debugger.execute("b main if A =="), "Unexpected end of condition");
+    }
+
+    #[test]
+    fn test_next_runs_subroutines_as_one_step() {
+        let mut debugger = create_debugger();
+
+        debugger.execute("n");
+        debugger.execute("n");
+        assert_eq!(debugger.get_machine().get_state().program_counter, 0x105);
+        assert_eq!(debugger.get_machine().get_state().accumulator_a, 2);
+    }
+
+    #[test]
+    fn test_modify_registers_and_memory() {
+        let mut debugger = create_debugger();
+
+        debugger.execute("r pc increment");
+        debugger.execute("r a $41");
+        debugger.execute("e $10A $4A");
+        debugger.execute("s");
+
+        assert_eq!(debugger.get_machine().get_state().accumulator_a, 0x40);
+        assert_eq!(debugger.execute("r q 1"), "Unknown register 'q'");
+        assert_eq!(debugger.execute("d $10A 1"), ">  010A  4A        DECA         increment:".replace('>', " "));
+    }
+}
@@ -34,4 +34,20 @@ mod disassembler_tests {
         let disassembly = disassembly.unwrap_err();
         assert_eq!(disassembly, DisassemblyError::InvalidOpcodeByte);
     }
+
+    #[test]
+    fn test_format_instruction() {
+        let cases: [(&[u8], &str); 6] = [
+            (&[0x80, 0x05], "SUBA #$05"),
+            (&[0xE6, 0x10], "LDAB $10,X"),
+            (&[0xB7, 0x12, 0x34], "STAA $1234"),
+            (&[0x26, 0xFC], "BNE $00FE"),
+            (&[0x1B], "ABA"),
+            (&[0x37], "PSHB")
+        ];
+
+        for (bytes, text) in cases.iter() {
+            assert_eq!(format_instruction(&disassemble_instruction(bytes).unwrap(), 0x100), *text);
+        }
+    }
 }
@@ -0,0 +1,67 @@
+#[cfg(test)]
+mod et3400_tests {
+    use momulator::et3400::*;
+    use momulator::keypad::*;
+    use momulator::machine::*;
+    use momulator::processor::*;
+    use momulator::profile::*;
+    use momulator::seven_segment::*;
+
+    /// A 1K monitor running a program from $FC00 on reset
+    fn build_machine(program: &[u8]) -> Machine {
+        let mut rom: Vec<u8> = vec![0; ROM_SIZE];
+
+        rom[..program.len()].copy_from_slice(program);
+        rom[ROM_SIZE - 2] = 0xFC;
+        rom[ROM_SIZE - 1] = 0x00;
+
+        build(&rom).unwrap()
+    }
+
+    #[test]
+    fn test_display() {
+        // Light segment a of the leftmost digit and the decimal point of the rightmost one
+        let mut machine = build_machine(&[0x86, 0x01, 0xB7, 0xC1, 0x66, 0xB7, 0xC1, 0x1F, 0x20, 0xFE]);
+        let display: &Display;
+
+        machine.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(100)]);
+        display = machine.get_memory_manager().find_device::<Display>().unwrap();
+
+        assert_eq!(display.get_segments(), [SEGMENT_A, 0, 0, 0, 0, DECIMAL_POINT]);
+        assert_eq!(render_display(&machine), format!(" _\n\n{}.", " ".repeat(23)));
+    }
+
+    #[test]
+    fn test_keypad() {
+        // Wait for a key of the row holding 7 and store the row at $00
+        let mut machine = build_machine(&[0xB6, 0xC0, 0x06, 0x81, 0xFF, 0x27, 0xF9, 0x97, 0x00, 0x20, 0xFE]);
+
+        assert_eq!(machine.run(&[StopCondition::InstructionBudget(100)]).stop_reason, StopReason::InstructionBudgetExhausted);
+
+        // 5 is on another row
+        machine.get_memory_manager_mut().find_device_mut::<Keypad>().unwrap().get_keys_mut().press(5);
+        assert_eq!(machine.run(&[StopCondition::InstructionBudget(100)]).stop_reason, StopReason::InstructionBudgetExhausted);
+        machine.get_memory_manager_mut().find_device_mut::<Keypad>().unwrap().get_keys_mut().release(5);
+
+        assert_eq!(press_host_key(&mut machine, b'7'), true);
+        assert_eq!(press_host_key(&mut machine, b'x'), false);
+        machine.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(100)]);
+
+        assert_eq!(machine.read_memory(0x00, 1)[0], 0xFB);
+
+        // The key is released after a while
+        machine.run(&[StopCondition::CycleBudget(DEFAULT_HOLD_CYCLES * 2)]);
+        assert_eq!(machine.get_memory_manager().find_device::<Keypad>().unwrap().get_keys().is_typing(), false);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut machine = build_machine(&[0x20, 0xFE]);
+
+        machine.get_processor_mut().set_program_counter(0x1234);
+        assert_eq!(press_host_key(&mut machine, b'r'), true);
+
+        assert_eq!(machine.get_state().program_counter, ROM_ADDRESS);
+        assert_eq!(matches!(build(&[0; 512]), Err(ProfileError::RomSize(512))), true);
+    }
+}
@@ -0,0 +1,155 @@
+#[cfg(test)]
+mod gdb_stub_tests {
+    use std::io;
+    use std::io::Read;
+    use std::io::Write;
+    use std::net;
+    use std::thread;
+
+    use momulator::gdb_stub::*;
+    use momulator::machine::*;
+    use momulator::semihosting::*;
+
+    // LDAA #$10, loop: STAA $80, DECA, BNE loop, BRA *
+    const PROGRAM: [u8; 9] = [0x86, 0x10, 0x97, 0x80, 0x4A, 0x26, 0xFB, 0x20, 0xFE];
+
+    // LDAA #3, STAA $FF05 (the exit register of a semihosting device at $FF00), BRA *
+    const EXIT_PROGRAM: [u8; 7] = [0x86, 0x03, 0xB7, 0xFF, 0x05, 0x20, 0xFE];
+
+    fn start_stub() -> (net::TcpStream, thread::JoinHandle<Machine>) {
+        let mut machine = Machine::new();
+
+        machine.load_program(0, &PROGRAM);
+
+        serve_machine(machine)
+    }
+
+    fn start_exiting_stub() -> (net::TcpStream, thread::JoinHandle<Machine>) {
+        let mut machine = Machine::new();
+
+        machine.get_memory_manager_mut().attach_device(0xFF00, Box::new(Semihosting::with_console(Box::new(io::empty()), Box::new(io::sink()))));
+        machine.load_program(0, &EXIT_PROGRAM);
+
+        serve_machine(machine)
+    }
+
+    fn serve_machine(machine: Machine) -> (net::TcpStream, thread::JoinHandle<Machine>) {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let handle: thread::JoinHandle<Machine>;
+
+        handle = thread::spawn(move || {
+            let mut stub = GdbStub::new(machine);
+            let (stream, _) = listener.accept().unwrap();
+
+            stub.serve(stream).unwrap();
+            stub.into_machine()
+        });
+
+        (net::TcpStream::connect(address).unwrap(), handle)
+    }
+
+    fn read_reply(stream: &mut net::TcpStream) -> String {
+        let mut byte: [u8; 1] = [0];
+        let mut reply: Vec<u8> = Vec::new();
+        let mut checksum: [u8; 2] = [0; 2];
+
+        loop {
+            stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'$' {
+                break;
+            }
+        }
+
+        loop {
+            stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'#' {
+                break;
+            }
+            reply.push(byte[0]);
+        }
+
+        stream.read_exact(&mut checksum).unwrap();
+        assert_eq!(u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap(),
+                reply.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)));
+        stream.write_all(b"+").unwrap();
+
+        String::from_utf8(reply).unwrap()
+    }
+
+    fn send(stream: &mut net::TcpStream, packet: &str) -> String {
+        let mut acknowledgement: [u8; 1] = [0];
+
+        write!(stream, "${}#{:02x}", packet, packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte))).unwrap();
+        stream.read_exact(&mut acknowledgement).unwrap();
+        assert_eq!(acknowledgement[0], b'+');
+
+        read_reply(stream)
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        let (mut stream, handle) = start_stub();
+        let machine: Machine;
+
+        assert_eq!(send(&mut stream, "qSupported:swbreak+").contains("qXfer:features:read+"), true);
+        assert_eq!(send(&mut stream, "qXfer:features:read:target.xml:0,fff").contains("name=\"ccr\""), true);
+        assert_eq!(send(&mut stream, "?"), "S05");
+        assert_eq!(send(&mut stream, "g"), "000000000000000000");
+        assert_eq!(send(&mut stream, "P4=01ff"), "OK");
+        assert_eq!(send(&mut stream, "p4"), "01ff");
+        assert_eq!(send(&mut stream, "m0,4"), "86109780");
+        assert_eq!(send(&mut stream, "M1,1:05"), "OK");
+        assert_eq!(send(&mut stream, "s"), "S05");
+        assert_eq!(send(&mut stream, "g"), "05000000000201ff00");
+        assert_eq!(send(&mut stream, "vMustReplyEmpty"), "");
+        assert_eq!(send(&mut stream, "D"), "OK");
+
+        machine = handle.join().unwrap();
+        assert_eq!(machine.get_state().accumulator_a, 5);
+        assert_eq!(machine.get_state().stack_pointer, 0x1FF);
+    }
+
+    #[test]
+    fn test_breakpoints_watchpoints_and_interrupts() {
+        let (mut stream, handle) = start_stub();
+
+        assert_eq!(send(&mut stream, "Z0,4,1"), "OK");
+        assert_eq!(send(&mut stream, "c"), "S05");
+        assert_eq!(send(&mut stream, "p0"), "10");
+        assert_eq!(send(&mut stream, "c"), "S05");
+        assert_eq!(send(&mut stream, "p0"), "0f");
+        assert_eq!(send(&mut stream, "z0,4,1"), "OK");
+
+        assert_eq!(send(&mut stream, "Z2,80,1"), "OK");
+        assert_eq!(send(&mut stream, "c"), "T05watch:0080;");
+        assert_eq!(send(&mut stream, "z2,80,1"), "OK");
+
+        // The program ends in BRA *, which only stops when the debugger interrupts it
+        write!(stream, "$c#63").unwrap();
+        stream.write_all(&[0x03]).unwrap();
+        assert_eq!(read_reply(&mut stream), "S02");
+        assert_eq!(send(&mut stream, "p3"), "0007");
+
+        stream.write_all(b"$k#6b").unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_exit() {
+        let (mut stream, handle) = start_exiting_stub();
+        let mut byte: [u8; 1] = [0];
+
+        // The exit status ends the session, whether the program was continued or stepped
+        assert_eq!(send(&mut stream, "s"), "S05");
+        assert_eq!(send(&mut stream, "c"), "W03");
+        handle.join().unwrap();
+        assert_eq!(stream.read(&mut byte).unwrap(), 0);
+
+        let (mut stream, handle) = start_exiting_stub();
+
+        assert_eq!(send(&mut stream, "s"), "S05");
+        assert_eq!(send(&mut stream, "s"), "W03");
+        assert_eq!(handle.join().unwrap().get_state().program_counter, 0x5);
+    }
+}
@@ -0,0 +1,44 @@
+#[cfg(test)]
+mod instruction_cache_tests {
+    use std::sync::Arc;
+
+    use momulator::instruction_cache::*;
+    use momulator::disassembler::*;
+
+    #[test]
+    fn test_hits_and_misses() {
+        let mut cache = InstructionCache::new();
+        let load_a_immediate: [u8; 2] = [0x86, 0x12];
+
+        assert_eq!(cache.get(0x100).is_none(), true);
+        cache.insert(0x100, Arc::new(disassemble_instruction(&load_a_immediate).unwrap()));
+
+        let cached = cache.get(0x100);
+        assert_eq!(cached.is_none(), false);
+        assert_eq!(cached.unwrap().opcode_info.opcode, Opcode::LoadAccumulator);
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(cache.misses(), 1);
+    }
+
+    #[test]
+    fn test_invalidate_overlapping_instructions() {
+        let mut cache = InstructionCache::new();
+        let jump_extended: [u8; 3] = [0x7E, 0x12, 0x34];
+        let nop: [u8; 1] = [0x01];
+
+        // A 3 byte instruction at 0x10 and single byte instructions around it
+        cache.insert(0xF, Arc::new(disassemble_instruction(&nop).unwrap()));
+        cache.insert(0x10, Arc::new(disassemble_instruction(&jump_extended).unwrap()));
+        cache.insert(0x13, Arc::new(disassemble_instruction(&nop).unwrap()));
+
+        // Writing the last byte of the jump only invalidates the jump
+        cache.invalidate(0x12, 1);
+        assert_eq!(cache.contains(0xF), true);
+        assert_eq!(cache.contains(0x10), false);
+        assert_eq!(cache.contains(0x13), true);
+
+        cache.invalidate(0xF, 4);
+        assert_eq!(cache.contains(0xF), false);
+        assert_eq!(cache.contains(0x13), true);
+    }
+}
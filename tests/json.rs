@@ -0,0 +1,30 @@
+#[cfg(test)]
+mod json_tests {
+    use momulator::json::*;
+
+    #[test]
+    fn test_parse_and_serialize() {
+        let text = r#"{"seq": 1, "type": "request", "arguments": {"lines": [1, 2.5, -3e2], "ok": true, "none": null, "text": "a\"b\\cé\n"}}"#;
+        let value = JsonValue::parse(text).unwrap();
+        let arguments = value.get("arguments").unwrap();
+
+        assert_eq!(value.get("seq").unwrap().as_u64(), Some(1));
+        assert_eq!(value.get("type").unwrap().as_str(), Some("request"));
+        assert_eq!(arguments.get("lines").unwrap().as_array().unwrap().len(), 3);
+        assert_eq!(arguments.get("lines").unwrap().as_array().unwrap()[2].as_f64(), Some(-300.0));
+        assert_eq!(arguments.get("ok").unwrap().as_bool(), Some(true));
+        assert_eq!(arguments.get("text").unwrap().as_str(), Some("a\"b\\c\u{e9}\n"));
+        assert_eq!(arguments.get("missing"), None);
+
+        assert_eq!(value.to_string(), r#"{"seq":1,"type":"request","arguments":{"lines":[1,2.5,-300],"ok":true,"none":null,"text":"a\"b\\cé\n"}}"#);
+        assert_eq!(JsonValue::parse(&value.to_string()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(JsonValue::parse("{\"a\": }").is_err(), true);
+        assert_eq!(JsonValue::parse("[1, 2").is_err(), true);
+        assert_eq!(JsonValue::parse("\"unterminated").is_err(), true);
+        assert_eq!(JsonValue::parse("{} x").is_err(), true);
+    }
+}
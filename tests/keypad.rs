@@ -0,0 +1,28 @@
+#[cfg(test)]
+mod keypad_tests {
+    use momulator::keypad::*;
+
+    #[test]
+    fn test_type_keys() {
+        let mut keys = Keys::new();
+
+        keys.set_hold_cycles(100);
+        keys.type_keys(&[3, 20]);
+        assert_eq!(keys.is_typing(), true);
+
+        keys.tick(1);
+        assert_eq!(keys.get_held(), 1 << 3);
+
+        // Held for the hold time, then released for as long before the next key
+        keys.tick(99);
+        assert_eq!(keys.is_held(3), true);
+        keys.tick(1);
+        assert_eq!(keys.get_held(), 0);
+        keys.tick(100);
+        keys.tick(1);
+        assert_eq!(keys.get_held(), 1 << 20);
+
+        keys.tick(100);
+        assert_eq!(keys.is_typing(), false);
+    }
+}
@@ -0,0 +1,38 @@
+#[cfg(test)]
+mod line_table_tests {
+    use momulator::line_table::*;
+
+    #[test]
+    fn test_parse_listing() {
+        let listing = "243: Warning --- Indexed Addressing Assumed\n\
+                       0003 0000                    \t\torg $0\n\
+                       0006                         l_start\n\
+                       0007 0000 7e 00 03           \tjmp l_test_aba_adc\n\
+                       0011 0003 c9 f0  [ 2 ]       \tadcb #$f0\n\
+                       l_start    0000 *0006\n";
+        let line_table = LineTable::parse_listing(listing);
+
+        assert_eq!(line_table.len(), 2);
+        assert_eq!(line_table.get_address(7), Some(0));
+        assert_eq!(line_table.get_line(3), Some(11));
+        assert_eq!(line_table.get_address(3), None);
+        assert_eq!(line_table.find_code_line(8), Some((11, 3)));
+        assert_eq!(line_table.find_code_line(12), None);
+    }
+
+    #[test]
+    fn test_parse_test_program_listing() {
+        let line_table = LineTable::load_listing("./tests/test.lst").unwrap();
+
+        // Every instruction of tests/test.asm, from the jump on line 10 to the exits
+        assert_eq!(line_table.len(), 404);
+        assert_eq!(line_table.get_address(10), Some(0));
+
+        // Lines following the warnings and errors as0 reports in the listing
+        assert_eq!(line_table.get_address(189), Some(0x102));
+        assert_eq!(line_table.get_address(246), Some(0x15E));
+        assert_eq!(line_table.get_address(563), Some(0x34D));
+        assert_eq!(line_table.get_line(0x34B), Some(562));
+        assert_eq!(line_table.find_code_line(560), Some((562, 0x34B)));
+    }
+}
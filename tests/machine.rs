@@ -0,0 +1,45 @@
+#[cfg(test)]
+mod machine_tests {
+    use momulator::machine::*;
+    use momulator::processor::*;
+    use std::thread;
+
+    #[test]
+    fn test_inspect_memory_between_steps() {
+        let mut machine = Machine::new();
+
+        // LDAA #$42, STAA $80, INCA, STAA $80
+        machine.load_program(0x100, &[0x86, 0x42, 0x97, 0x80, 0x4C, 0x97, 0x80]);
+
+        assert_eq!(machine.step().is_err(), false);
+        assert_eq!(machine.step().is_err(), false);
+        assert_eq!(machine.read_memory(0x80, 1)[0], 0x42);
+
+        // Patch the increment into a decrement while the machine exists
+        machine.write_memory(0x104, &[0x4A]);
+        assert_eq!(machine.step().is_err(), false);
+        assert_eq!(machine.step().is_err(), false);
+        assert_eq!(machine.get_memory_manager().read(0x80, 1)[0], 0x41);
+        assert_eq!(machine.get_state().program_counter, 0x107);
+    }
+
+    #[test]
+    fn test_run_on_another_thread() {
+        let mut machine = Machine::new();
+        let result: RunResult;
+
+        // LDAB #$10, DECB, BNE -3, BRA *
+        machine.load_program(0, &[0xC6, 0x10, 0x5A, 0x26, 0xFD, 0x20, 0xFE]);
+
+        machine = thread::spawn(move || {
+            machine.run(&[StopCondition::InfiniteLoop]);
+            machine
+        }).join().unwrap();
+
+        assert_eq!(machine.get_state().accumulator_b, 0);
+        assert_eq!(machine.get_state().program_counter, 0x5);
+
+        result = machine.run(&[StopCondition::InstructionBudget(1)]);
+        assert_eq!(result.stop_reason, StopReason::InstructionBudgetExhausted);
+    }
+}
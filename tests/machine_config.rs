@@ -0,0 +1,133 @@
+#[cfg(test)]
+mod machine_config_tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use momulator::machine_config::*;
+    use momulator::memory_manager::*;
+    use momulator::pia::*;
+    use momulator::processor::*;
+
+    /// A directory holding a 256 byte ROM image for a test, with the reset vector at its end
+    fn write_rom(name: &str, program: &[u8], nmi_handler: &[u8]) -> PathBuf {
+        let directory = env::temp_dir().join(format!("momulator_{}_{}", name, std::process::id()));
+        let mut rom: Vec<u8> = vec![0xFF; 0x100];
+
+        rom[..program.len()].copy_from_slice(program);
+        rom[0x10..0x10 + nmi_handler.len()].copy_from_slice(nmi_handler);
+        rom[0xFC..].copy_from_slice(&[0xF0, 0x10, 0xF0, 0x00]);
+
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("rom.bin"), rom).unwrap();
+
+        directory
+    }
+
+    #[test]
+    fn test_build() {
+        // Print "OK" through the ACIA, then try to clear a byte of ROM and one nothing answers at
+        let directory = write_rom("machine_config_build", &[
+            0x86, 0x03, 0xB7, 0x80, 0x04, 0x86, 0x15, 0xB7, 0x80, 0x04, 0x86, 0x4F, 0xB7, 0x80, 0x05,
+            0x86, 0x4B, 0xB7, 0x80, 0x05, 0x7F, 0xF0, 0x00, 0x7F, 0x40, 0x00, 0x20, 0xFE
+        ], &[]);
+        let config = MachineConfig::parse("
+            name = \"test\"
+            clock_rate = 1000000
+
+            [[ram]]
+            address = 0x0000
+            size = 0x1000
+
+            [[rom]]
+            address = 0xF000
+            size = 0x200
+            file = \"rom.bin\"
+
+            [[mirror]]
+            address = 0xFF00
+            size = 0x100
+            target = 0xF000
+
+            [[device]]
+            type = \"acia\"
+            address = 0x8004
+            interrupt = \"none\"
+            output = \"output.txt\"
+        ", &directory).unwrap();
+        let mut machine = config.build().unwrap();
+
+        assert_eq!(config.rom[0].region, Region { address: 0xF000, size: 0x200 });
+        assert_eq!(config.devices[0].interrupt_line, InterruptLine::None);
+        assert_eq!(machine.get_clock_rate(), Some(1000000));
+        assert_eq!(machine.get_state().program_counter, 0xF000);
+
+        machine.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(100)]);
+
+        assert_eq!(fs::read(directory.join("output.txt")).unwrap(), b"OK".to_vec());
+        assert_eq!(machine.read_memory(0xF000, 1)[0], 0x86);
+        assert_eq!(machine.read_memory(0xF100, 1)[0], 0xFF);
+        assert_eq!(machine.read_memory(0x4000, 1)[0], 0xFF);
+        assert_eq!(machine.read_memory(0xFFFE, 2), &[0xF0, 0x00]);
+    }
+
+    #[test]
+    fn test_nmi_wiring() {
+        // Enable the CA1 interrupt and wait, the NMI handler at $F010 loops
+        let directory = write_rom("machine_config_nmi", &[0x8E, 0x00, 0xFF, 0x86, 0x01, 0xB7, 0x80, 0x01, 0x20, 0xFE], &[0x20, 0xFE]);
+        let config = MachineConfig::parse("
+            [[ram]]
+            address = 0
+            size = 0x100
+
+            [[rom]]
+            address = 0xF000
+            file = \"rom.bin\"
+
+            [[mirror]]
+            address = 0xFF00
+            size = 0x100
+            target = 0xF000
+
+            [[device]]
+            type = \"pia\"
+            address = 0x8000
+            interrupt = \"nmi\"
+        ", &directory).unwrap();
+        let mut machine = config.build().unwrap();
+        let pia: &mut Pia;
+
+        assert_eq!(machine.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(100)]).stop_reason, StopReason::InfiniteLoop(0xF008));
+
+        pia = machine.get_memory_manager_mut().find_device_mut::<Pia>().unwrap();
+        pia.set_c1(Port::A, true);
+        pia.set_c1(Port::A, false);
+
+        assert_eq!(machine.get_memory_manager().get_irq(), false);
+        assert_eq!(machine.get_memory_manager().get_nmi(), true);
+        assert_eq!(machine.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(100)]).stop_reason, StopReason::InfiniteLoop(0xF010));
+    }
+
+    #[test]
+    fn test_errors() {
+        let directory = env::temp_dir();
+        let message = |text: &str| match MachineConfig::parse(text, &directory) {
+            Err(ConfigError::Invalid(message)) | Err(ConfigError::Syntax(message)) => message,
+            other => panic!("unexpected {:?}", other)
+        };
+
+        assert_eq!(message("clock_rate ="), "Expected a value on line 1");
+        assert_eq!(message("[[ram]]\naddres = 0"), "unknown key 'addres' in ram 1");
+        assert_eq!(message("[[ram]]\naddress = 0"), "ram 1 needs 'size'");
+        assert_eq!(message("[[ram]]\naddress = 0xF000\nsize = 0x2000"), "'size' in ram 1 must be a number from 0 to 4096");
+        assert_eq!(message("[[device]]\ntype = \"via\"\naddress = 0"), "unknown device type 'via' in device 1");
+        assert_eq!(message("[[device]]\ntype = \"pia\"\naddress = 0\ninterrupt = \"firq\""),
+                "unknown interrupt 'firq' in device 1, expected irq, nmi or none");
+
+        // Devices may not share addresses with memory or with each other
+        assert_eq!(matches!(MachineConfig::parse("[[ram]]\naddress = 0\nsize = 0x100\n[[device]]\ntype = \"pia\"\naddress = 0xFE",
+                &directory).unwrap().build(), Err(ConfigError::Invalid(message)) if message == "device 1 at $00FE overlaps RAM, ROM or another device"), true);
+        assert_eq!(matches!(MachineConfig::parse("[[device]]\ntype = \"pia\"\naddress = 0x8000\n[[device]]\ntype = \"ptm\"\naddress = 0x8002",
+                &directory).unwrap().build(), Err(ConfigError::Invalid(message)) if message == "device 2 at $8002 overlaps RAM, ROM or another device"), true);
+    }
+}
@@ -0,0 +1,69 @@
+#[cfg(test)]
+mod mek6800d2_tests {
+    use momulator::machine::*;
+    use momulator::mek6800d2::*;
+    use momulator::processor::*;
+    use momulator::profile::*;
+    use momulator::serial::*;
+    use momulator::seven_segment::*;
+
+    // Make PA0-PA6 and PB0-PB5 outputs and select the output registers
+    const SETUP_PANEL: [u8; 18] = [0x86, 0x7F, 0xB7, 0x80, 0x20, 0x86, 0x3F, 0xB7, 0x80, 0x22, 0x86, 0x04, 0xB7, 0x80, 0x21, 0xB7, 0x80, 0x23];
+
+    /// A 1K monitor running a program from $E000 on reset, with a BRA * at $E040 for the NMI
+    fn build_machine(program: &[u8]) -> Machine {
+        let mut rom: Vec<u8> = vec![0; ROM_SIZE];
+
+        rom[..SETUP_PANEL.len()].copy_from_slice(&SETUP_PANEL);
+        rom[SETUP_PANEL.len()..SETUP_PANEL.len() + program.len()].copy_from_slice(program);
+        rom[0x40..0x42].copy_from_slice(&[0x20, 0xFE]);
+        rom[ROM_SIZE - 4..].copy_from_slice(&[0xE0, 0x40, 0xE0, 0x00]);
+
+        build(&rom, Box::new(BufferPort::new())).unwrap()
+    }
+
+    #[test]
+    fn test_display() {
+        // Light the digits one after another with the segments in the table at $40, blanking them
+        // while the segments change
+        let mut machine = build_machine(&[
+            0xCE, 0x00, 0x40, 0xC6, 0x01, 0x7F, 0x80, 0x22, 0xA6, 0x00, 0xB7, 0x80, 0x20, 0xF7, 0x80, 0x22,
+            0x86, 0x20, 0x4A, 0x26, 0xFD, 0x08, 0x58, 0xC1, 0x40, 0x26, 0xEA, 0x20, 0xE3
+        ]);
+        let segments: Vec<u8> = (0..6).map(encode_hex).collect();
+
+        machine.write_memory(0x40, &segments);
+        machine.run(&[StopCondition::CycleBudget(CLOCK_RATE / 10)]);
+
+        assert_eq!(machine.get_memory_manager().find_device::<Panel>().unwrap().get_segments().to_vec(), segments);
+        assert_eq!(render_display(&machine), render(&segments));
+    }
+
+    #[test]
+    fn test_keypad() {
+        // Drive row PA0 low, select column 1 and wait for the sense input, then store port A at $00
+        let mut machine = build_machine(&[0x86, 0x7E, 0xB7, 0x80, 0x20, 0x86, 0x02, 0xB7, 0x80, 0x22, 0xB6, 0x80, 0x20, 0x2B, 0xFB, 0x97, 0x00, 0x20, 0xFE]);
+
+        assert_eq!(machine.run(&[StopCondition::InstructionBudget(200)]).stop_reason, StopReason::InstructionBudgetExhausted);
+
+        // 5 is on the same column but another row
+        machine.get_memory_manager_mut().find_device_mut::<Panel>().unwrap().get_keys_mut().press(5);
+        assert_eq!(machine.run(&[StopCondition::InstructionBudget(200)]).stop_reason, StopReason::InstructionBudgetExhausted);
+
+        assert_eq!(press_host_key(&mut machine, b'1'), true);
+        assert_eq!(machine.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(200)]).stop_reason, StopReason::InfiniteLoop(0xE023));
+        assert_eq!(machine.read_memory(0x00, 1)[0], 0x7E);
+    }
+
+    #[test]
+    fn test_abort_and_reset() {
+        let mut machine = build_machine(&[0x20, 0xFE]);
+
+        assert_eq!(press_host_key(&mut machine, b'!'), true);
+        assert_eq!(machine.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(10)]).stop_reason, StopReason::InfiniteLoop(0xE040));
+
+        assert_eq!(press_host_key(&mut machine, b'#'), true);
+        assert_eq!(machine.get_state().program_counter, ROM_ADDRESS);
+        assert_eq!(matches!(build(&[0; 0x800], Box::new(BufferPort::new())), Err(ProfileError::RomSize(0x800))), true);
+    }
+}
@@ -18,4 +18,43 @@ mod memory_manager_tests {
             i += 1;
         }
     }
+
+    #[test]
+    fn test_rom_and_mirrors() {
+        let mut memory_manager = MemoryManager::new();
+
+        memory_manager.load_rom(0xE000, &[0x7E, 0xE0, 0x00, 0x39]);
+        memory_manager.add_mirror(0xFFFC, 4, 0xE000);
+        memory_manager.add_mirror(0x0100, 0x100, 0x0000);
+
+        // ROM ignores writes, also through a mirror, but not loads
+        memory_manager.write(0xE000, &[0]);
+        memory_manager.write(0xFFFD, &[0]);
+        assert_eq!(memory_manager.read(0xE000, 4), &[0x7E, 0xE0, 0x00, 0x39]);
+        assert_eq!(memory_manager.read(0xFFFC, 4), &[0x7E, 0xE0, 0x00, 0x39]);
+
+        memory_manager.load(0xFFFF, &[0x3F]);
+        assert_eq!(memory_manager.read(0xE003, 1)[0], 0x3F);
+
+        // RAM mirrors show writes on both sides
+        memory_manager.write(0x0110, &[0x55]);
+        memory_manager.write(0x0020, &[0xAA]);
+        assert_eq!(memory_manager.read(0x0010, 1)[0], 0x55);
+        assert_eq!(memory_manager.read(0x0120, 1)[0], 0xAA);
+        assert_eq!(memory_manager.read_byte(0x0110), 0x55);
+        assert_eq!(memory_manager.get_aliases(0x0120), vec![0x0020, 0x0120]);
+    }
+
+    #[test]
+    fn test_wrap_at_top_of_memory() {
+        let mut memory_manager = MemoryManager::new();
+
+        memory_manager.write(0xFFFF, &[1, 2]);
+        assert_eq!(memory_manager.read(0xFFFF, 1)[0], 1);
+        assert_eq!(memory_manager.read(0x0000, 1)[0], 2);
+
+        memory_manager.load(0xFFFE, &[3, 4, 5]);
+        assert_eq!(memory_manager.read(0xFFFE, 4), &[3, 4]);
+        assert_eq!(memory_manager.read(0x0000, 1)[0], 5);
+    }
 }
@@ -0,0 +1,25 @@
+#[cfg(test)]
+mod number_tests {
+    use momulator::number::*;
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("100"), Some(100));
+        assert_eq!(parse_number("$100"), Some(0x100));
+        assert_eq!(parse_number("0xFFfe"), Some(0xFFFE));
+        assert_eq!(parse_number("%1010"), Some(10));
+        assert_eq!(parse_number("0b11"), Some(3));
+
+        assert_eq!(parse_number("FF"), None);
+        assert_eq!(parse_number("$"), None);
+        assert_eq!(parse_number("+1"), None);
+        assert_eq!(parse_number("%102"), None);
+    }
+
+    #[test]
+    fn test_parse_digits() {
+        assert_eq!(parse_digits("ff", 16), Some(0xFF));
+        assert_eq!(parse_digits("$ff", 16), None);
+        assert_eq!(parse_digits("", 16), None);
+    }
+}
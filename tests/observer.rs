@@ -0,0 +1,72 @@
+#[cfg(test)]
+mod observer_tests {
+    use momulator::disassembler::*;
+    use momulator::memory_manager::*;
+    use momulator::observer::*;
+    use momulator::processor::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        instructions: Vec<(u16, u16)>,
+        cycles: u64,
+        reads: Vec<(u16, u8)>,
+        writes: Vec<(u16, u8, u8)>,
+        interrupts: Vec<(InterruptKind, u16, u16, u64)>
+    }
+
+    impl Observer for Recorder {
+        fn after_instruction(&mut self, _instruction_info: &InstructionInfo, before: &ProcessorState, after: &ProcessorState, cycles: u64) {
+            self.instructions.push((before.program_counter, after.program_counter));
+            self.cycles += cycles;
+        }
+
+        fn memory_read(&mut self, address: u16, value: u8) {
+            self.reads.push((address, value));
+        }
+
+        fn memory_write(&mut self, address: u16, value: u8, previous_value: u8) {
+            self.writes.push((address, value, previous_value));
+        }
+
+        fn interrupt(&mut self, kind: InterruptKind, from: u16, state: &ProcessorState, cycles: u64) {
+            self.interrupts.push((kind, from, state.program_counter, cycles));
+        }
+    }
+
+    struct NoOp;
+
+    impl Observer for NoOp {}
+
+    #[test]
+    fn test_observer_callbacks() {
+        let mut memory_manager = MemoryManager::new();
+        let mut processor = Processor::new();
+        let recorder: &Recorder;
+        let id: u32;
+
+        // LDAA $80, INCA, STAA $80, SWI
+        memory_manager.write(0, &[0x96, 0x80, 0x4C, 0x97, 0x80, 0x3F]);
+        memory_manager.write(0x80, &[0x41]);
+        memory_manager.write(SOFTWARE_INTERRUPT_VECTOR, &[0x01, 0x00]);
+        processor.set_memory_manager(&mut memory_manager);
+        processor.set_state(ProcessorState { stack_pointer: 0xFF, ..processor.get_state() });
+
+        id = processor.add_observer(Box::new(Recorder::default()));
+        processor.run(&[StopCondition::InstructionBudget(4)]);
+
+        recorder = processor.get_observer::<Recorder>(id).unwrap();
+        assert_eq!(recorder.instructions, vec![(0, 2), (2, 3), (3, 5), (5, 0x100)]);
+        assert_eq!(recorder.cycles, 3 + 2 + 4 + 12);
+        assert_eq!(recorder.reads, vec![(0x80, 0x41)]);
+        assert_eq!(recorder.writes[0], (0x80, 0x42, 0x41));
+        // The SWI stacks seven bytes, starting with the low byte of the return address
+        assert_eq!(recorder.writes.len(), 8);
+        assert_eq!(recorder.writes[1], (0xFF, 0x06, 0x00));
+        assert_eq!(recorder.interrupts, vec![(InterruptKind::Software, 5, 0x100, 0)]);
+
+        // Asking for the wrong type finds nothing, and removed observers are no longer called
+        assert_eq!(processor.get_observer::<NoOp>(id).is_none(), true);
+        assert_eq!(processor.remove_observer(id).is_some(), true);
+        assert_eq!(processor.get_observer::<Recorder>(id).is_none(), true);
+    }
+}
@@ -0,0 +1,94 @@
+#[cfg(test)]
+mod pia_tests {
+    use momulator::machine::*;
+    use momulator::memory_manager::*;
+    use momulator::pia::*;
+    use momulator::processor::*;
+
+    const PIA_ADDRESS: u16 = 0x8000;
+
+    #[test]
+    fn test_registers() {
+        let mut memory_manager = MemoryManager::new();
+        let id: u32 = memory_manager.attach_device(PIA_ADDRESS, Box::new(Pia::new()));
+
+        // Port A: low nibble outputs, port B: all outputs
+        memory_manager.write(PIA_ADDRESS, &[0x0F, 0x04, 0xFF, 0x04]);
+        memory_manager.write(PIA_ADDRESS, &[0xA5]);
+        memory_manager.write(PIA_ADDRESS + 2, &[0x3C]);
+        memory_manager.get_device_mut::<Pia>(id).unwrap().set_input(Port::A, 0x90);
+
+        assert_eq!(memory_manager.read_byte(PIA_ADDRESS), 0x95);
+        assert_eq!(memory_manager.get_device::<Pia>(id).unwrap().get_pins(Port::B), 0x3C);
+        assert_eq!(memory_manager.get_device::<Pia>(id).unwrap().get_data_direction(Port::A), 0x0F);
+
+        // Back to the data direction register
+        memory_manager.write(PIA_ADDRESS + 1, &[0x00]);
+        assert_eq!(memory_manager.read_byte(PIA_ADDRESS), 0x0F);
+    }
+
+    #[test]
+    fn test_ca1_interrupt() {
+        let mut machine = Machine::new();
+        let id: u32 = machine.get_memory_manager_mut().attach_device(PIA_ADDRESS, Box::new(Pia::new()));
+        let pia: &mut Pia;
+
+        // Port B outputs, CA1 falling edge interrupt, then wait. The handler copies port A to port B.
+        machine.load_program(0, &[
+            0x8E, 0x00, 0xFF, 0x86, 0xFF, 0xB7, 0x80, 0x02, 0x86, 0x04, 0xB7, 0x80, 0x03, 0x86, 0x05, 0xB7, 0x80, 0x01,
+            0x0E, 0x3E, 0x20, 0xFE
+        ]);
+        machine.write_memory(0x40, &[0xB6, 0x80, 0x00, 0xB7, 0x80, 0x02, 0x3B]);
+        machine.write_memory(IRQ_VECTOR, &[0x00, 0x40]);
+        machine.run(&[StopCondition::InstructionBudget(50)]);
+
+        pia = machine.get_memory_manager_mut().get_device_mut(id).unwrap();
+        pia.set_input(Port::A, 0x5A);
+        pia.set_c1(Port::A, true);
+        assert_eq!(pia.get_irq(Port::A), false);
+        pia.set_c1(Port::A, false);
+        assert_eq!(pia.get_irq(Port::A), true);
+
+        assert_eq!(machine.run(&[StopCondition::ProgramCounterEquals(0x14), StopCondition::InstructionBudget(50)]).stop_reason,
+                StopReason::ProgramCounterReached(0x14));
+        assert_eq!(machine.get_memory_manager().get_device::<Pia>(id).unwrap().get_pins(Port::B), 0x5A);
+        assert_eq!(machine.get_memory_manager().get_irq(), false);
+    }
+
+    #[test]
+    fn test_c2_modes() {
+        let mut memory_manager = MemoryManager::new();
+        let id: u32 = memory_manager.attach_device(PIA_ADDRESS, Box::new(Pia::new()));
+
+        // CB2 handshake: low after writing port B, high again on the CB1 active edge
+        memory_manager.write(PIA_ADDRESS + 3, &[0x24]);
+        memory_manager.write(PIA_ADDRESS + 2, &[0x01]);
+        assert_eq!(memory_manager.get_device::<Pia>(id).unwrap().get_c2(Port::B), false);
+        memory_manager.get_device_mut::<Pia>(id).unwrap().set_c1(Port::B, true);
+        memory_manager.get_device_mut::<Pia>(id).unwrap().set_c1(Port::B, false);
+        assert_eq!(memory_manager.get_device::<Pia>(id).unwrap().get_c2(Port::B), true);
+        assert_eq!(memory_manager.read_byte(PIA_ADDRESS + 3), CONTROL_IRQ1_FLAG | 0x24);
+
+        // CA2 pulse: low for a cycle after reading port A
+        memory_manager.write(PIA_ADDRESS + 1, &[0x2C]);
+        memory_manager.read_byte(PIA_ADDRESS);
+        assert_eq!(memory_manager.get_device::<Pia>(id).unwrap().get_c2(Port::A), false);
+        memory_manager.tick(1);
+        assert_eq!(memory_manager.get_device::<Pia>(id).unwrap().get_c2(Port::A), true);
+
+        // CA2 set and reset
+        memory_manager.write(PIA_ADDRESS + 1, &[0x34]);
+        assert_eq!(memory_manager.get_device::<Pia>(id).unwrap().get_c2(Port::A), false);
+        memory_manager.write(PIA_ADDRESS + 1, &[0x3C]);
+        assert_eq!(memory_manager.get_device::<Pia>(id).unwrap().get_c2(Port::A), true);
+
+        // CA2 rising edge interrupt
+        memory_manager.write(PIA_ADDRESS + 1, &[0x1C]);
+        memory_manager.get_device_mut::<Pia>(id).unwrap().set_c2(Port::A, true);
+        memory_manager.refresh_devices();
+        assert_eq!(memory_manager.read(PIA_ADDRESS + 1, 1)[0], CONTROL_IRQ2_FLAG | 0x1C);
+        assert_eq!(memory_manager.get_irq(), true);
+        memory_manager.read_byte(PIA_ADDRESS);
+        assert_eq!(memory_manager.get_irq(), false);
+    }
+}
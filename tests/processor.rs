@@ -1,9 +1,14 @@
+// Some tests keep results they do not check yet, and check errors before unwrapping them
+#![allow(unused_variables, unused_assignments, clippy::unnecessary_unwrap)]
+
 #[cfg(test)]
 mod processor_tests {
     use momulator::processor::*;
     use momulator::memory_manager::*;
     use momulator::disassembler::*;
+    use momulator::semihosting::*;
     use std::fs;
+    use std::io;
     
     // TODO: Add B To A Test
 
@@ -66,17 +71,22 @@ mod processor_tests {
         assert_eq!(state.accumulator_a as i8, -0x15);
     }
 
+    // tests/test.asm reports its result through the exit register of a semihosting device at $FF00
+    fn attach_semihosting(memory_manager: &mut MemoryManager) {
+        memory_manager.attach_device(0xFF00, Box::new(Semihosting::with_console(Box::new(io::empty()), Box::new(io::sink()))));
+    }
+
     #[test]
     fn processor_test_test_program() {
         let mut memory_manager = MemoryManager::new();
         let mut processor = Processor::new();
         let mut emulation_result: Result<InstructionInfo, EmulationError>;
         let mut state: ProcessorState;
-        let error_address = 0x345;
-        let success_address = error_address + 3;
+        let mut exit_code: Option<u8> = None;
         let mut i = 0;
 
         memory_manager.write(0, fs::read("./tests/test.bin").unwrap().as_slice());
+        attach_semihosting(&mut memory_manager);
         processor.set_memory_manager(&mut memory_manager);
 
         while i < 2000 {
@@ -92,13 +102,255 @@ mod processor_tests {
                 assert_eq!(true, false);
             }
 
-            if state.program_counter == success_address {
+            exit_code = processor.get_memory_manager().unwrap().find_device::<Semihosting>().unwrap().get_exit_code();
+
+            if exit_code.is_some() {
                 break;
             }
-
-            assert_ne!(state.program_counter, error_address);
             
             i += 1
         }
+
+        assert_eq!(exit_code, Some(0));
+    }
+
+    // Run a program which rewrites the operand of its first instruction and then loops back to it
+    fn run_self_modifying_program(processor: &mut Processor) -> ProcessorState {
+        let mut i = 0;
+
+        while i < 5 {
+            assert_eq!(processor.emulate_instruction().is_err(), false);
+            i += 1;
+        }
+
+        processor.get_state()
+    }
+
+    // LDAB #1, LDAA #5, STAA $01, BRA $00
+    const SELF_MODIFYING_PROGRAM: [u8; 8] = [0xC6, 0x01, 0x86, 0x05, 0x97, 0x01, 0x20, 0xF8];
+
+    #[test]
+    fn processor_test_instruction_cache_self_modifying_code() {
+        let mut memory_manager = MemoryManager::new();
+        let mut processor = Processor::new();
+        let state: ProcessorState;
+
+        memory_manager.write(0, &SELF_MODIFYING_PROGRAM);
+        processor.set_memory_manager(&mut memory_manager);
+
+        state = run_self_modifying_program(&mut processor);
+
+        // The modified instruction must have been decoded again after the write
+        assert_eq!(state.accumulator_b, 0x5);
+        assert_eq!(processor.get_instruction_cache().unwrap().misses(), 5);
+        assert_eq!(processor.get_instruction_cache().unwrap().contains(0), true);
+        assert_eq!(processor.get_instruction_cache().unwrap().contains(4), true);
+    }
+
+    #[test]
+    fn processor_test_instruction_cache_disabled() {
+        let mut memory_manager = MemoryManager::new();
+        let mut processor = Processor::new();
+        let state: ProcessorState;
+
+        memory_manager.write(0, &SELF_MODIFYING_PROGRAM);
+        processor.set_instruction_cache_enabled(false);
+        processor.set_memory_manager(&mut memory_manager);
+
+        state = run_self_modifying_program(&mut processor);
+
+        assert_eq!(state.accumulator_b, 0x5);
+        assert_eq!(processor.get_instruction_cache().is_none(), true);
+    }
+
+    #[test]
+    fn processor_test_run_test_program() {
+        let mut memory_manager = MemoryManager::new();
+        let mut processor = Processor::new();
+        let result: RunResult;
+
+        memory_manager.write(0, fs::read("./tests/test.bin").unwrap().as_slice());
+        attach_semihosting(&mut memory_manager);
+        processor.set_memory_manager(&mut memory_manager);
+
+        result = processor.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(2000)]);
+
+        assert_eq!(result.stop_reason, StopReason::Exited(0));
+        assert_eq!(result.statistics.cycles, processor.get_cycles());
+    }
+
+    #[test]
+    fn processor_test_run_stop_reasons() {
+        let mut memory_manager = MemoryManager::new();
+        let mut processor = Processor::new();
+        let mut result: RunResult;
+
+        // NOP, NOP, BRA *, followed by an illegal opcode at 0x10
+        memory_manager.write(0, &[0x01, 0x01, 0x20, 0xFE]);
+        memory_manager.write(0x10, &[0x01, 0x02]);
+        processor.set_memory_manager(&mut memory_manager);
+
+        result = processor.run(&[StopCondition::InfiniteLoop]);
+        assert_eq!(result.stop_reason, StopReason::InfiniteLoop(0x2));
+        assert_eq!(result.statistics, RunStatistics { instructions: 3, cycles: 6 });
+
+        processor.set_program_counter(0);
+        result = processor.run(&[StopCondition::CycleBudget(2)]);
+        assert_eq!(result.stop_reason, StopReason::CycleBudgetExhausted);
+        assert_eq!(result.statistics.instructions, 2);
+
+        processor.set_program_counter(0);
+        result = processor.run(&[StopCondition::InstructionBudget(10)]);
+        assert_eq!(result.stop_reason, StopReason::InstructionBudgetExhausted);
+
+        processor.set_program_counter(0x10);
+        result = processor.run(&[]);
+        assert_eq!(result.stop_reason, StopReason::IllegalOpcode(0x11));
+        assert_eq!(result.statistics.instructions, 1);
+    }
+
+    #[test]
+    fn processor_test_nmi() {
+        let mut memory_manager = MemoryManager::new();
+        let mut processor = Processor::new();
+        let result: RunResult;
+
+        // LDS #$FF, SEI, BRA *, with an NMI handler of INC $80, RTI
+        memory_manager.write(0, &[0x8E, 0x00, 0xFF, 0x0F, 0x20, 0xFE]);
+        memory_manager.write(0x200, &[0x7C, 0x00, 0x80, 0x3B]);
+        memory_manager.write(NMI_VECTOR, &[0x02, 0x00]);
+        processor.set_memory_manager(&mut memory_manager);
+
+        assert_eq!(processor.run(&[StopCondition::InfiniteLoop]).stop_reason, StopReason::InfiniteLoop(0x4));
+
+        // The NMI is serviced even with interrupts masked, once per edge
+        processor.trigger_nmi();
+        assert_eq!(processor.emulate_instruction().is_err(), false);
+        assert_eq!(processor.get_state().program_counter, 0x203);
+        assert_eq!(processor.get_state().stack_pointer, 0xF8);
+
+        result = processor.run(&[StopCondition::InfiniteLoop]);
+        assert_eq!(result.stop_reason, StopReason::InfiniteLoop(0x4));
+        assert_eq!(processor.get_memory_manager().unwrap().read(0x80, 1)[0], 1);
+        assert_eq!(processor.get_state().stack_pointer, 0xFF);
+        assert_eq!(processor.get_interrupt_state().nmi_pending, false);
+    }
+
+    #[test]
+    fn processor_test_top_of_memory() {
+        let mut memory_manager = MemoryManager::new();
+        let mut processor = Processor::new();
+        let result: RunResult;
+
+        // NOP at $FFFE, then LDAA #$42 with its operand wrapping around to $0000, and BRA *
+        memory_manager.write(0xFFFE, &[0x01, 0x86]);
+        memory_manager.write(0, &[0x42, 0x20, 0xFE]);
+        processor.set_memory_manager(&mut memory_manager);
+        processor.set_program_counter(0xFFFE);
+
+        result = processor.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(10)]);
+
+        assert_eq!(result.stop_reason, StopReason::InfiniteLoop(0x1));
+        assert_eq!(processor.get_state().accumulator_a, 0x42);
+        assert_eq!(processor.get_memory_manager().unwrap().read(0xFFFE, 4), &[0x01, 0x86]);
+    }
+
+    #[test]
+    fn processor_test_interrupts() {
+        let mut memory_manager = MemoryManager::new();
+        let mut processor = Processor::new();
+        let mut result: RunResult;
+
+        // LDS #$FF, CLI, SWI, WAI, BRA *
+        memory_manager.write(0, &[0x8E, 0x00, 0xFF, 0x0E, 0x3F, 0x3E, 0x20, 0xFE]);
+        // Both handlers: INC $80, RTI
+        memory_manager.write(0x200, &[0x7C, 0x00, 0x80, 0x3B]);
+        memory_manager.write(SOFTWARE_INTERRUPT_VECTOR, &[0x02, 0x00]);
+        memory_manager.write(IRQ_VECTOR, &[0x02, 0x00]);
+        processor.set_memory_manager(&mut memory_manager);
+
+        // The software interrupt returns to the WAI, which waits forever without an interrupt but
+        // is not taken for an infinite loop
+        result = processor.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(10)]);
+        assert_eq!(result.stop_reason, StopReason::InstructionBudgetExhausted);
+        assert_eq!(processor.get_state().program_counter, 0x5);
+        assert_eq!(processor.get_memory_manager().unwrap().read(0x80, 1)[0], 1);
+        assert_eq!(processor.get_interrupt_state().waiting_for_interrupt, true);
+        assert_eq!(processor.get_state().stack_pointer, 0xF8);
+
+        processor.set_irq_line(true);
+        assert_eq!(processor.emulate_instruction().is_err(), false);
+        processor.set_irq_line(false);
+        assert_eq!(processor.get_state().get_condition_code_flag(ConditionCodeFlag::InterruptMask), true);
+
+        result = processor.run(&[StopCondition::InfiniteLoop]);
+        assert_eq!(result.stop_reason, StopReason::InfiniteLoop(0x6));
+        assert_eq!(processor.get_memory_manager().unwrap().read(0x80, 1)[0], 2);
+        assert_eq!(processor.get_state().stack_pointer, 0xFF);
+        assert_eq!(processor.get_state().get_condition_code_flag(ConditionCodeFlag::InterruptMask), false);
+    }
+
+    #[test]
+    fn processor_test_step_over_and_out() {
+        let mut memory_manager = MemoryManager::new();
+        let mut processor = Processor::new();
+        let mut result: RunResult;
+
+        // LDS #$FF, LDAA #2, BSR count, NOP, BRA *
+        memory_manager.write(0, &[0x8E, 0x00, 0xFF, 0x86, 0x02, 0x8D, 0x03, 0x01, 0x20, 0xFE]);
+        // count: DECA, BEQ done, BSR count, done: RTS
+        memory_manager.write(0x0A, &[0x4A, 0x27, 0x02, 0x8D, 0xFB, 0x39]);
+        // NMI handler: INC $80, RTI
+        memory_manager.write(0x200, &[0x7C, 0x00, 0x80, 0x3B]);
+        memory_manager.write(NMI_VECTOR, &[0x02, 0x00]);
+        processor.set_memory_manager(&mut memory_manager);
+
+        // The whole recursion is a single step
+        processor.run(&[StopCondition::ProgramCounterEquals(0x05)]);
+        result = processor.step_over(&[]);
+        assert_eq!(result.stop_reason, StopReason::Returned(0x07));
+        assert_eq!(result.statistics.instructions, 8);
+        assert_eq!(processor.get_state().accumulator_a, 0);
+        assert_eq!(processor.get_state().stack_pointer, 0xFF);
+
+        // An interrupt arriving before the NOP is run through as well
+        processor.trigger_nmi();
+        result = processor.step_over(&[]);
+        assert_eq!(result.stop_reason, StopReason::Stepped);
+        assert_eq!(processor.get_state().program_counter, 0x08);
+        assert_eq!(processor.get_memory_manager().unwrap().read(0x80, 1)[0], 1);
+
+        // Stepping out of the inner call of the recursion returns to the outer one
+        processor.set_program_counter(0x03);
+        processor.run(&[StopCondition::ProgramCounterEquals(0x0D)]);
+        processor.emulate_instruction().unwrap();
+        assert_eq!(processor.get_call_stack().depth(), 2);
+        assert_eq!(processor.step_out(&[]).unwrap().stop_reason, StopReason::Returned(0x0F));
+        assert_eq!(processor.get_call_stack().depth(), 1);
+        assert_eq!(processor.step_out(&[]).unwrap().stop_reason, StopReason::Returned(0x07));
+        assert_eq!(processor.step_out(&[]).is_none(), true);
+    }
+
+    #[test]
+    fn processor_test_step_over_with_stack_at_zero() {
+        let mut memory_manager = MemoryManager::new();
+        let mut processor = Processor::new();
+        let mut result: RunResult;
+
+        // JSR $0110, NOP, BRA * with the stack pointer left at $0000, so the call stacks its
+        // return address at $0000 and $FFFF; the subroutine is a lone RTS
+        memory_manager.write(0x100, &[0xBD, 0x01, 0x10, 0x01, 0x20, 0xFE]);
+        memory_manager.write(0x110, &[0x39]);
+        processor.set_memory_manager(&mut memory_manager);
+        processor.set_program_counter(0x100);
+
+        result = processor.step_over(&[StopCondition::InstructionBudget(100)]);
+        assert_eq!(result.stop_reason, StopReason::Returned(0x103));
+        assert_eq!(result.statistics.instructions, 2);
+        assert_eq!(processor.get_state().stack_pointer, 0);
+
+        result = processor.step_over(&[StopCondition::InstructionBudget(100)]);
+        assert_eq!(result.stop_reason, StopReason::Stepped);
+        assert_eq!(processor.get_state().program_counter, 0x104);
     }
 }
@@ -0,0 +1,100 @@
+mod common;
+
+#[cfg(test)]
+mod profiler_tests {
+    use std::fs;
+
+    use momulator::machine::*;
+    use momulator::processor::*;
+    use momulator::profiler::*;
+    use momulator::symbols::*;
+
+    use super::common;
+
+    // main: LDS #$FF, JSR outer, BRA *; outer: BSR inner, RTS; inner: NOP, RTS
+    fn run_program() -> (Machine, SymbolTable, u32) {
+        let mut machine = Machine::new();
+        let mut symbols = SymbolTable::new();
+        let id: u32;
+
+        machine.load_program(0, &[0x8E, 0x00, 0xFF, 0xBD, 0x00, 0x10, 0x20, 0xFE]);
+        machine.write_memory(0x10, &[0x8D, 0x01, 0x39, 0x01, 0x39]);
+        symbols.insert("main", 0x00);
+        symbols.insert("outer", 0x10);
+        symbols.insert("inner", 0x13);
+
+        (machine, id) = common::run_observed(machine, Box::new(Profiler::new()));
+
+        (machine, symbols, id)
+    }
+
+    #[test]
+    fn test_routine_cycles() {
+        let (machine, _, id) = run_program();
+        let profiler: &Profiler = machine.get_processor().get_observer(id).unwrap();
+        let routines: Vec<RoutineProfile> = profiler.get_routines();
+
+        assert_eq!(routines.len(), 3);
+        assert_eq!(routines[1], RoutineProfile { address: 0x10, calls: 1, self_cycles: 13, inclusive_cycles: 19 });
+        assert_eq!(routines[2], RoutineProfile { address: 0x13, calls: 1, self_cycles: 6, inclusive_cycles: 6 });
+        assert_eq!(routines[0].address, 0);
+        assert_eq!(routines[0].inclusive_cycles, profiler.get_total_cycles());
+        assert_eq!(profiler.get_instructions()[0], InstructionProfile { address: 3, executions: 1, cycles: 9 });
+    }
+
+    #[test]
+    fn test_folded_stacks() {
+        let (machine, symbols, id) = run_program();
+        let profiler: &Profiler = machine.get_processor().get_observer(id).unwrap();
+        let folded: String = profiler.to_folded(&symbols);
+
+        assert_eq!(folded.contains("main;outer 13\n"), true);
+        assert_eq!(folded.contains("main;outer;inner 6\n"), true);
+        assert_eq!(profiler.to_folded(&SymbolTable::new()).contains("$0000;$0010;$0013 6\n"), true);
+        assert_eq!(profiler.format_report(&symbols, 1).lines().count(), 7);
+    }
+
+    #[test]
+    fn test_interrupt_cycles() {
+        let mut machine = Machine::new();
+        let profiler: &Profiler;
+        let id: u32;
+
+        // LDS #$FF, CLI, WAI, BRA *, with an IRQ handler of NOP, RTI at $20
+        machine.load_program(0, &[0x8E, 0x00, 0xFF, 0x0E, 0x3E, 0x20, 0xFE]);
+        machine.write_memory(0x20, &[0x01, 0x3B]);
+        machine.write_memory(IRQ_VECTOR, &[0x00, 0x20]);
+        id = machine.get_processor_mut().add_observer(Box::new(Profiler::new()));
+
+        // The first interrupt wakes up the WAI, which stacked the registers already, the second
+        // one interrupts the loop and has to stack them: the handler runs twice (NOP and RTI,
+        // 11 cycles) and is charged one stacking (12 cycles). The WAI is reached after three
+        // instructions, the BRA * when the handler returned.
+        for _ in 0..2 {
+            machine.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(3)]);
+            machine.get_processor_mut().set_irq_line(true);
+            assert_eq!(machine.get_processor_mut().emulate_instruction().is_err(), false);
+            machine.get_processor_mut().set_irq_line(false);
+        }
+
+        machine.run(&[StopCondition::InfiniteLoop]);
+        profiler = machine.get_processor().get_observer(id).unwrap();
+
+        assert_eq!(profiler.get_routines()[1], RoutineProfile { address: 0x20, calls: 2, self_cycles: 34, inclusive_cycles: 34 });
+        assert_eq!(profiler.get_total_cycles(), machine.get_processor().get_cycles());
+    }
+
+    /// Profile tests/test.asm with the symbols of its as0 listing, the way the command line does
+    #[test]
+    fn test_command_line_profile() {
+        let directory = common::create_directory("profiler");
+        let report_path = directory.join("profile.txt");
+        let folded_path = directory.join("test.folded");
+        let output = common::run_test_program(&["--symbols", "tests/test.lst", "--profile", report_path.to_str().unwrap(),
+                "--folded", folded_path.to_str().unwrap()]);
+
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(fs::read_to_string(report_path).unwrap().contains("1  l_test_func ($00F4)\n"), true);
+        assert_eq!(fs::read_to_string(folded_path).unwrap().contains("l_start;l_test_func 9\n"), true);
+    }
+}
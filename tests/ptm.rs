@@ -0,0 +1,128 @@
+#[cfg(test)]
+mod ptm_tests {
+    use momulator::device::Device;
+    use momulator::machine::*;
+    use momulator::memory_manager::*;
+    use momulator::processor::*;
+    use momulator::ptm::*;
+
+    const PTM_ADDRESS: u16 = 0x8010;
+
+    #[test]
+    fn test_continuous_mode() {
+        let mut memory_manager = MemoryManager::new();
+        let id: u32 = memory_manager.attach_device(PTM_ADDRESS, Box::new(Ptm::new()));
+        let ptm = |memory_manager: &MemoryManager| memory_manager.get_device::<Ptm>(id).unwrap().clone();
+
+        // Timer 2 on the internal clock with its output enabled, period 5, then leave reset
+        memory_manager.write(PTM_ADDRESS + 1, &[0x83]);
+        memory_manager.write(PTM_ADDRESS + 4, &[0x00, 0x04]);
+        memory_manager.write(PTM_ADDRESS, &[0x00]);
+
+        memory_manager.tick(4);
+        assert_eq!(ptm(&memory_manager).get_counter(2), 0);
+        assert_eq!(ptm(&memory_manager).get_output(2), false);
+        memory_manager.tick(1);
+        assert_eq!(ptm(&memory_manager).get_output(2), true);
+        assert_eq!(ptm(&memory_manager).get_counter(2), 4);
+        memory_manager.tick(5);
+        assert_eq!(ptm(&memory_manager).get_output(2), false);
+
+        // The flag is set without raising an interrupt, and cleared by reading the status then
+        // the counter
+        assert_eq!(memory_manager.read_byte(PTM_ADDRESS + 1), 0x02);
+        assert_eq!(memory_manager.get_irq(), false);
+        memory_manager.read_byte(PTM_ADDRESS + 4);
+        assert_eq!(memory_manager.read_byte(PTM_ADDRESS + 5), 4);
+        assert_eq!(memory_manager.read_byte(PTM_ADDRESS + 1), 0x00);
+    }
+
+    #[test]
+    fn test_timer_interrupt() {
+        let mut machine = Machine::new();
+
+        machine.get_memory_manager_mut().attach_device(PTM_ADDRESS, Box::new(Ptm::new()));
+
+        // Interrupt every 100 cycles from timer 1 and wait for it in a loop. The handler
+        // acknowledges the interrupt and counts at $50.
+        machine.load_program(0, &[
+            0x8E, 0x00, 0xFF, 0xCE, 0x00, 0x63, 0xFF, 0x80, 0x12, 0x86, 0x01, 0xB7, 0x80, 0x11, 0x86, 0x42, 0xB7, 0x80, 0x10,
+            0x0E, 0x3E, 0x20, 0xFD
+        ]);
+        machine.write_memory(0x40, &[0xB6, 0x80, 0x11, 0xB6, 0x80, 0x12, 0x7C, 0x00, 0x50, 0x3B]);
+        machine.write_memory(IRQ_VECTOR, &[0x00, 0x40]);
+
+        machine.run(&[StopCondition::CycleBudget(1100)]);
+
+        assert_eq!(machine.read_memory(0x50, 1)[0], 10);
+        assert_eq!(machine.get_memory_manager().find_device::<Ptm>().unwrap().get_status(), 0);
+    }
+
+    #[test]
+    fn test_wait_for_timer_interrupt() {
+        let mut machine = Machine::new();
+        let result: RunResult;
+
+        machine.get_memory_manager_mut().attach_device(PTM_ADDRESS, Box::new(Ptm::new()));
+
+        // The same program, waiting at a WAI between the interrupts, run with the infinite loop
+        // check of the run command: the WAI keeps waking up instead of stopping the run
+        machine.load_program(0, &[
+            0x8E, 0x00, 0xFF, 0xCE, 0x00, 0x63, 0xFF, 0x80, 0x12, 0x86, 0x01, 0xB7, 0x80, 0x11, 0x86, 0x42, 0xB7, 0x80, 0x10,
+            0x0E, 0x3E, 0x20, 0xFD
+        ]);
+        machine.write_memory(0x40, &[0xB6, 0x80, 0x11, 0xB6, 0x80, 0x12, 0x7C, 0x00, 0x50, 0x3B]);
+        machine.write_memory(IRQ_VECTOR, &[0x00, 0x40]);
+
+        result = machine.run(&[StopCondition::InfiniteLoop, StopCondition::CycleBudget(1100)]);
+
+        assert_eq!(result.stop_reason, StopReason::CycleBudgetExhausted);
+        assert_eq!(machine.read_memory(0x50, 1)[0], 10);
+    }
+
+    #[test]
+    fn test_waveforms() {
+        let mut memory_manager = MemoryManager::new();
+        let id: u32 = memory_manager.attach_device(PTM_ADDRESS, Box::new(Ptm::new()));
+        let mut outputs: Vec<bool> = Vec::new();
+
+        // Timer 3 in dual 8-bit mode: 3 periods of 4 clocks low, then one high. Timer 2 single-shot.
+        memory_manager.write(PTM_ADDRESS, &[0x86]);
+        memory_manager.write(PTM_ADDRESS + 6, &[0x02, 0x03]);
+        memory_manager.write(PTM_ADDRESS + 1, &[0xA3]);
+        memory_manager.write(PTM_ADDRESS + 4, &[0x00, 0x02]);
+        memory_manager.write(PTM_ADDRESS, &[0x00]);
+        assert_eq!(memory_manager.get_device::<Ptm>(id).unwrap().get_output(2), true);
+
+        for _ in 0..12 {
+            memory_manager.tick(1);
+            outputs.push(memory_manager.get_device::<Ptm>(id).unwrap().get_output(3));
+        }
+
+        assert_eq!(outputs, [vec![false; 7], vec![true; 4], vec![false]].concat());
+        assert_eq!(memory_manager.get_device::<Ptm>(id).unwrap().get_status(), 0x06);
+        assert_eq!(memory_manager.get_device::<Ptm>(id).unwrap().get_output(2), false);
+    }
+
+    #[test]
+    fn test_pulse_width_comparison() {
+        let mut ptm = Ptm::new();
+
+        // Timer 1 interrupts when the gate pulse is shorter than 11 clocks
+        ptm.set_gate(1, true);
+        ptm.write(1, 0x01);
+        ptm.write(2, 0x00);
+        ptm.write(3, 0x0A);
+        ptm.write(0, 0x5A);
+
+        ptm.set_gate(1, false);
+        ptm.tick(20);
+        ptm.set_gate(1, true);
+        assert_eq!(ptm.get_status(), 0);
+
+        ptm.set_gate(1, false);
+        ptm.tick(5);
+        ptm.set_gate(1, true);
+        assert_eq!(ptm.get_status(), STATUS_INTERRUPT_REQUEST | 0x01);
+    }
+}
@@ -0,0 +1,51 @@
+#[cfg(test)]
+mod rewind_buffer_tests {
+    use momulator::machine::*;
+    use momulator::processor::*;
+
+    // LDAA #$11, STAA $80, INCA, STAA $80, INC $80, BRA *
+    const PROGRAM: [u8; 12] = [0x86, 0x11, 0x97, 0x80, 0x4C, 0x97, 0x80, 0x7C, 0x00, 0x80, 0x20, 0xFE];
+
+    #[test]
+    fn test_step_back_restores_registers_and_memory() {
+        let mut machine = Machine::new();
+        let cycles_after_first_store: u64;
+
+        machine.load_program(0, &PROGRAM);
+        machine.get_processor_mut().enable_rewind(16);
+
+        machine.run(&[StopCondition::InstructionBudget(2)]);
+        cycles_after_first_store = machine.get_processor().get_cycles();
+        machine.run(&[StopCondition::InfiniteLoop]);
+        assert_eq!(machine.read_memory(0x80, 1)[0], 0x13);
+
+        // Undo BRA * and INC $80
+        assert_eq!(machine.get_processor_mut().step_back(), true);
+        assert_eq!(machine.get_processor_mut().step_back(), true);
+        assert_eq!(machine.read_memory(0x80, 1)[0], 0x12);
+        assert_eq!(machine.get_state().program_counter, 0x7);
+
+        assert_eq!(machine.get_processor_mut().run_back_to(0x4), true);
+        assert_eq!(machine.get_state().accumulator_a, 0x11);
+        assert_eq!(machine.read_memory(0x80, 1)[0], 0x11);
+        assert_eq!(machine.get_processor().get_cycles(), cycles_after_first_store);
+
+        // Running forward again gives the same result
+        machine.run(&[StopCondition::InfiniteLoop]);
+        assert_eq!(machine.read_memory(0x80, 1)[0], 0x13);
+    }
+
+    #[test]
+    fn test_rewind_buffer_is_bounded() {
+        let mut machine = Machine::new();
+
+        machine.load_program(0, &PROGRAM);
+        machine.get_processor_mut().enable_rewind(2);
+        machine.run(&[StopCondition::InfiniteLoop]);
+
+        assert_eq!(machine.get_processor().get_rewind_buffer().unwrap().len(), 2);
+        assert_eq!(machine.get_processor_mut().run_back_to(0x0), false);
+        assert_eq!(machine.get_state().program_counter, 0x7);
+        assert_eq!(machine.get_processor_mut().step_back(), false);
+    }
+}
@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod save_state_tests {
+    use momulator::device::Device;
+    use momulator::machine::*;
+    use momulator::processor::*;
+    use momulator::ptm::Ptm;
+    use momulator::save_state::*;
+
+    #[test]
+    fn test_save_and_restore_machine() {
+        let mut machine = Machine::new();
+        let mut restored = Machine::new();
+        let saved: SaveState;
+        let bytes: Vec<u8>;
+
+        // LDS #$1FF, LDAA #$12, LDAB #$34, PSHA, BRA *
+        machine.load_program(0x100, &[0x8E, 0x01, 0xFF, 0x86, 0x12, 0xC6, 0x34, 0x36, 0x20, 0xFE]);
+        machine.run(&[StopCondition::InstructionBudget(4)]);
+        machine.get_processor_mut().set_irq_line(true);
+
+        saved = machine.save_state();
+        bytes = saved.to_bytes();
+        restored.load_state(&SaveState::from_bytes(&bytes).unwrap()).unwrap();
+
+        assert_eq!(restored.get_state().accumulator_a, 0x12);
+        assert_eq!(restored.get_state().accumulator_b, 0x34);
+        assert_eq!(restored.get_state().stack_pointer, 0x1FE);
+        assert_eq!(restored.get_state().program_counter, 0x108);
+        assert_eq!(restored.get_processor().get_cycles(), machine.get_processor().get_cycles());
+        assert_eq!(restored.get_processor().get_interrupt_state().irq_line, true);
+        assert_eq!(restored.read_memory(0x1FF, 1)[0], 0x12);
+
+        // The restored machine continues exactly where the original one stopped
+        restored.get_processor_mut().set_irq_line(false);
+        assert_eq!(restored.run(&[StopCondition::InfiniteLoop]).stop_reason, StopReason::InfiniteLoop(0x108));
+    }
+
+    #[test]
+    fn test_load_state_drops_history() {
+        let mut machine = Machine::new();
+        let saved: SaveState;
+
+        // LDS #$1FF, JSR $0110, BRA * with RTS at $0110
+        machine.load_program(0x100, &[0x8E, 0x01, 0xFF, 0xBD, 0x01, 0x10, 0x20, 0xFE]);
+        machine.write_memory(0x110, &[0x39]);
+        saved = machine.save_state();
+
+        machine.get_processor_mut().enable_rewind(16);
+        machine.run(&[StopCondition::InstructionBudget(2)]);
+
+        assert_eq!(machine.get_processor().get_call_stack().depth(), 1);
+        assert_eq!(machine.get_processor().get_rewind_buffer().unwrap().len(), 2);
+
+        machine.load_state(&saved).unwrap();
+
+        assert_eq!(machine.get_processor().get_call_stack().depth(), 0);
+        assert_eq!(machine.get_processor().get_rewind_buffer().unwrap().is_empty(), true);
+    }
+
+    #[test]
+    fn test_load_state_for_another_machine() {
+        let mut machine = Machine::new();
+        let mut other = Machine::new();
+        let mut saved: SaveState;
+        let timer_state: Vec<u8>;
+
+        // The timer at $8010 is on both machines, the one at $8020 only on the other one
+        machine.get_memory_manager_mut().attach_device(0x8010, Box::new(Ptm::new()));
+        other.get_memory_manager_mut().attach_device(0x8010, Box::new(Ptm::new()));
+        other.get_memory_manager_mut().attach_device(0x8020, Box::new(Ptm::new()));
+
+        // Timer 1 with a period of $1234, LDAA #$12, BRA *
+        machine.write_memory(0x8012, &[0x12, 0x34]);
+        machine.load_program(0, &[0x86, 0x12, 0x20, 0xFE]);
+        machine.run(&[StopCondition::InfiniteLoop]);
+        timer_state = machine.get_memory_manager().find_device::<Ptm>().unwrap().save();
+
+        // Nothing is restored, not even the devices matched before the unknown one
+        saved = other.save_state();
+        assert_eq!(matches!(machine.load_state(&saved), Err(SaveStateError::UnknownDevice(name)) if name == "ptm@8020"), true);
+        assert_eq!(machine.get_memory_manager().find_device::<Ptm>().unwrap().save(), timer_state);
+        assert_eq!(machine.get_state().accumulator_a, 0x12);
+        assert_eq!(machine.read_memory(0, 1)[0], 0x86);
+
+        saved.devices.truncate(1);
+        saved.devices[0].data.pop();
+        assert_eq!(matches!(machine.load_state(&saved), Err(SaveStateError::Truncated)), true);
+        assert_eq!(machine.get_memory_manager().find_device::<Ptm>().unwrap().save(), timer_state);
+        assert_eq!(machine.get_state().accumulator_a, 0x12);
+    }
+
+    #[test]
+    fn test_reject_bad_save_states() {
+        let mut bytes = Machine::new().save_state().to_bytes();
+
+        assert_eq!(matches!(SaveState::from_bytes(&bytes[..100]), Err(SaveStateError::Truncated)), true);
+
+        bytes[9] = 0xFF;
+        assert_eq!(matches!(SaveState::from_bytes(&bytes), Err(SaveStateError::UnsupportedVersion(0xFF))), true);
+
+        bytes[0] = 0;
+        assert_eq!(matches!(SaveState::from_bytes(&bytes), Err(SaveStateError::BadMagic)), true);
+    }
+}
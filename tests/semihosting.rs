@@ -0,0 +1,125 @@
+#[cfg(test)]
+mod semihosting_tests {
+    use std::env;
+    use std::fs;
+    use std::io;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use momulator::machine::*;
+    use momulator::processor::*;
+    use momulator::semihosting::*;
+
+    const SEMIHOSTING_ADDRESS: u16 = 0xFF00;
+
+    // A writer which can still be read after it was moved into the device
+    #[derive(Clone)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn build_machine(input: &[u8], output: &SharedBuffer) -> Machine {
+        let mut machine = Machine::new();
+
+        machine.get_memory_manager_mut().attach_device(SEMIHOSTING_ADDRESS,
+                Box::new(Semihosting::with_console(Box::new(io::Cursor::new(input.to_vec())), Box::new(output.clone()))));
+
+        machine
+    }
+
+    #[test]
+    fn test_test_program_exits_with_success() {
+        let output = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+        let mut machine = build_machine(&[], &output);
+        let result: RunResult;
+
+        machine.load_program(0, &fs::read("./tests/test.bin").unwrap());
+        result = machine.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(2000)]);
+
+        assert_eq!(result.stop_reason, StopReason::Exited(0));
+    }
+
+    #[test]
+    fn test_exit_is_reported_once() {
+        let output = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+        let mut machine = build_machine(&[], &output);
+
+        // LDAA #1, STAA EXIT, BRA *
+        machine.load_program(0, &[0x86, 0x01, 0xB7, 0xFF, 0x05, 0x20, 0xFE]);
+
+        assert_eq!(machine.run(&[StopCondition::InfiniteLoop]).stop_reason, StopReason::Exited(1));
+        assert_eq!(machine.run(&[StopCondition::InfiniteLoop]).stop_reason, StopReason::InfiniteLoop(5));
+    }
+
+    #[test]
+    fn test_console_echo() {
+        let output = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+        let mut machine = build_machine(b"hello\n", &output);
+        let result: RunResult;
+
+        // Echo the console input until its end, then exit with status 0
+        machine.load_program(0, &[
+            0xF6, 0xFF, 0x00, 0xB6, 0xFF, 0x01, 0x26, 0x05, 0xF7, 0xFF, 0x00, 0x20, 0xF3,
+            0x7F, 0xFF, 0x05, 0x20, 0xFE
+        ]);
+        result = machine.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(1000)]);
+
+        assert_eq!(result.stop_reason, StopReason::Exited(0));
+        assert_eq!(output.0.lock().unwrap().as_slice(), b"hello\n");
+    }
+
+    #[test]
+    fn test_files() {
+        let output = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+        let mut machine = build_machine(&[], &output);
+        let path = env::temp_dir().join(format!("momulator_semihosting_{}.txt", std::process::id()));
+        let memory_manager = machine.get_memory_manager_mut();
+        let handle: u8;
+
+        // Write a file, then append to it
+        for command in [COMMAND_OPEN_WRITE, COMMAND_OPEN_APPEND] {
+            for byte in path.to_str().unwrap().bytes() {
+                memory_manager.write(SEMIHOSTING_ADDRESS + 3, &[byte]);
+            }
+
+            memory_manager.write(SEMIHOSTING_ADDRESS + 4, &[command]);
+            assert_eq!(memory_manager.read_byte(SEMIHOSTING_ADDRESS + 1), 0);
+            memory_manager.write(SEMIHOSTING_ADDRESS, &[b'A' + command]);
+            memory_manager.write(SEMIHOSTING_ADDRESS + 4, &[COMMAND_CLOSE]);
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "CD");
+
+        // Read it back to its end
+        for byte in path.to_str().unwrap().bytes() {
+            memory_manager.write(SEMIHOSTING_ADDRESS + 3, &[byte]);
+        }
+
+        memory_manager.write(SEMIHOSTING_ADDRESS + 4, &[COMMAND_OPEN_READ]);
+        handle = memory_manager.read_byte(SEMIHOSTING_ADDRESS + 2);
+
+        assert_eq!(handle >= 2, true);
+        assert_eq!(memory_manager.read_byte(SEMIHOSTING_ADDRESS), b'C');
+        assert_eq!(memory_manager.read_byte(SEMIHOSTING_ADDRESS), b'D');
+        assert_eq!(memory_manager.read_byte(SEMIHOSTING_ADDRESS), 0);
+        assert_eq!(memory_manager.read_byte(SEMIHOSTING_ADDRESS + 1), STATUS_END_OF_FILE);
+
+        // A closed handle cannot be used
+        memory_manager.write(SEMIHOSTING_ADDRESS + 4, &[COMMAND_CLOSE]);
+        memory_manager.write(SEMIHOSTING_ADDRESS + 2, &[handle]);
+        memory_manager.write(SEMIHOSTING_ADDRESS, b"E");
+
+        assert_eq!(memory_manager.read_byte(SEMIHOSTING_ADDRESS + 1), STATUS_ERROR);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,20 @@
+#[cfg(test)]
+mod seven_segment_tests {
+    use momulator::seven_segment::*;
+
+    #[test]
+    fn test_hex_digits() {
+        for value in 0..16 {
+            assert_eq!(decode_hex(encode_hex(value)), Some(value));
+        }
+
+        assert_eq!(decode_hex(encode_hex(0xA) | DECIMAL_POINT), Some(0xA));
+        assert_eq!(decode_hex(SEGMENT_A), None);
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(render(&[encode_hex(8) | DECIMAL_POINT, encode_hex(1)]), " _\n|_|   |\n|_|.  |");
+        assert_eq!(render(&[0, 0]), "\n\n");
+    }
+}
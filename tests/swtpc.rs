@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod swtpc_tests {
+    use momulator::machine::*;
+    use momulator::processor::*;
+    use momulator::profile::*;
+    use momulator::serial::*;
+    use momulator::swtpc;
+
+    /// A 512 byte monitor ROM: the reset vector is only at its end, $E1FE
+    fn build_rom(program: &[u8]) -> Vec<u8> {
+        let mut rom: Vec<u8> = vec![0; 512];
+
+        rom[..program.len()].copy_from_slice(program);
+        rom[0x1FE] = 0xE0;
+        rom[0x1FF] = 0x00;
+
+        rom
+    }
+
+    #[test]
+    fn test_boots_monitor() {
+        let port = BufferPort::new();
+        let mut machine: Machine;
+
+        // Reset the ACIA, print '*' and then '>' through the repeated registers, then try to clear
+        // the first byte of the ROM
+        machine = swtpc::build(&build_rom(&[
+            0x8E, 0xA0, 0x7F, 0x86, 0x03, 0xB7, 0x80, 0x04, 0x86, 0x15, 0xB7, 0x80, 0x04,
+            0x86, 0x2A, 0xB7, 0x80, 0x05, 0x86, 0x3E, 0xB7, 0x80, 0x07, 0x7F, 0xE0, 0x00, 0x20, 0xFE
+        ]), Box::new(port.clone())).unwrap();
+
+        assert_eq!(machine.get_state().program_counter, swtpc::ROM_ADDRESS);
+
+        machine.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(100)]);
+
+        assert_eq!(port.take_output(), b"*>".to_vec());
+        assert_eq!(machine.read_memory(swtpc::ROM_ADDRESS, 1)[0], 0x8E);
+        assert_eq!(machine.read_memory(0xFE00, 1)[0], 0x8E);
+    }
+
+    #[test]
+    fn test_monitor_reads_terminal() {
+        let port = BufferPort::new();
+        let mut machine: Machine;
+
+        // Reset the ACIA and echo one byte, waiting for RDRF
+        machine = swtpc::build(&build_rom(&[
+            0x86, 0x03, 0xB7, 0x80, 0x04, 0x86, 0x15, 0xB7, 0x80, 0x04,
+            0xB6, 0x80, 0x04, 0x84, 0x01, 0x27, 0xF9, 0xB6, 0x80, 0x05, 0xB7, 0x80, 0x05, 0x20, 0xFE
+        ]), Box::new(port.clone())).unwrap();
+        port.push_input(b"G");
+
+        machine.run(&[StopCondition::InfiniteLoop, StopCondition::InstructionBudget(100)]);
+
+        assert_eq!(port.take_output(), b"G".to_vec());
+    }
+
+    /// Boot the SWTBUG image named by SWTBUG_ROM, which is not part of the repository, through its
+    /// own I/O routines to the prompt
+    #[test]
+    fn test_boots_swtbug() {
+        let port = BufferPort::new();
+        let rom: Vec<u8>;
+        let mut machine: Machine;
+
+        rom = match std::env::var("SWTBUG_ROM") {
+            Ok(path) => std::fs::read(path).unwrap(),
+            Err(_) => return
+        };
+        machine = swtpc::build(&rom, Box::new(port.clone())).unwrap();
+
+        machine.run(&[StopCondition::InstructionBudget(100000)]);
+
+        assert_eq!(port.take_output().contains(&b'$'), true);
+    }
+
+    #[test]
+    fn test_rom_size() {
+        assert_eq!(matches!(swtpc::build(&[0; 1000], Box::new(BufferPort::new())), Err(ProfileError::RomSize(1000))), true);
+        assert_eq!(matches!(swtpc::build(&[0; 0x4000], Box::new(BufferPort::new())), Err(ProfileError::RomSize(0x4000))), true);
+        assert_eq!(MachineProfile::from_name("swtpc"), Some(MachineProfile::Swtpc));
+    }
+}
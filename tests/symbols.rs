@@ -0,0 +1,19 @@
+#[cfg(test)]
+mod symbols_tests {
+    use momulator::symbols::*;
+
+    #[test]
+    fn test_parse_cross_reference() {
+        let listing = "0007 0000 7e 00 03           [ 3 ] \tjmp l_test_aba_adc\n\
+                       l_start    0000 *0006\n\
+                       l_test_tst 030d *0517 0512\n\
+                       243: Warning --- Indexed Addressing Assumed\n";
+        let symbols = SymbolTable::parse(listing);
+
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols.get_address("l_test_tst"), Some(0x30D));
+        assert_eq!(symbols.get_name(0), Some("l_start"));
+        assert_eq!(symbols.get_nearest(0x310), Some(("l_test_tst", 3)));
+        assert_eq!(symbols.format_address(0x310), "l_test_tst+3 ($0310)");
+    }
+}
@@ -1,5 +1,8 @@
 		nam test_program
 
+* The exit register of the semihosting device (momulator run --semihosting 0xFF00)
+SEMIHOST_EXIT equ $FF05
+
 		org $0
 
 * The entry point of the program
@@ -548,8 +551,18 @@ l_tsx_txs_error
 
 * Something went wrong
 l_error
-	jmp l_error
+	jmp l_error_exit
 
 * Everything was OK!
 l_success
-	jmp l_success
+	jmp l_success_exit
+
+* Report the result as the exit status, then stop here when there is no semihosting device
+l_error_exit
+	ldaa #1
+	staa SEMIHOST_EXIT
+	bra *
+l_success_exit
+	ldaa #0
+	staa SEMIHOST_EXIT
+	bra *
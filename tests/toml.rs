@@ -0,0 +1,47 @@
+#[cfg(test)]
+mod toml_tests {
+    use momulator::toml::*;
+
+    #[test]
+    fn test_parse() {
+        let document = TomlValue::parse("# A board
+name = \"Test \\\"board\\\"\"   # trailing comment
+clock_rate = 1_000_000
+paths = ['C:\\roms', \"b\", ]
+
+[cpu]
+fast = true
+offset = -0x10
+
+[[device]]
+type = \"acia\"
+address = 0x8004
+
+[[device]]
+address = 0b1000
+[device.options]
+level = 0o17
+").unwrap();
+        let devices: &[TomlValue] = document.get("device").unwrap().as_array().unwrap();
+
+        assert_eq!(document.get("name").unwrap().as_str(), Some("Test \"board\""));
+        assert_eq!(document.get("clock_rate").unwrap().as_u64(), Some(1000000));
+        assert_eq!(document.get("paths"), Some(&TomlValue::Array(vec![TomlValue::String(String::from("C:\\roms")), TomlValue::String(String::from("b"))])));
+        assert_eq!(document.get("cpu").unwrap().get("fast").unwrap().as_bool(), Some(true));
+        assert_eq!(document.get("cpu").unwrap().get("offset").unwrap().as_i64(), Some(-16));
+        assert_eq!(document.get("cpu").unwrap().get("offset").unwrap().as_u64(), None);
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].get("address").unwrap().as_u64(), Some(0x8004));
+        assert_eq!(devices[1].get("address").unwrap().as_u64(), Some(8));
+        assert_eq!(devices[1].get("options").unwrap().get("level").unwrap().as_u64(), Some(15));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(TomlValue::parse("a = 1\na = 2"), Err(String::from("Duplicate key 'a' on line 2")));
+        assert_eq!(TomlValue::parse("a = { b = 1 }"), Err(String::from("Inline tables are not supported on line 1")));
+        assert_eq!(TomlValue::parse("\n\na = \"open"), Err(String::from("Unterminated string on line 3")));
+        assert_eq!(TomlValue::parse("a = 12 34"), Err(String::from("Unexpected text after the value on line 1")));
+        assert_eq!(TomlValue::parse("a = 0xZZ"), Err(String::from("Invalid value '0xZZ' on line 1")));
+    }
+}
@@ -0,0 +1,75 @@
+#[cfg(test)]
+mod trace_tests {
+    use momulator::machine::*;
+    use momulator::processor::*;
+    use momulator::trace::*;
+    use std::io;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    // A writer which can still be read after it was moved into a tracer
+    #[derive(Clone)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Run LDAA #$80, LDX $1234, BRA * and return the trace
+    fn trace_program(format: TraceFormat, accumulator_value: u8) -> Vec<u8> {
+        let buffer = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+        let mut machine = Machine::new();
+
+        machine.load_program(0x10, &[0x86, accumulator_value, 0xFE, 0x12, 0x34, 0x20, 0xFE]);
+        machine.get_processor_mut().set_tracer(Tracer::new(format, Box::new(buffer.clone())));
+        machine.run(&[StopCondition::InfiniteLoop]);
+        machine.get_processor_mut().take_tracer().unwrap().finish().unwrap();
+
+        let data = buffer.0.lock().unwrap().clone();
+        data
+    }
+
+    #[test]
+    fn test_text_trace() {
+        let text = String::from_utf8(trace_program(TraceFormat::Text, 0x80)).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "0010  86 80     LDAA #$80     A=00 B=00 X=0000 SP=0000 CCR=00 ...... CYC=0");
+        assert_eq!(lines[1].starts_with("0012  FE 12 34  LDX $1234     A=80"), true);
+        assert_eq!(lines[1].contains("CCR=08 ..N..."), true);
+        assert_eq!(lines[2].starts_with("0015  20 FE     BRA $0015"), true);
+    }
+
+    #[test]
+    fn test_binary_and_text_traces_match() {
+        let text = parse_trace(&trace_program(TraceFormat::Text, 0x80)).unwrap();
+        let binary = parse_trace(&trace_program(TraceFormat::Binary, 0x80)).unwrap();
+
+        assert_eq!(binary.len(), 3);
+        assert_eq!(binary[1].bytes, vec![0xFE, 0x12, 0x34]);
+        assert_eq!(diff_traces(&text, &binary), None);
+    }
+
+    #[test]
+    fn test_diff_reports_first_divergence() {
+        let left = parse_trace(&trace_program(TraceFormat::Text, 0x80)).unwrap();
+        let right = parse_trace(&trace_program(TraceFormat::Binary, 0x81)).unwrap();
+        let divergence = diff_traces(&left, &right).unwrap();
+
+        // The operand differs, so the very first record already diverges
+        assert_eq!(divergence.index, 0);
+        assert_eq!(divergence.right.unwrap().bytes, vec![0x86, 0x81]);
+
+        let divergence = diff_traces(&left, &left[..2]).unwrap();
+        assert_eq!(divergence.index, 2);
+        assert_eq!(divergence.right, None);
+    }
+}
@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tui_tests {
+    use momulator::debugger::*;
+    use momulator::machine::*;
+    use momulator::symbols::*;
+    use momulator::tui::*;
+
+    // main: LDAA #$01, INCA, STAA $80, BRA *
+    fn create_tui() -> Tui {
+        let mut machine = Machine::new();
+        let mut state: momulator::processor::ProcessorState;
+
+        machine.load_program(0x100, &[0x86, 0x01, 0x4C, 0x97, 0x80, 0x20, 0xFE]);
+        state = machine.get_state();
+        state.stack_pointer = 0x1FF;
+        machine.get_processor_mut().set_state(state);
+
+        Tui::new(Debugger::new(machine, SymbolTable::new()))
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(parse_keys(b"s\x1b[A\x1b[6~\x1b[1;5C\r\x7f\x1b"),
+                vec![Key::Character('s'), Key::Up, Key::PageDown, Key::Enter, Key::Backspace, Key::Escape]);
+    }
+
+    #[test]
+    fn test_render() {
+        let tui = create_tui();
+        let screen: Vec<String> = tui.render(80, 24);
+
+        assert_eq!(screen.len(), 24);
+        assert_eq!(screen[0].starts_with("-- Disassembly"), true);
+        assert_eq!(screen.iter().any(|line| line.contains("\x1b[7m") && line.contains("0100")), true);
+        assert_eq!(screen.iter().any(|line| line.contains("PC $0100")), true);
+        assert_eq!(screen.iter().any(|line| line.starts_with("-- Memory $0000")), true);
+    }
+
+    #[test]
+    fn test_keys_drive_the_debugger() {
+        let mut tui = create_tui();
+
+        tui.handle_key(Key::Character('s'));
+        assert_eq!(tui.get_debugger().get_machine().get_state().program_counter, 0x102);
+
+        // Move the cursor to the store and break on it
+        tui.handle_key(Key::Down);
+        tui.handle_key(Key::Character('b'));
+        assert_eq!(tui.get_debugger().get_machine().get_processor().get_breakpoints().has_breakpoint_at(0x103), true);
+
+        tui.handle_key(Key::Character('c'));
+        while tui.is_running() {
+            tui.run_slice();
+        }
+        assert_eq!(tui.get_debugger().get_machine().get_state().program_counter, 0x103);
+        assert_eq!(tui.get_status().starts_with("Breakpoint 1"), true);
+
+        for key in parse_keys(b":m $100 2\r") {
+            tui.handle_key(key);
+        }
+        assert_eq!(tui.get_status().starts_with("0100  86 01"), true);
+
+        tui.handle_key(Key::Character('q'));
+        assert_eq!(tui.should_quit(), true);
+    }
+}
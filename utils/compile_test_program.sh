@@ -1,7 +1,7 @@
 #!/bin/bash
 
-# Assemble the program
-./utils/as0 tests/test.asm -l cre c s
+# Assemble the program, keeping the listing for the line table and coverage tests
+./utils/as0 tests/test.asm -l cre c s > tests/test.lst
 
 # Extract the binary from the S19 record
 objcopy --input-target=srec --output-target=binary tests/test.s19 tests/test.bin